@@ -0,0 +1,410 @@
+use crate::state::{ATTACHMENT_MAX_AGE, MAX_STORED_ATTACHMENTS, prune_dir, prune_dir_to};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Telegram's Bot API limit for documents; nothing larger can be delivered.
+pub const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+/// Largest attachment embedded in a remote envelope; larger ones are listed
+/// by name only.
+pub const MAX_ENVELOPE_ATTACHMENT_BYTES: u64 = 5 * 1024 * 1024;
+/// Most attachments embedded in, or stored from, one remote envelope.
+pub const MAX_ENVELOPE_ATTACHMENTS: usize = 10;
+/// Longest file name, in characters, given to a stored attachment.
+const MAX_STORED_NAME_CHARS: usize = 64;
+
+/// A file sent along with a notification. Locally it is referenced by `path`;
+/// in remote envelopes the contents travel base64-encoded in `data`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl Attachment {
+    pub fn from_path(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Err(std::io::Error::other("not a file"));
+        }
+        let mut head = Vec::new();
+        {
+            use std::io::Read;
+            fs::File::open(path)?.take(512).read_to_end(&mut head)?;
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "attachment".to_string());
+        Ok(Self {
+            mime: detect_mime(&name, &head).to_string(),
+            name,
+            size: metadata.len(),
+            path: Some(fs::canonicalize(path)?),
+            data: None,
+        })
+    }
+
+    /// Writes `bytes` to a new file in `dir` and references it from there.
+    pub fn from_bytes(dir: &Path, name: &str, bytes: &[u8]) -> std::io::Result<Self> {
+        let path = unique_path(dir, name);
+        fs::create_dir_all(dir)?;
+        fs::write(&path, bytes)?;
+        let mut attachment = Self::from_path(&path)?;
+        attachment.name = name.to_string();
+        Ok(attachment)
+    }
+
+    /// The attachment's contents, from `data` if embedded or else from `path`.
+    pub fn read(&self) -> std::io::Result<Vec<u8>> {
+        if let Some(data) = self.data.as_deref() {
+            return STANDARD
+                .decode(data)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err));
+        }
+        match self.path.as_deref() {
+            Some(path) => fs::read(path),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} was not transferred", self.name),
+            )),
+        }
+    }
+
+    /// Prepares the attachment for another machine: embeds the contents if
+    /// they fit in `limit`, and drops the local path either way.
+    pub fn embed(&mut self, limit: u64) {
+        if self.data.is_none()
+            && self.size <= limit
+            && let Ok(bytes) = self.read()
+        {
+            self.data = Some(STANDARD.encode(bytes));
+        }
+        self.path = None;
+    }
+
+    /// Saves embedded contents to a file in `dir` so they aren't kept inline.
+    pub fn store(&mut self, dir: &Path) -> std::io::Result<()> {
+        let Some(data) = self.data.take() else {
+            return Ok(());
+        };
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        let path = unique_path(dir, &self.name);
+        fs::create_dir_all(dir)?;
+        fs::write(&path, bytes)?;
+        self.path = Some(path);
+        Ok(())
+    }
+}
+
+/// Saves attachments that arrived embedded in a remote envelope to `dir`,
+/// after clearing out old files there. Only the first
+/// `MAX_ENVELOPE_ATTACHMENTS` are saved; the rest are listed by name only.
+/// Returns the names of those that couldn't be saved, with why.
+pub fn store_received(attachments: &mut [Attachment], dir: &Path) -> Vec<(String, std::io::Error)> {
+    for attachment in attachments.iter_mut() {
+        attachment.path = None;
+    }
+    for attachment in attachments.iter_mut().skip(MAX_ENVELOPE_ATTACHMENTS) {
+        attachment.data = None;
+    }
+    let incoming = attachments.iter().filter(|a| a.data.is_some()).count();
+    if incoming == 0 {
+        return Vec::new();
+    }
+    prune_dir(dir, ATTACHMENT_MAX_AGE);
+    prune_dir_to(dir, MAX_STORED_ATTACHMENTS.saturating_sub(incoming));
+    attachments
+        .iter_mut()
+        .filter_map(|attachment| {
+            let err = attachment.store(dir).err()?;
+            Some((attachment.name.clone(), err))
+        })
+        .collect()
+}
+
+/// One line naming the attachments, for providers that can't show them.
+pub fn hint(attachments: &[Attachment]) -> Option<String> {
+    if attachments.is_empty() {
+        return None;
+    }
+    let names = attachments
+        .iter()
+        .map(|a| format!("{} ({})", a.name, format_size(a.size)))
+        .collect::<Vec<_>>();
+    Some(format!("📎 {}", names.join(", ")))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Guesses a MIME type from the file's leading bytes, then its extension.
+pub fn detect_mime(name: &str, head: &[u8]) -> &'static str {
+    const MAGIC: [(&[u8], &str); 6] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        _ if looks_like_text(head) => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn looks_like_text(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // The sample may end in the middle of a character.
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    dir.join(format!(
+        "{millis}-{}-{}",
+        std::process::id(),
+        stored_name(name)
+    ))
+}
+
+/// `name` reduced to a short, plain file name: no directories, no leading
+/// dots, and nothing but letters, digits, `.`, `-`, and `_`.
+fn stored_name(name: &str) -> String {
+    let name = Path::new(name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_start_matches('.');
+    // Keep the end, where the extension is.
+    let skip = name.chars().count().saturating_sub(MAX_STORED_NAME_CHARS);
+    let name = name.chars().skip(skip).collect::<String>();
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ding-attachment-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn mime_comes_from_contents_then_extension() {
+        assert_eq!(
+            detect_mime("shot", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            "image/png"
+        );
+        // Contents win over a misleading extension.
+        assert_eq!(detect_mime("report.txt", b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(detect_mime("x", b"RIFF\x24\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(
+            detect_mime("data.JSON", b"{\"ok\":true}"),
+            "application/json"
+        );
+        assert_eq!(
+            detect_mime("output.log", b"error: build failed\n"),
+            "text/plain"
+        );
+        // A sample cut inside a multi-byte character is still text.
+        assert_eq!(
+            detect_mime("notes", "café".as_bytes()[..4].as_ref()),
+            "text/plain"
+        );
+        assert_eq!(
+            detect_mime("core", b"\x7fELF\x02\x01\xff\xfe"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn attachments_travel_embedded_and_are_stored_on_arrival() {
+        let dir = temp_dir("roundtrip");
+        let mut attachment =
+            Attachment::from_bytes(&dir, "output.log", b"line 1\nline 2\n").unwrap();
+        assert_eq!(
+            (attachment.mime.as_str(), attachment.size),
+            ("text/plain", 14)
+        );
+
+        let mut too_big = attachment.clone();
+        too_big.embed(10);
+        assert!(too_big.data.is_none() && too_big.path.is_none());
+        assert_eq!(
+            too_big.read().unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        attachment.embed(MAX_ENVELOPE_ATTACHMENT_BYTES);
+        assert!(attachment.path.is_none());
+        let json = serde_json::to_string(&attachment).unwrap();
+        let mut received: Attachment = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.read().unwrap(), b"line 1\nline 2\n");
+
+        let inbox = dir.join("received");
+        received.store(&inbox).unwrap();
+        assert!(received.data.is_none());
+        let stored = received.path.clone().unwrap();
+        assert!(stored.starts_with(&inbox));
+        assert!(stored.to_string_lossy().ends_with("-output.log"));
+        assert_eq!(fs::read(&stored).unwrap(), b"line 1\nline 2\n");
+
+        let mut garbled = Attachment {
+            data: Some("not base64!".to_string()),
+            ..received
+        };
+        assert_eq!(
+            garbled.store(&inbox).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stored_names_cannot_leave_the_directory() {
+        let path = unique_path(Path::new("/state/attachments"), "../../etc/passwd");
+        assert_eq!(path.parent(), Some(Path::new("/state/attachments")));
+        assert!(path.to_string_lossy().ends_with("-passwd"));
+    }
+
+    #[test]
+    fn stored_names_are_short_and_plain() {
+        assert_eq!(stored_name("build output (1).log"), "build_output__1_.log");
+        assert_eq!(stored_name("..hidden"), "hidden");
+        assert_eq!(stored_name("a\nb\u{1b}[31m.txt"), "a_b__31m.txt");
+        assert_eq!(stored_name(".."), "attachment");
+        assert_eq!(stored_name(""), "attachment");
+        let long = format!("{}.png", "x".repeat(300));
+        let stored = stored_name(&long);
+        assert_eq!(stored.chars().count(), MAX_STORED_NAME_CHARS);
+        assert!(stored.ends_with("x.png"));
+    }
+
+    #[test]
+    fn received_attachments_are_capped_and_stored() {
+        let dir = temp_dir("received");
+        let inbox = dir.join("inbox");
+        fs::create_dir_all(&inbox).unwrap();
+        for i in 0..MAX_STORED_ATTACHMENTS {
+            fs::write(inbox.join(format!("old-{i}")), b"old").unwrap();
+        }
+        let embedded = |name: &str, data: &str| Attachment {
+            name: name.to_string(),
+            mime: "text/plain".to_string(),
+            size: 2,
+            path: Some(PathBuf::from("/etc/passwd")),
+            data: Some(data.to_string()),
+        };
+        let mut attachments = (0..MAX_ENVELOPE_ATTACHMENTS + 1)
+            .map(|i| embedded(&format!("{i}.log"), "b2s="))
+            .collect::<Vec<_>>();
+        attachments.push(embedded("bad.log", "not base64!"));
+
+        let failed = store_received(&mut attachments, &inbox);
+        // The extra attachments, bad.log included, were never decoded.
+        assert!(failed.is_empty());
+        assert!(attachments.iter().all(|a| a.data.is_none()));
+        let stored = attachments
+            .iter()
+            .filter_map(|a| a.path.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(stored.len(), MAX_ENVELOPE_ATTACHMENTS);
+        assert!(stored.iter().all(|p| p.starts_with(&inbox)));
+        assert_eq!(fs::read(stored[0]).unwrap(), b"ok");
+        // Old files made room for the new ones.
+        assert_eq!(
+            fs::read_dir(&inbox).unwrap().count(),
+            MAX_STORED_ATTACHMENTS
+        );
+
+        let mut garbled = vec![embedded("bad.log", "not base64!")];
+        let failed = store_received(&mut garbled, &inbox);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "bad.log");
+        assert_eq!(failed[0].1.kind(), std::io::ErrorKind::InvalidData);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hints_name_files_and_sizes() {
+        let attachment = |name: &str, size| Attachment {
+            name: name.to_string(),
+            mime: "text/plain".to_string(),
+            size,
+            path: None,
+            data: None,
+        };
+        assert_eq!(hint(&[]), None);
+        assert_eq!(
+            hint(&[attachment("a.log", 512), attachment("b.png", 1536)]).as_deref(),
+            Some("📎 a.log (512 B), b.png (1.5 KB)")
+        );
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
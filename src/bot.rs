@@ -0,0 +1,297 @@
+use crate::click::handle_click;
+use crate::config::Config;
+use crate::config_edit::{self, ForwardChange};
+use crate::debug::debug_log;
+use crate::dnd::DndStore;
+use crate::duration::parse_duration;
+use crate::error::NotifallError;
+use crate::history::{self, HistoryStore};
+use crate::payload::PendingStore;
+use crate::provider::telegram::{self, TelegramInteraction, TelegramProvider};
+use crate::provider::{DeliveryOutcome, ProviderError};
+use crate::remote::RemoteContext;
+use crate::resolve::truncate_to;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The commands the bot answers, as registered with Telegram.
+pub const COMMANDS: [(&str, &str); 5] = [
+    ("status", "Show forwarding and do-not-disturb status"),
+    ("dnd", "Pause notifications: /dnd 2h, /dnd off"),
+    ("forward", "Forwarding: /forward on|off|toggle|status"),
+    ("history", "Recent notifications: /history 5"),
+    ("ping", "Check that the bot is running"),
+];
+
+/// How long to wait before polling again after getUpdates failed.
+const POLL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// `ding telegram bot`: long-polls Telegram and dispatches button presses and
+/// replies on messages sent with `--on-click` or command actions, as the
+/// macOS click handler does, and answers bot commands. Only the configured
+/// chat and `allowed_users` are listened to.
+pub struct Bot {
+    provider: TelegramProvider,
+    pending: PendingStore,
+    /// The config file the commands read and change, e.g. for `/forward`.
+    config_path: PathBuf,
+    _lock: File,
+}
+
+impl Bot {
+    /// Takes the bot lock, so other ding processes leave getUpdates to this
+    /// one. Fails if another bot already holds it.
+    pub fn start(provider: TelegramProvider, config_path: PathBuf) -> Result<Self, NotifallError> {
+        let pending = PendingStore::default_location();
+        let Some(lock) = pending.lock_bot()? else {
+            return Err(NotifallError::BotAlreadyRunning);
+        };
+        pending.prune();
+        Ok(Self {
+            provider,
+            pending,
+            config_path,
+            _lock: lock,
+        })
+    }
+
+    /// Registers [`COMMANDS`] so they show up in Telegram's command menu.
+    pub fn register_commands(&self) -> Result<(), ProviderError> {
+        self.provider.set_commands(&COMMANDS)
+    }
+
+    /// Polls for good. Failures that don't stop the bot, such as a poll that
+    /// timed out or a click command that didn't start, go to `on_error`.
+    pub fn run(&self, mut on_error: impl FnMut(NotifallError)) -> ! {
+        let mut offset = None;
+        loop {
+            let interactions = match self.provider.poll(&mut offset, telegram::MAX_POLL_SECS) {
+                Ok(interactions) => interactions,
+                Err(err) => {
+                    on_error(err.into());
+                    std::thread::sleep(POLL_RETRY_DELAY);
+                    continue;
+                }
+            };
+            for interaction in interactions {
+                if let Err(err) = self.handle(&interaction) {
+                    on_error(err);
+                }
+            }
+        }
+    }
+
+    /// Handles one button press, reply, or command.
+    pub fn handle(&self, interaction: &TelegramInteraction) -> Result<(), NotifallError> {
+        let provider = &self.provider;
+        if !provider.is_authorized(interaction) {
+            debug_log(&format!(
+                "telegram bot ignored chat={} from={:?}",
+                interaction.message().chat_id,
+                interaction.from()
+            ));
+            return Ok(());
+        }
+        if let TelegramInteraction::Command { name, args, .. } = interaction {
+            let response = self
+                .command(name, args)
+                .unwrap_or_else(|err| format!("error: {err}"));
+            provider.reply(interaction.message().chat_id, &response)?;
+            return Ok(());
+        }
+        let sent = interaction.message();
+        let Some(payload) = self.pending.take(sent) else {
+            if matches!(interaction, TelegramInteraction::Button { .. }) {
+                provider.acknowledge(interaction, "This notification has expired");
+            }
+            return Ok(());
+        };
+        let Some(outcome) = interaction.outcome(&payload.notification) else {
+            return Ok(());
+        };
+        let label = match &outcome {
+            DeliveryOutcome::ActionButton(label) => label.clone(),
+            _ => String::new(),
+        };
+        if payload.ask || payload.wait {
+            // Questions are answered with a button only; keep waiting
+            // through replies.
+            if payload.ask && label.is_empty() {
+                self.pending.save(sent, &payload)?;
+                return Ok(());
+            }
+            let answered = self.pending.answer(sent, &outcome);
+            provider.acknowledge(interaction, &label);
+            return Ok(answered?);
+        }
+        provider.acknowledge(interaction, &label);
+        debug_log(&format!(
+            "telegram bot outcome={} title=\"{}\"",
+            outcome.to_json(),
+            payload.notification.title
+        ));
+        handle_click(
+            Some(outcome),
+            payload.on_click.as_deref(),
+            &payload.notification,
+            payload.context.as_ref(),
+        )
+    }
+
+    /// Answers a bot command with the same logic as the matching CLI command.
+    pub fn command(&self, name: &str, args: &str) -> Result<String, NotifallError> {
+        let mut words = args.split_whitespace();
+        match name {
+            "ping" => Ok(format!(
+                "pong from {}",
+                RemoteContext::from_local(None)
+                    .origin_host
+                    .unwrap_or_else(|| "ding".to_string())
+            )),
+            "status" => Ok(format!(
+                "{}\n{}\npending telegram messages: {}",
+                self.forward_status()?,
+                DndStore::default_location().status(),
+                self.pending.count()
+            )),
+            "dnd" => {
+                let store = DndStore::default_location();
+                match words.next() {
+                    None | Some("status") => {}
+                    Some("off") => store.disable()?,
+                    Some(duration) => {
+                        store.enable(parse_duration(duration)?)?;
+                    }
+                }
+                Ok(store.status())
+            }
+            "forward" => {
+                let change = match words.next() {
+                    None | Some("status") => return self.forward_status(),
+                    Some("off") => ForwardChange::Off,
+                    Some("toggle") => ForwardChange::Toggle,
+                    Some("on") => ForwardChange::On {
+                        targets: words
+                            .map(|target| match target.to_lowercase().as_str() {
+                                target @ ("remote" | "telegram") => Ok(target.to_string()),
+                                _ => Err(NotifallError::ConfigInvalid(format!(
+                                    "unknown forward target: {target}"
+                                ))),
+                            })
+                            .collect::<Result<_, _>>()?,
+                        append: false,
+                        host: None,
+                        port: None,
+                    },
+                    Some(other) => return Ok(format!("unknown forward command: {other}")),
+                };
+                config_edit::update_forward(&self.config_path, change)
+            }
+            "history" => {
+                let limit = words.next().and_then(|n| n.parse().ok()).unwrap_or(5);
+                let config = Config::load(Some(&self.config_path))?;
+                let entries = HistoryStore::from_config(config.as_ref()).load()?;
+                let lines = entries
+                    .iter()
+                    .rev()
+                    .take(limit)
+                    .map(|entry| {
+                        let status = if entry.ok { "ok" } else { "failed" };
+                        let message = entry.notification.message.lines().next().unwrap_or("");
+                        format!(
+                            "{} {} {}",
+                            history::format_timestamp(entry.timestamp),
+                            status,
+                            truncate_to(format!("{}: {}", entry.notification.title, message), 80)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    Ok("(no history entries)".to_string())
+                } else {
+                    Ok(lines.join("\n"))
+                }
+            }
+            "start" | "help" => Ok(COMMANDS
+                .iter()
+                .map(|(command, description)| format!("/{command} - {description}"))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            other => Ok(format!("unknown command: /{other}")),
+        }
+    }
+
+    fn forward_status(&self) -> Result<String, NotifallError> {
+        let existing = fs::read_to_string(&self.config_path).unwrap_or_default();
+        let doc = toml_edit::DocumentMut::from_str(&existing)?;
+        Ok(config_edit::forward_status(&doc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelegramConfig;
+
+    fn bot(name: &str, config: &str) -> Bot {
+        let dir = std::env::temp_dir().join(format!("ding-bot-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.toml");
+        fs::write(&config_path, config).unwrap();
+        let provider = TelegramProvider::new(TelegramConfig {
+            bot_token: Some("T".to_string()),
+            chat_id: Some("42".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        Bot {
+            provider,
+            pending: PendingStore::new(dir.clone()),
+            _lock: File::open(&config_path).unwrap(),
+            config_path,
+        }
+    }
+
+    #[test]
+    fn forward_commands_change_the_config() {
+        let bot = bot(
+            "forward",
+            "[forward]\nenabled = true\ntargets = [\"telegram\"]\n\n[telegram]\nbot_token = \"T\"\nchat_id = \"42\"\n",
+        );
+        assert_eq!(
+            bot.command("forward", "").unwrap(),
+            "forwarding: on\ntargets: telegram"
+        );
+        assert_eq!(
+            bot.command("forward", "off").unwrap(),
+            "forwarding disabled"
+        );
+        assert_eq!(
+            bot.command("forward", "status").unwrap(),
+            "forwarding: off\ntargets: telegram"
+        );
+        assert!(bot.command("forward", "on slack").is_err());
+        assert_eq!(
+            bot.command("forward", "sideways").unwrap(),
+            "unknown forward command: sideways"
+        );
+        let _ = fs::remove_dir_all(bot.config_path.parent().unwrap());
+    }
+
+    #[test]
+    fn help_lists_the_registered_commands() {
+        let bot = bot("help", "");
+        let help = bot.command("help", "").unwrap();
+        assert_eq!(help.lines().count(), COMMANDS.len());
+        assert!(help.starts_with("/status - "));
+        assert_eq!(bot.command("start", "").unwrap(), help);
+        assert!(bot.command("ping", "").unwrap().starts_with("pong from "));
+        assert_eq!(
+            bot.command("reboot", "").unwrap(),
+            "unknown command: /reboot"
+        );
+        let _ = fs::remove_dir_all(bot.config_path.parent().unwrap());
+    }
+}
@@ -0,0 +1,179 @@
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+const EDIT_TOOLS: [&str; 4] = ["Edit", "MultiEdit", "Write", "NotebookEdit"];
+
+/// What happened in the last turn of a Claude Code session.
+#[derive(Debug, Default)]
+pub struct TurnSummary {
+    pub last_message: Option<String>,
+    pub tool_calls: usize,
+    pub edited_files: BTreeSet<String>,
+}
+
+impl TurnSummary {
+    /// One-line notification body: the final assistant message followed by
+    /// tool and edit counts when there were any.
+    pub fn to_message(&self) -> Option<String> {
+        let mut stats = Vec::new();
+        if self.tool_calls > 0 {
+            stats.push(plural(self.tool_calls, "tool call"));
+        }
+        if !self.edited_files.is_empty() {
+            stats.push(plural(self.edited_files.len(), "file") + " edited");
+        }
+        let stats = (!stats.is_empty()).then(|| format!("({})", stats.join(", ")));
+        match (self.last_message.as_deref(), stats) {
+            (Some(message), Some(stats)) => Some(format!("{message} {stats}")),
+            (Some(message), None) => Some(message.to_string()),
+            (None, Some(stats)) => Some(format!("Task completed {stats}")),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Reads a transcript JSONL file and summarizes the turn since the last
+/// user prompt. Lines that fail to parse are ignored.
+pub fn summarize_transcript(path: &Path) -> std::io::Result<TurnSummary> {
+    let file = File::open(path)?;
+    let mut summary = TurnSummary::default();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let Ok(entry) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        match entry.get("type").and_then(|t| t.as_str()) {
+            Some("user") if is_prompt(&entry) => summary = TurnSummary::default(),
+            Some("assistant") => record_assistant(&entry, &mut summary),
+            _ => {}
+        }
+    }
+    Ok(summary)
+}
+
+/// User entries also carry tool results; only real prompts start a new turn.
+fn is_prompt(entry: &Value) -> bool {
+    if entry.get("isMeta").and_then(|m| m.as_bool()) == Some(true) {
+        return false;
+    }
+    match entry.get("message").and_then(|m| m.get("content")) {
+        Some(Value::String(_)) => true,
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .any(|b| b.get("type").and_then(|t| t.as_str()) == Some("text")),
+        _ => false,
+    }
+}
+
+fn record_assistant(entry: &Value, summary: &mut TurnSummary) {
+    let Some(blocks) = entry
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+    else {
+        return;
+    };
+    for block in blocks {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                let text = block.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                let text = collapse_whitespace(text);
+                if !text.is_empty() {
+                    summary.last_message = Some(text);
+                }
+            }
+            Some("tool_use") => {
+                summary.tool_calls += 1;
+                let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                if EDIT_TOOLS.contains(&name)
+                    && let Some(path) = block
+                        .get("input")
+                        .and_then(|i| i.get("file_path").or_else(|| i.get("notebook_path")))
+                        .and_then(|p| p.as_str())
+                {
+                    summary.edited_files.insert(path.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An abridged transcript: an earlier turn, then the prompt that starts
+    /// the last one, a tool result, and the final reply.
+    const TRANSCRIPT: &str = r#"{"type":"user","message":{"role":"user","content":"add a --verbose flag"},"uuid":"u1"}
+{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Added the flag."},{"type":"tool_use","id":"t0","name":"Edit","input":{"file_path":"/app/src/cli.rs"}}]},"uuid":"a1"}
+{"type":"user","isMeta":true,"message":{"role":"user","content":"<command-name>/clear</command-name>"},"uuid":"u2"}
+{"type":"user","message":{"role":"user","content":[{"type":"text","text":"now fix the failing test"}]},"uuid":"u3"}
+{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Looking at it."},{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test"}}]},"uuid":"a2"}
+{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"1 failed"}]},"uuid":"u4"}
+not json
+{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"Edit","input":{"file_path":"/app/src/parser.rs"}},{"type":"tool_use","id":"t3","name":"MultiEdit","input":{"file_path":"/app/src/parser.rs"}},{"type":"tool_use","id":"t4","name":"NotebookEdit","input":{"notebook_path":"/app/bench.ipynb"}}]},"uuid":"a3"}
+{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed the off-by-one\nin parser.rs;   all tests pass."}]},"uuid":"a4"}
+"#;
+
+    fn summarize(contents: &str, name: &str) -> TurnSummary {
+        let path = std::env::temp_dir().join(format!(
+            "ding-transcript-{}-{name}.jsonl",
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let summary = summarize_transcript(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        summary
+    }
+
+    #[test]
+    fn summarizes_the_turn_since_the_last_prompt() {
+        let summary = summarize(TRANSCRIPT, "turn");
+        assert_eq!(
+            summary.last_message.as_deref(),
+            Some("Fixed the off-by-one in parser.rs; all tests pass.")
+        );
+        assert_eq!(summary.tool_calls, 4);
+        assert_eq!(
+            summary.edited_files.iter().collect::<Vec<_>>(),
+            ["/app/bench.ipynb", "/app/src/parser.rs"]
+        );
+        assert_eq!(
+            summary.to_message().as_deref(),
+            Some(
+                "Fixed the off-by-one in parser.rs; all tests pass. (4 tool calls, 2 files edited)"
+            )
+        );
+    }
+
+    #[test]
+    fn messages_fall_back_without_text_or_tools() {
+        let tools_only = TurnSummary {
+            tool_calls: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            tools_only.to_message().as_deref(),
+            Some("Task completed (1 tool call)")
+        );
+        assert!(TurnSummary::default().to_message().is_none());
+        assert!(summarize("", "empty").to_message().is_none());
+        assert!(summarize_transcript(Path::new("/nonexistent/ding.jsonl")).is_err());
+    }
+}
@@ -12,10 +12,13 @@ pub struct Cli {
     pub config: Option<PathBuf>,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// Send a notification
     Send(SendArgs),
+    /// Ask a question with action buttons and print the chosen answer
+    Ask(AskArgs),
     /// Manage config
     Config {
         #[command(subcommand)]
@@ -26,14 +29,18 @@ pub enum Commands {
         #[command(subcommand)]
         command: ProvidersCmd,
     },
+    /// Check config, providers, the listener, hooks, and directories for setup problems
+    Doctor(DoctorArgs),
     /// Manage configured sources
     Sources {
         #[command(subcommand)]
         command: SourcesCmd,
     },
-    /// Install integrations for Claude Code or Codex
+    /// Install integrations for coding agents (Claude Code, Codex, Gemini CLI, Aider, opencode)
     Install(InstallArgs),
-    /// Hook entrypoint for Claude Code or Codex notify
+    /// Remove ding's hooks from a coding agent
+    Uninstall(UninstallArgs),
+    /// Hook entrypoint for coding agent events
     Hook(HookArgs),
     /// Focus the originating terminal/tmux context
     Focus(FocusArgs),
@@ -49,17 +56,38 @@ pub enum Commands {
         #[command(subcommand)]
         command: ForwardCmd,
     },
+    /// Pause notifications for a while
+    Dnd {
+        #[command(subcommand)]
+        command: DndCmd,
+    },
+    /// Keep one message updated with a long job's progress
+    Progress {
+        #[command(subcommand)]
+        command: ProgressCmd,
+    },
     /// Telegram provider utilities
     Telegram {
         #[command(subcommand)]
         command: TelegramCmd,
     },
+    /// Browse sent and received notifications
+    History(HistoryArgs),
+    /// Re-deliver notifications from history
+    Replay(ReplayArgs),
+    /// Run a command and notify when it finishes
+    Run(RunArgs),
+    /// Watch log files or processes and notify on events
+    Watch {
+        #[command(subcommand)]
+        command: WatchCmd,
+    },
     /// Internal macOS click-wait helper
     #[command(hide = true)]
     WaitMacos(WaitMacosArgs),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Args, Default)]
 pub struct SendArgs {
     /// Notification title (optional)
     #[arg(long)]
@@ -93,7 +121,7 @@ pub struct SendArgs {
     #[arg(long)]
     pub telegram_token: Option<String>,
 
-    /// Telegram chat ID (telegram provider only)
+    /// Telegram chat ID or [telegram.chats] name (telegram provider only)
     #[arg(long)]
     pub telegram_chat_id: Option<String>,
 
@@ -117,6 +145,26 @@ pub struct SendArgs {
     #[arg(long)]
     pub source: Option<String>,
 
+    /// Extra metadata to attach (KEY=VALUE, repeatable)
+    #[arg(long = "meta", value_name = "KEY=VALUE")]
+    pub metadata: Vec<String>,
+
+    /// Action button (LABEL=url:URL or LABEL=cmd:COMMAND, repeatable)
+    #[arg(long = "action", value_name = "LABEL=url:URL|LABEL=cmd:COMMAND")]
+    pub actions: Vec<String>,
+
+    /// Attach a file (repeatable)
+    #[arg(long = "attach", value_name = "PATH")]
+    pub attachments: Vec<PathBuf>,
+
+    /// Attach standard input as a file
+    #[arg(long)]
+    pub attach_stdin: bool,
+
+    /// File name for --attach-stdin
+    #[arg(long, default_value = "stdin.txt", requires = "attach_stdin")]
+    pub attach_name: String,
+
     /// Command to execute on click
     #[arg(long)]
     pub on_click: Option<String>,
@@ -168,10 +216,18 @@ pub enum ConfigCmd {
     Init(ConfigInitArgs),
     /// Set a config key (supports dotted paths)
     Set(ConfigSetArgs),
+    /// Print a config key's value
+    Get(ConfigKeyArgs),
+    /// Remove a config key
+    Unset(ConfigKeyArgs),
+    /// Open the config in $EDITOR and validate it on save
+    Edit,
     /// Show the resolved config path
     Path,
     /// Show current config contents
     List,
+    /// Check the config file for unknown keys and invalid values
+    Validate,
 }
 
 #[derive(Debug, Args)]
@@ -190,14 +246,44 @@ pub struct ConfigSetArgs {
     /// Config key to set (e.g. remote.host)
     pub key: String,
 
-    /// Value to set
+    /// Value to set; lists take comma-separated items
+    #[arg(allow_hyphen_values = true)]
     pub value: String,
+
+    /// Add the value to a list instead of replacing it
+    #[arg(long, conflicts_with = "remove")]
+    pub append: bool,
+
+    /// Remove the value from a list
+    #[arg(long)]
+    pub remove: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigKeyArgs {
+    /// Config key (e.g. remote.host)
+    pub key: String,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum ProvidersCmd {
-    /// List providers available on this platform
+    /// List providers with their status, features, and config source
     List,
+    /// Send a test notification through a provider and report the result
+    Test(ProvidersTestArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    /// Output the checks as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ProvidersTestArgs {
+    /// Provider to test, or "all" for every provider available on this platform
+    pub provider: String,
 }
 
 #[derive(Debug, Subcommand)]
@@ -208,26 +294,101 @@ pub enum SourcesCmd {
 
 #[derive(Debug, Args)]
 pub struct InstallArgs {
-    /// Target tool (claude or codex)
+    /// Target tool
+    #[arg(value_enum, required_unless_present = "status")]
+    pub target: Option<InstallTarget>,
+
+    /// Apply changes (default is dry-run)
+    #[arg(long)]
+    pub apply: bool,
+
+    /// Report installed hooks and whether their binaries still exist
+    #[arg(long, conflicts_with_all = ["apply", "force", "events"])]
+    pub status: bool,
+
+    /// Settings to modify: user (~/.claude) or project (./.claude)
+    #[arg(long, value_enum, default_value = "user")]
+    pub scope: InstallScope,
+
+    /// Replace an existing Codex/Aider notify command or opencode plugin that is not ding's
+    #[arg(long)]
+    pub force: bool,
+
+    /// Claude Code hook events to install (defaults to notification,stop)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub events: Vec<ClaudeEventArg>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum InstallTarget {
+    Claude,
+    Codex,
+    Gemini,
+    Aider,
+    Opencode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InstallScope {
+    User,
+    Project,
+}
+
+#[derive(Debug, Args)]
+pub struct UninstallArgs {
+    /// Target tool
     #[arg(value_enum)]
     pub target: InstallTarget,
 
     /// Apply changes (default is dry-run)
     #[arg(long)]
     pub apply: bool,
+
+    /// Settings to modify: user (~/.claude) or project (./.claude)
+    #[arg(long, value_enum, default_value = "user")]
+    pub scope: InstallScope,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ClaudeEventArg {
+    Notification,
+    Stop,
+    SubagentStop,
+    PreToolUse,
+    PostToolUse,
+    SessionStart,
+    SessionEnd,
+    UserPromptSubmit,
+    PreCompact,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
-pub enum InstallTarget {
+pub enum HookTarget {
     Claude,
     Codex,
+    Gemini,
+    Aider,
+    Opencode,
+    Generic,
 }
 
 #[derive(Debug, Args)]
 pub struct HookArgs {
-    /// Target tool (claude or codex)
+    /// Target tool, or `generic` to use a `[hook_mappings.<name>]` config mapping
     #[arg(value_enum)]
-    pub target: InstallTarget,
+    pub target: HookTarget,
+
+    /// Mapping name under `[hook_mappings]` (required for `generic`)
+    #[arg(long, required_if_eq("target", "generic"))]
+    pub mapping: Option<String>,
+
+    /// Ask for approval on `PreToolUse` and print the decision (claude only)
+    #[arg(long)]
+    pub ask: bool,
+
+    /// How long to wait for an answer before deferring to the terminal
+    #[arg(long, default_value = "50s", requires = "ask")]
+    pub timeout: String,
 
     /// JSON payload (if not provided, read from stdin)
     pub json: Option<String>,
@@ -296,6 +457,10 @@ pub struct ListenArgs {
     /// Disable click handling entirely
     #[arg(long)]
     pub no_click: bool,
+
+    /// Run command actions sent by remote machines (dropped by default)
+    #[arg(long)]
+    pub allow_remote_commands: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -316,6 +481,74 @@ pub enum ForwardCmd {
     Status,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum DndCmd {
+    /// Hold back notifications below high urgency
+    On(DndOnArgs),
+    /// Resume notifications
+    Off,
+    /// Show do-not-disturb status
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProgressCmd {
+    /// Send the first progress message
+    Start(ProgressArgs),
+    /// Update the progress message in place
+    Update(ProgressArgs),
+    /// Show the final state and forget the key
+    Finish(ProgressFinishArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ProgressArgs {
+    /// Name tying start, update, and finish together
+    #[arg(long)]
+    pub key: String,
+
+    /// Progress message
+    #[arg(value_name = "MESSAGE", default_value = "")]
+    pub message: String,
+
+    /// Percent complete, shown as a bar
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub percent: Option<u8>,
+
+    /// Notification title (defaults to the key)
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Optional tag/category (provider-specific)
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Source identifier to resolve icon/logo
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Provider override (macos, telegram, remote, forward)
+    #[arg(long)]
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ProgressFinishArgs {
+    #[command(flatten)]
+    pub progress: ProgressArgs,
+
+    /// Also send a new, audible notification with the final message
+    #[arg(long)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DndOnArgs {
+    /// How long to stay quiet (e.g. 30m, 2h)
+    #[arg(default_value = "1h")]
+    pub duration: String,
+}
+
 #[derive(Debug, Args)]
 pub struct ForwardOnArgs {
     /// Forward targets (remote, telegram)
@@ -345,6 +578,8 @@ pub enum ForwardTarget {
 pub enum TelegramCmd {
     /// Fetch recent chat IDs for the bot
     ChatId(TelegramChatIdArgs),
+    /// Poll for button presses and replies, and run their click handlers
+    Bot,
 }
 
 #[derive(Debug, Args)]
@@ -373,6 +608,281 @@ pub struct RemotePingArgs {
     pub remote_token: Option<String>,
 }
 
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub command: Option<HistoryCmd>,
+
+    /// Only show notifications from this source (e.g. claude, codex)
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Only show notifications newer than this (e.g. 30m, 2h, 7d)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only show notifications whose title or message contains this text
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Maximum number of entries to show
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Output entries as JSON lines
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCmd {
+    /// Show a single history entry
+    Show(HistoryShowArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryShowArgs {
+    /// Entry id (a unique prefix is enough)
+    pub id: String,
+
+    /// Output the entry as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+#[command(group(
+    clap::ArgGroup::new("selection")
+        .required(true)
+        .args(["id", "last", "failed"]),
+))]
+pub struct ReplayArgs {
+    /// History entry id to replay (a unique prefix is enough)
+    pub id: Option<String>,
+
+    /// Replay the most recently sent notification
+    #[arg(long)]
+    pub last: bool,
+
+    /// Replay every notification with targets that failed and were not replayed successfully
+    #[arg(long)]
+    pub failed: bool,
+
+    /// Only consider failures newer than this (e.g. 30m, 2h, 7d; with --failed)
+    #[arg(long, requires = "failed")]
+    pub since: Option<String>,
+
+    /// Provider override (defaults to the provider used originally)
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// List what would be replayed without sending anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Notification title (defaults to the program name)
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Message template on success ({command}, {code}, {duration}, {tail})
+    #[arg(long)]
+    pub on_success: Option<String>,
+
+    /// Message template on failure ({command}, {code}, {duration}, {tail})
+    #[arg(long)]
+    pub on_failure: Option<String>,
+
+    /// Only notify if the command ran at least this long (e.g. 30s, 5m)
+    #[arg(long)]
+    pub min_duration: Option<String>,
+
+    /// Number of trailing output lines available as {tail}
+    #[arg(long, default_value_t = 10)]
+    pub tail_lines: usize,
+
+    /// Trailing output lines attached as output.log on failure (0 disables)
+    #[arg(long, default_value_t = 200)]
+    pub attach_lines: usize,
+
+    /// Source identifier to resolve icon/logo
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Optional tag/category (provider-specific)
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Provider override (e.g. macos)
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// Command to run, after `--`
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AskArgs {
+    /// Question to ask
+    #[arg(value_name = "MESSAGE")]
+    pub message: String,
+
+    /// Notification title (optional)
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Answer buttons, in order
+    #[arg(long, value_delimiter = ',', default_value = "Approve,Deny")]
+    pub actions: Vec<String>,
+
+    /// How long to wait for an answer (e.g. 30s, 5m)
+    #[arg(long, default_value = "2m")]
+    pub timeout: String,
+
+    /// Urgency hint (defaults to high)
+    #[arg(long, value_enum)]
+    pub urgency: Option<UrgencyArg>,
+
+    /// Optional tag/category (provider-specific)
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Source identifier to resolve icon/logo
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Provider override (macos, telegram, remote, forward)
+    #[arg(long)]
+    pub provider: Option<String>,
+
+    /// Output JSON result
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WatchCmd {
+    /// Follow a file (tail -F) and notify on lines matching a regex
+    File(WatchFileArgs),
+    /// Notify when a process ID exits
+    Pid(WatchPidArgs),
+    /// Notify when every process with a given name exits
+    Proc(WatchProcArgs),
+    /// Follow Codex session logs (or `codex exec --json` on stdin)
+    Codex(WatchCodexArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct WatchFileArgs {
+    /// File to follow (may not exist yet; rotation is handled)
+    pub path: PathBuf,
+
+    /// Regex a line must match to trigger a notification
+    #[arg(long = "match", value_name = "REGEX")]
+    pub pattern: String,
+
+    /// Also scan lines already in the file
+    #[arg(long)]
+    pub from_start: bool,
+
+    /// Suppress identical matches seen within this window (e.g. 5m; 0 disables)
+    #[arg(long, default_value = "5m")]
+    pub dedupe_window: String,
+
+    /// Stop after the first notification
+    #[arg(long)]
+    pub once: bool,
+
+    #[command(flatten)]
+    pub watch: WatchOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchPidArgs {
+    /// Process ID to wait for
+    #[arg(value_parser = clap::value_parser!(i32).range(1..))]
+    pub pid: i32,
+
+    #[command(flatten)]
+    pub watch: WatchOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchProcArgs {
+    /// Process name to wait for (exact match)
+    pub name: String,
+
+    /// Stop after the first exit instead of waiting for the process to return
+    #[arg(long)]
+    pub once: bool,
+
+    #[command(flatten)]
+    pub watch: WatchOptions,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchCodexArgs {
+    /// Read JSONL events from stdin (e.g. `codex exec --json ... | ding watch codex --stdin`)
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Events to notify about (defaults to failed, approval, file-change, plan)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub events: Vec<CodexEventArg>,
+
+    /// Sessions directory (defaults to $CODEX_HOME/sessions or ~/.codex/sessions)
+    #[arg(long)]
+    pub sessions_dir: Option<PathBuf>,
+
+    /// Poll interval in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub interval_ms: u64,
+
+    /// Provider override (e.g. macos)
+    #[arg(long)]
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CodexEventArg {
+    Failed,
+    Approval,
+    FileChange,
+    Plan,
+    Complete,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchOptions {
+    /// Poll interval in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub interval_ms: u64,
+
+    /// Notification title
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Notification urgency
+    #[arg(long, value_enum)]
+    pub urgency: Option<UrgencyArg>,
+
+    /// Optional tag/category (provider-specific)
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Source identifier to resolve icon/logo
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Provider override (e.g. macos)
+    #[arg(long)]
+    pub provider: Option<String>,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum UrgencyArg {
     Low,
@@ -0,0 +1,224 @@
+use crate::context::Context;
+use crate::error::NotifallError;
+use crate::notification::{Action, Notification};
+use crate::payload::WaitPayload;
+use crate::provider::DeliveryOutcome;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often a question shown by a `ding wait-macos` child is checked on.
+const HELPER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a send waits for the user, e.g. from `--on-click`, `--wait-for-click`,
+/// and `--background`. Providers that can wait (`capabilities().wait_for_click`)
+/// read it from the `SendContext`; the rest deliver and return.
+#[derive(Debug, Clone, Default)]
+pub struct Interaction {
+    /// Shell command run when the notification, or a button without its own
+    /// URL or command, is clicked or answered.
+    pub on_click: Option<String>,
+    /// Block until the notification is clicked or dismissed, even without a
+    /// click command.
+    pub wait_for_click: bool,
+    /// Leave the waiting to another process and return once delivered.
+    pub background: bool,
+}
+
+impl Interaction {
+    /// Whether something has to happen when `notification` is clicked.
+    pub fn handles_clicks(&self, notification: &Notification) -> bool {
+        self.on_click.is_some() || has_click_actions(notification)
+    }
+
+    /// Whether delivering `notification` in the foreground blocks for the click.
+    pub fn waits(&self, notification: &Notification) -> bool {
+        self.wait_for_click || self.handles_clicks(notification)
+    }
+}
+
+/// Whether pressing one of the notification's actions has to be handled by
+/// ding, so delivery must wait for the interaction.
+pub fn has_click_actions(notification: &Notification) -> bool {
+    notification
+        .actions
+        .iter()
+        .any(|action| action.url.is_some() || action.command.is_some())
+}
+
+/// Runs what a click on `notification` asks for: the pressed action's command
+/// or URL, or else `on_click`. Nothing runs when the notification was closed
+/// or left alone.
+pub fn handle_click(
+    outcome: Option<DeliveryOutcome>,
+    on_click: Option<&str>,
+    notification: &Notification,
+    context: Option<&Context>,
+) -> Result<(), NotifallError> {
+    let action = match &outcome {
+        Some(DeliveryOutcome::ActionButton(label)) => {
+            notification.actions.iter().find(|a| &a.label == label)
+        }
+        _ => None,
+    };
+    let open_command = if cfg!(target_os = "macos") {
+        "open \"$DING_ACTION_URL\""
+    } else {
+        "xdg-open \"$DING_ACTION_URL\""
+    };
+    let cmd = match (action, &outcome, on_click) {
+        (
+            Some(Action {
+                command: Some(cmd), ..
+            }),
+            _,
+            _,
+        ) => cmd.as_str(),
+        (Some(Action { url: Some(_), .. }), _, _) => open_command,
+        (_, Some(DeliveryOutcome::Clicked), Some(cmd)) => cmd,
+        (_, Some(DeliveryOutcome::ActionButton(_)), Some(cmd)) => cmd,
+        (_, Some(DeliveryOutcome::Replied(_)), Some(cmd)) => cmd,
+        _ => return Ok(()),
+    };
+
+    let mut child = Command::new("sh");
+    child.arg("-c").arg(cmd);
+    if let Some(DeliveryOutcome::Replied(text)) = &outcome {
+        child.env("DING_REPLY", text);
+    }
+    let attachments = notification
+        .attachments
+        .iter()
+        .filter_map(|a| a.path.as_deref())
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>();
+    if !attachments.is_empty() {
+        child.env("DING_ATTACHMENTS", attachments.join("\n"));
+    }
+    if let Some(action) = action {
+        child.env("DING_ACTION", &action.label);
+        if let Some(url) = action.url.as_deref() {
+            child.env("DING_ACTION_URL", url);
+        }
+    }
+    if let Some(source) = notification.source.as_deref() {
+        child.env("DING_SOURCE", source);
+    }
+    child.env("DING_TITLE", &notification.title);
+    child.env("DING_MESSAGE", &notification.message);
+    if let Some(tag) = notification.tag.as_deref() {
+        child.env("DING_TAG", tag);
+    }
+    if let Some(context) = context {
+        if let Some(tmux) = context.tmux.as_ref() {
+            child.env("DING_TMUX_SESSION", &tmux.session);
+            child.env("DING_TMUX_WINDOW", &tmux.window);
+            child.env("DING_TMUX_PANE", &tmux.pane);
+            if let Some(client) = tmux.client.as_deref() {
+                child.env("DING_TMUX_CLIENT", client);
+            }
+        }
+        if let Some(terminal) = context.terminal.as_ref().and_then(|t| t.app.as_deref()) {
+            child.env("DING_TERMINAL_APP", terminal);
+        }
+        if let Ok(json) = serde_json::to_string(context) {
+            child.env("DING_CONTEXT_JSON", json);
+        }
+    }
+
+    child.spawn()?;
+    Ok(())
+}
+
+/// `ding focus` for the running binary: the click command hooks and the
+/// listener use when nothing else is configured.
+pub fn default_focus_command() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    Some(format!("{} focus", exe.display()))
+}
+
+/// Hands `payload` to a detached `ding wait-macos`, which shows the
+/// notification and handles the click after this process has exited. Returns
+/// the payload file it reads.
+pub fn spawn_background_wait(payload: WaitPayload) -> Result<PathBuf, NotifallError> {
+    let payload_path = write_payload(payload)?;
+    let exe = std::env::current_exe()?;
+    let mut cmd = Command::new(exe);
+    cmd.arg("wait-macos").arg("--payload").arg(&payload_path);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+    }
+    cmd.spawn()?;
+    Ok(payload_path)
+}
+
+/// Shows `payload`, a question, from a `ding wait-macos` child and returns
+/// the outcome it prints, or `None` once `timeout` passes. Notification
+/// Center offers no way to stop waiting for a click, so the wait runs in a
+/// process that can be killed rather than a thread that would be stuck.
+pub fn wait_in_helper(
+    payload: WaitPayload,
+    timeout: Duration,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let payload_path = write_payload(payload)?;
+    let result = run_helper(&payload_path, timeout);
+    let _ = fs::remove_file(&payload_path);
+    result
+}
+
+fn run_helper(
+    payload_path: &Path,
+    timeout: Duration,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg("wait-macos")
+        .arg("--payload")
+        .arg(payload_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(HELPER_POLL_INTERVAL);
+    };
+    let output = child.wait_with_output()?;
+    if !status.success() {
+        return Err(NotifallError::Io(std::io::Error::other(format!(
+            "wait-macos exited with {status}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn write_payload(payload: WaitPayload) -> Result<PathBuf, NotifallError> {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let file_name = format!("ding-payload-{}-{}.json", std::process::id(), ts);
+    let path = std::env::temp_dir().join(file_name);
+    let data = serde_json::to_vec(&payload)?;
+    fs::write(&path, data)?;
+    Ok(path)
+}
@@ -0,0 +1,452 @@
+use crate::debug::debug_log;
+use crate::notification::Urgency;
+use crate::watch::FileFollower;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodexEventKind {
+    Failed,
+    Approval,
+    FileChange,
+    Plan,
+    Complete,
+}
+
+impl CodexEventKind {
+    pub const DEFAULTS: [CodexEventKind; 4] =
+        [Self::Failed, Self::Approval, Self::FileChange, Self::Plan];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Failed => "failed",
+            Self::Approval => "approval",
+            Self::FileChange => "file-change",
+            Self::Plan => "plan",
+            Self::Complete => "complete",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "failed" => Some(Self::Failed),
+            "approval" => Some(Self::Approval),
+            "file-change" => Some(Self::FileChange),
+            "plan" => Some(Self::Plan),
+            "complete" => Some(Self::Complete),
+            _ => None,
+        }
+    }
+
+    pub fn urgency(&self) -> Urgency {
+        match self {
+            Self::Failed | Self::Approval => Urgency::High,
+            Self::FileChange | Self::Complete => Urgency::Normal,
+            Self::Plan => Urgency::Low,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Failed => "Codex: turn failed",
+            Self::Approval => "Codex: approval requested",
+            Self::FileChange => "Codex: files changed",
+            Self::Plan => "Codex: plan updated",
+            Self::Complete => "Codex: turn complete",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CodexEvent {
+    pub kind: CodexEventKind,
+    pub title: String,
+    pub message: String,
+}
+
+impl CodexEvent {
+    fn new(kind: CodexEventKind, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            kind,
+            title: kind.title().to_string(),
+            message: if message.trim().is_empty() {
+                " ".to_string()
+            } else {
+                message
+            },
+        }
+    }
+}
+
+/// Parses one JSONL line from either `codex exec --json` or a session
+/// rollout file. Returns `None` for events ding does not notify about.
+pub fn parse_line(line: &str) -> Option<CodexEvent> {
+    let value: Value = serde_json::from_str(line.trim()).ok()?;
+    match str_at(&value, "type")? {
+        "event_msg" => parse_rollout_event(value.get("payload")?),
+        "response_item" => parse_rollout_item(value.get("payload")?),
+        _ => parse_exec_event(&value),
+    }
+}
+
+fn parse_exec_event(value: &Value) -> Option<CodexEvent> {
+    let etype = str_at(value, "type")?;
+    match etype {
+        "turn.failed" => Some(CodexEvent::new(
+            CodexEventKind::Failed,
+            value
+                .get("error")
+                .and_then(|e| str_at(e, "message"))
+                .unwrap_or("turn failed"),
+        )),
+        "error" => Some(CodexEvent::new(
+            CodexEventKind::Failed,
+            str_at(value, "message").unwrap_or("error"),
+        )),
+        "turn.completed" => Some(CodexEvent::new(CodexEventKind::Complete, "Turn complete")),
+        "item.started" | "item.updated" | "item.completed" => {
+            let item = value.get("item")?;
+            match str_at(item, "type")? {
+                "file_change" if etype == "item.completed" => {
+                    let paths = item
+                        .get("changes")
+                        .and_then(|c| c.as_array())
+                        .map(|changes| {
+                            changes
+                                .iter()
+                                .filter_map(|c| str_at(c, "path"))
+                                .map(short_path)
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    Some(CodexEvent::new(
+                        CodexEventKind::FileChange,
+                        paths.join(", "),
+                    ))
+                }
+                "todo_list" if etype != "item.completed" => {
+                    let steps = item
+                        .get("items")
+                        .and_then(|i| i.as_array())
+                        .cloned()
+                        .unwrap_or_default();
+                    let done = steps
+                        .iter()
+                        .filter(|s| s.get("completed").and_then(|c| c.as_bool()) == Some(true))
+                        .count();
+                    Some(CodexEvent::new(
+                        CodexEventKind::Plan,
+                        plan_summary(done, steps.len(), next_step(&steps, "text")),
+                    ))
+                }
+                other if other.contains("approval") => Some(CodexEvent::new(
+                    CodexEventKind::Approval,
+                    str_at(item, "command").unwrap_or(other),
+                )),
+                _ => None,
+            }
+        }
+        other if other.contains("approval") => {
+            Some(CodexEvent::new(CodexEventKind::Approval, other))
+        }
+        _ => None,
+    }
+}
+
+fn parse_rollout_event(payload: &Value) -> Option<CodexEvent> {
+    match str_at(payload, "type")? {
+        "exec_approval_request" => {
+            let command = payload
+                .get("command")
+                .and_then(|c| c.as_array())
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter_map(|p| p.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            Some(CodexEvent::new(CodexEventKind::Approval, command))
+        }
+        "apply_patch_approval_request" => Some(CodexEvent::new(
+            CodexEventKind::Approval,
+            str_at(payload, "reason").unwrap_or("Apply patch"),
+        )),
+        "error" | "stream_error" => Some(CodexEvent::new(
+            CodexEventKind::Failed,
+            str_at(payload, "message").unwrap_or("error"),
+        )),
+        "task_complete" => Some(CodexEvent::new(
+            CodexEventKind::Complete,
+            str_at(payload, "last_agent_message").unwrap_or("Turn complete"),
+        )),
+        "patch_apply_end" => {
+            if payload.get("success").and_then(|s| s.as_bool()) == Some(false) {
+                return None;
+            }
+            let paths = payload
+                .get("changes")
+                .and_then(|c| c.as_object())
+                .map(|changes| changes.keys().map(|k| short_path(k)).collect::<Vec<_>>())
+                .unwrap_or_default();
+            Some(CodexEvent::new(
+                CodexEventKind::FileChange,
+                paths.join(", "),
+            ))
+        }
+        "plan_update" => Some(plan_event(payload)),
+        _ => None,
+    }
+}
+
+fn parse_rollout_item(payload: &Value) -> Option<CodexEvent> {
+    if str_at(payload, "type")? != "function_call" || str_at(payload, "name")? != "update_plan" {
+        return None;
+    }
+    let arguments = str_at(payload, "arguments")?;
+    let arguments: Value = serde_json::from_str(arguments).ok()?;
+    Some(plan_event(&arguments))
+}
+
+fn plan_event(plan: &Value) -> CodexEvent {
+    let steps = plan
+        .get("plan")
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let done = steps
+        .iter()
+        .filter(|s| str_at(s, "status") == Some("completed"))
+        .count();
+    let pending = steps
+        .iter()
+        .filter(|s| str_at(s, "status") != Some("completed"))
+        .cloned()
+        .collect::<Vec<_>>();
+    CodexEvent::new(
+        CodexEventKind::Plan,
+        plan_summary(done, steps.len(), next_step(&pending, "step")),
+    )
+}
+
+fn plan_summary(done: usize, total: usize, next: Option<&str>) -> String {
+    match next {
+        Some(next) => format!("{done}/{total} done, next: {next}"),
+        None => format!("{done}/{total} done"),
+    }
+}
+
+fn next_step<'a>(steps: &'a [Value], field: &str) -> Option<&'a str> {
+    steps
+        .iter()
+        .find(|s| s.get("completed").and_then(|c| c.as_bool()) != Some(true))
+        .and_then(|s| str_at(s, field))
+}
+
+fn str_at<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|v| v.as_str())
+}
+
+fn short_path(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+pub fn default_sessions_dir() -> Option<PathBuf> {
+    if let Ok(home) = std::env::var("CODEX_HOME") {
+        return Some(PathBuf::from(home).join("sessions"));
+    }
+    std::env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".codex/sessions"))
+}
+
+/// Recursively collects `rollout-*.jsonl` files under `dir`.
+pub fn find_rollout_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with("rollout-") && name.ends_with(".jsonl") {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// `ding watch codex`: follows the rollout files of active Codex sessions
+/// under a sessions directory. Sessions that already exist are followed from
+/// their current end; only files created after startup are read from the
+/// beginning.
+#[derive(Debug)]
+pub struct SessionWatcher {
+    dir: PathBuf,
+    existing: HashSet<PathBuf>,
+    followers: HashMap<PathBuf, FileFollower>,
+}
+
+impl SessionWatcher {
+    pub fn new(dir: PathBuf) -> Self {
+        let existing = find_rollout_files(&dir).into_iter().collect();
+        Self {
+            dir,
+            existing,
+            followers: HashMap::new(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Lines appended to active sessions since the last poll. Files that
+    /// can't be read are skipped until the next poll.
+    pub fn poll(&mut self) -> Vec<String> {
+        for path in find_rollout_files(&self.dir) {
+            if self.followers.contains_key(&path) || !recently_modified(&path) {
+                continue;
+            }
+            let from_start = !self.existing.contains(&path);
+            debug_log(&format!(
+                "watch codex follow={} from_start={from_start}",
+                path.display()
+            ));
+            self.followers
+                .insert(path.clone(), FileFollower::new(path, from_start));
+        }
+        self.followers.retain(|path, _| recently_modified(path));
+
+        let mut lines = Vec::new();
+        for follower in self.followers.values_mut() {
+            match follower.poll() {
+                Ok(new) => lines.extend(new),
+                Err(err) => debug_log(&format!(
+                    "watch codex read failed path={} err={err}",
+                    follower.path().display()
+                )),
+            }
+        }
+        lines
+    }
+}
+
+/// Whether `path` was modified within the last day; older sessions are idle.
+fn recently_modified(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < Duration::from_secs(86_400))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXEC_TURN_FAILED: &str =
+        r#"{"type":"turn.failed","error":{"message":"stream disconnected before completion"}}"#;
+
+    const EXEC_FILE_CHANGE: &str = r#"{"type":"item.completed","item":{"id":"item_4","type":"file_change","changes":[{"path":"/home/dev/app/src/parser.rs","kind":"update"},{"path":"/home/dev/app/tests/roundtrip.rs","kind":"add"}],"status":"completed"}}"#;
+
+    const EXEC_TODO_LIST: &str = r#"{"type":"item.updated","item":{"id":"item_1","type":"todo_list","items":[{"text":"Reproduce the failure","completed":true},{"text":"Fix the parser","completed":false},{"text":"Run the tests","completed":false}]}}"#;
+
+    const ROLLOUT_APPROVAL: &str = r#"{"timestamp":"2025-09-14T10:02:11.512Z","type":"event_msg","payload":{"type":"exec_approval_request","call_id":"call_7","command":["cargo","test","--workspace"],"cwd":"/home/dev/app"}}"#;
+
+    const ROLLOUT_PATCH: &str = r#"{"timestamp":"2025-09-14T10:03:40.001Z","type":"event_msg","payload":{"type":"patch_apply_end","call_id":"call_8","success":true,"changes":{"/home/dev/app/src/lib.rs":{"update":{}}}}}"#;
+
+    const ROLLOUT_PLAN: &str = r#"{"timestamp":"2025-09-14T10:01:02.300Z","type":"response_item","payload":{"type":"function_call","name":"update_plan","arguments":"{\"plan\":[{\"step\":\"Read the parser\",\"status\":\"completed\"},{\"step\":\"Fix the bounds check\",\"status\":\"in_progress\"}]}","call_id":"call_3"}}"#;
+
+    fn parsed(line: &str) -> (CodexEventKind, String, String) {
+        let event = parse_line(line).expect("event");
+        (event.kind, event.title, event.message)
+    }
+
+    #[test]
+    fn exec_events_map_to_kinds() {
+        assert_eq!(
+            parsed(EXEC_TURN_FAILED),
+            (
+                CodexEventKind::Failed,
+                "Codex: turn failed".to_string(),
+                "stream disconnected before completion".to_string()
+            )
+        );
+        assert_eq!(
+            parsed(EXEC_FILE_CHANGE).2,
+            "parser.rs, roundtrip.rs".to_string()
+        );
+        assert_eq!(
+            parsed(EXEC_TODO_LIST),
+            (
+                CodexEventKind::Plan,
+                "Codex: plan updated".to_string(),
+                "1/3 done, next: Fix the parser".to_string()
+            )
+        );
+        // Only the finished file change is reported, and a plan only while it runs.
+        assert!(parse_line(&EXEC_FILE_CHANGE.replace("item.completed", "item.started")).is_none());
+        assert!(parse_line(&EXEC_TODO_LIST.replace("item.updated", "item.completed")).is_none());
+        assert!(parse_line(r#"{"type":"thread.started","thread_id":"t1"}"#).is_none());
+    }
+
+    #[test]
+    fn rollout_events_map_to_kinds() {
+        assert_eq!(
+            parsed(ROLLOUT_APPROVAL),
+            (
+                CodexEventKind::Approval,
+                "Codex: approval requested".to_string(),
+                "cargo test --workspace".to_string()
+            )
+        );
+        assert_eq!(
+            parsed(ROLLOUT_PATCH),
+            (
+                CodexEventKind::FileChange,
+                "Codex: files changed".to_string(),
+                "lib.rs".to_string()
+            )
+        );
+        assert!(parse_line(&ROLLOUT_PATCH.replace("true", "false")).is_none());
+        assert_eq!(
+            parsed(ROLLOUT_PLAN).2,
+            "1/2 done, next: Fix the bounds check".to_string()
+        );
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("{\"type\":").is_none());
+        assert!(parse_line(r#"{"payload":{}}"#).is_none());
+        assert!(parse_line(r#"{"type":"event_msg","payload":{"type":"token_count"}}"#).is_none());
+    }
+
+    #[test]
+    fn kinds_parse_and_rank() {
+        assert_eq!(
+            CodexEventKind::from_name("File_Change"),
+            Some(CodexEventKind::FileChange)
+        );
+        assert_eq!(CodexEventKind::from_name("nope"), None);
+        assert!(matches!(CodexEventKind::Approval.urgency(), Urgency::High));
+        assert!(matches!(CodexEventKind::Plan.urgency(), Urgency::Low));
+    }
+}
@@ -1,6 +1,9 @@
+use crate::error::NotifallError;
+use crate::notification::Urgency;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -11,6 +14,9 @@ pub struct Config {
     pub forward: Option<ForwardConfig>,
     pub telegram: Option<TelegramConfig>,
     pub sources: Option<BTreeMap<String, SourceConfig>>,
+    pub history: Option<HistoryConfig>,
+    pub watch: Option<WatchConfig>,
+    pub hook_mappings: Option<BTreeMap<String, HookMappingConfig>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -18,6 +24,8 @@ pub struct MacosConfig {
     pub sound: Option<String>,
     pub app_bundle_id: Option<String>,
     pub icon: Option<PathBuf>,
+    pub truncate_title: Option<usize>,
+    pub truncate_message: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -36,6 +44,8 @@ pub struct RemoteConfig {
     pub timeout_ms: Option<u64>,
     pub retries: Option<u32>,
     pub fallback_to_local: Option<bool>,
+    pub truncate_title: Option<usize>,
+    pub truncate_message: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -47,14 +57,88 @@ pub struct ListenerConfig {
     pub prefix_hostname: Option<bool>,
     pub allow_hosts: Option<Vec<String>>,
     pub on_click: Option<String>,
+    /// Run `cmd:` actions that arrive from other machines. Off by default:
+    /// anyone with the token could otherwise run commands here.
+    pub allow_remote_commands: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TelegramConfig {
     pub bot_token: Option<String>,
+    /// Bot API base URL, for self-hosted Bot API servers or testing.
+    pub api_url: Option<String>,
+    /// User IDs allowed to press buttons and send bot commands from any chat.
+    pub allowed_users: Option<Vec<i64>>,
     pub chat_id: Option<String>,
+    /// Forum topic within `chat_id`.
+    pub message_thread_id: Option<i64>,
     pub parse_mode: Option<String>,
     pub silent: Option<bool>,
+    pub truncate_title: Option<usize>,
+    pub truncate_message: Option<usize>,
+    pub connect_timeout_ms: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    /// Retries for rate limits and transient failures.
+    pub retries: Option<u32>,
+    /// Minimum gap between messages to one chat, across ding processes.
+    pub min_interval_ms: Option<u64>,
+    /// Named chats that `route` and `--telegram-chat-id` can refer to.
+    pub chats: Option<BTreeMap<String, TelegramChatConfig>>,
+    pub route: Option<TelegramRouteConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TelegramChatConfig {
+    pub chat_id: Option<String>,
+    pub message_thread_id: Option<i64>,
+}
+
+/// Picks a named chat by the notification's source, then by its urgency;
+/// anything unmatched goes to the default `chat_id`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TelegramRouteConfig {
+    pub source: Option<BTreeMap<String, String>>,
+    pub urgency: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HistoryConfig {
+    pub enabled: Option<bool>,
+    pub path: Option<PathBuf>,
+    pub max_entries: Option<usize>,
+    pub max_age_days: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HookMappingConfig {
+    /// JSON pointer to the event type (e.g. "/hook_event_name").
+    pub event: Option<String>,
+    /// JSON pointer to an optional subtype, matched as "<event>.<subtype>".
+    pub subtype: Option<String>,
+    pub source: Option<String>,
+    pub events: Option<BTreeMap<String, HookEventConfig>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HookEventConfig {
+    pub title: Option<String>,
+    pub message: Option<String>,
+    pub urgency: Option<Urgency>,
+    pub tag: Option<String>,
+    pub skip: Option<bool>,
+    /// Metadata key to template, e.g. `session = "{/session_id}"`.
+    pub metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WatchConfig {
+    pub codex: Option<CodexWatchConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CodexWatchConfig {
+    pub sessions_dir: Option<PathBuf>,
+    pub events: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -63,7 +147,31 @@ pub struct ForwardConfig {
     pub targets: Option<Vec<String>>,
 }
 
+/// `$XDG_CONFIG_HOME/ding/config.toml`, falling back to `~/.config`.
+pub fn default_config_path() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+        return PathBuf::from(dir).join("ding/config.toml");
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return PathBuf::from(home).join(".config/ding/config.toml");
+    }
+    PathBuf::from("ding.toml")
+}
+
 impl Config {
+    /// Reads the config at `path`, or the default path. A missing file is
+    /// not an error: ding works without a config.
+    pub fn load(path: Option<&Path>) -> Result<Option<Self>, NotifallError> {
+        let path = path
+            .map(Path::to_path_buf)
+            .unwrap_or_else(default_config_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)?;
+        Ok(Some(toml::from_str(&contents)?))
+    }
+
     pub fn template() -> &'static str {
         r#"# ding config
 # default_provider = "macos"
@@ -72,6 +180,8 @@ impl Config {
 # sound = "default" # use "none" to disable
 # app_bundle_id = "com.apple.Terminal"
 # icon = "/path/to/icon.png"
+# truncate_title = 120   # 0 disables truncation
+# truncate_message = 300
 
 [remote]
 # host = "127.0.0.1"
@@ -80,6 +190,8 @@ impl Config {
 # timeout_ms = 2000
 # retries = 2
 # fallback_to_local = true
+# truncate_title = 0     # no limit by default; the receiver truncates
+# truncate_message = 0
 
 [forward]
 # enabled = true
@@ -99,6 +211,48 @@ impl Config {
 # chat_id = "123456789"
 # parse_mode = "MarkdownV2"
 # silent = false
+# api_url = "https://api.telegram.org"
+# allowed_users = [123456789]
+# message_thread_id = 42  # forum topic in chat_id
+# truncate_title = 256
+# truncate_message = 3500
+# connect_timeout_ms = 5000
+# timeout_ms = 15000
+# retries = 3
+# min_interval_ms = 1000
+
+# Named chats (and forum topics), chosen by source or urgency.
+# [telegram.chats.alerts]
+# chat_id = "-1001234567890"
+# message_thread_id = 7
+# [telegram.route.urgency]
+# high = "alerts"
+# [telegram.route.source]
+# codex = "alerts"
+
+[history]
+# enabled = true
+# path = "/path/to/history.jsonl"
+# max_entries = 1000
+# max_age_days = 30
+
+[watch.codex]
+# sessions_dir = "/path/to/.codex/sessions"
+# events = ["failed", "approval", "file-change", "plan"] # also: "complete"
+
+# Map arbitrary JSON hook payloads (`ding hook generic --mapping myagent`).
+# [hook_mappings.<name>] also overrides the built-in claude/codex/gemini/aider/opencode hooks.
+# [hook_mappings.myagent]
+# event = "/event"          # JSON pointer to the event type
+# subtype = "/kind"         # optional; rules can match "<event>.<subtype>"
+# source = "myagent"
+# [hook_mappings.myagent.events.done]
+# title = "My agent: finished"
+# message = "{/summary|Task completed}"
+# urgency = "normal"
+# tag = "stop"
+# [hook_mappings.myagent.events."*"]
+# skip = true               # ignore events without a rule
 
 [sources.claude]
 # icon = "/path/to/claude.icns"
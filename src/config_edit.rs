@@ -0,0 +1,528 @@
+use crate::error::NotifallError;
+use crate::provider::registry::ProviderRegistry;
+use crate::remote::parse_remote_url;
+use crate::schema::{self, Diagnostic};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item, TableLike, Value};
+
+/// How `config set` treats a list key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEdit {
+    /// Replace the whole value.
+    Replace,
+    /// Add the item unless it is already there.
+    Append,
+    /// Remove the item, which must be there.
+    Remove,
+}
+
+/// Sets dotted `key` to `raw`, parsed as the type the schema gives the key.
+/// Unknown keys are written as text so validation can point out the typo.
+pub fn set_value(
+    doc: &mut DocumentMut,
+    key: &str,
+    raw: &str,
+    edit: ListEdit,
+) -> Result<(), NotifallError> {
+    let invalid = |message: String| NotifallError::ConfigInvalid(format!("`{key}` {message}"));
+    let kind = schema::lookup(key).unwrap_or(&schema::Kind::String);
+
+    let value = if edit == ListEdit::Replace {
+        schema::coerce(kind, raw).map_err(invalid)?
+    } else {
+        let schema::Kind::List(item_kind) = kind else {
+            return Err(invalid("is not a list".to_string()));
+        };
+        let item = schema::coerce(item_kind, raw).map_err(invalid)?;
+        let mut list = get_key(doc, key)
+            .and_then(|existing| existing.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let present = list.iter().any(|value| same_value(value, &item));
+        if edit == ListEdit::Append && !present {
+            list.push(item);
+        } else if edit == ListEdit::Remove {
+            if !present {
+                return Err(invalid(format!("doesn't contain {raw:?}")));
+            }
+            list.retain(|value| !same_value(value, &item));
+        }
+        list.fmt();
+        Value::Array(list)
+    };
+
+    set_key(doc, key, value)
+}
+
+/// Writes `contents` to a new draft next to the config at `path` for `config
+/// edit`. The draft holds the same tokens as the config, so only the owner may
+/// read it, and it is never an existing file or a link someone left there.
+pub fn create_draft(path: &Path, contents: &str) -> std::io::Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let draft = dir.join(format!(".{name}.edit-{}.toml", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&draft)?.write_all(contents.as_bytes())?;
+    Ok(draft)
+}
+
+/// Validates `contents` before it replaces the config. Problems with `key`
+/// refuse the change; problems elsewhere were already there, so they are
+/// returned for the caller to report.
+pub fn check_change(
+    contents: &str,
+    key: &str,
+    registry: &ProviderRegistry,
+) -> Result<Vec<Diagnostic>, NotifallError> {
+    let (own, other): (Vec<_>, Vec<_>) = schema::validate(contents, registry)
+        .into_iter()
+        .partition(|diagnostic| {
+            diagnostic.key == key
+                || diagnostic.key.starts_with(&format!("{key}."))
+                || diagnostic.key.starts_with(&format!("{key}["))
+        });
+    match own.into_iter().next() {
+        Some(diagnostic) => Err(NotifallError::ConfigInvalid(diagnostic.message)),
+        None => Ok(other),
+    }
+}
+
+/// The error for a key that isn't in the config: unset if the schema knows
+/// it, a likely typo otherwise.
+pub fn missing_key(key: &str) -> NotifallError {
+    match schema::lookup(key) {
+        Some(_) => NotifallError::ConfigKeyNotSet(key.to_string()),
+        None => NotifallError::ConfigInvalid(format!("unknown key `{key}`")),
+    }
+}
+
+pub fn get_key<'a>(doc: &'a DocumentMut, key: &str) -> Option<&'a Item> {
+    key.split('.')
+        .try_fold(doc.as_item(), |item, part| item.get(part))
+}
+
+/// Removes dotted `key`, then any tables on its path that it leaves empty,
+/// so unsetting the last key of `[remote]` drops the header too.
+pub fn remove_key(doc: &mut DocumentMut, key: &str) -> Option<Item> {
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, parents) = parts.split_last()?;
+    let removed = table_mut(doc, parents)?.remove(last)?;
+    for depth in (1..parts.len()).rev() {
+        if !table_mut(doc, &parts[..depth]).is_some_and(|table| table.is_empty()) {
+            break;
+        }
+        if let Some(parent) = table_mut(doc, &parts[..depth - 1]) {
+            parent.remove(parts[depth - 1]);
+        }
+    }
+    Some(removed)
+}
+
+fn table_mut<'a>(doc: &'a mut DocumentMut, path: &[&str]) -> Option<&'a mut dyn TableLike> {
+    path.iter()
+        .try_fold(doc.as_item_mut(), |item, part| item.get_mut(part))?
+        .as_table_like_mut()
+}
+
+/// Whether two list items are the same string, number, or flag, ignoring
+/// formatting.
+fn same_value(a: &Value, b: &Value) -> bool {
+    a.as_str() == b.as_str() && a.as_integer() == b.as_integer() && a.as_bool() == b.as_bool()
+}
+
+/// Sets dotted `key` to `value`, creating the tables on its path. Tables on
+/// the path may be inline, as in `alerts = { chat_id = "1" }`; a path through
+/// anything else is an error rather than overwriting it.
+pub fn set_key(doc: &mut DocumentMut, key: &str, value: Value) -> Result<(), NotifallError> {
+    let parts: Vec<&str> = key.split('.').collect();
+    let Some((last, parents)) = parts.split_last() else {
+        return Ok(());
+    };
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    let mut inline = false;
+    for (depth, part) in parents.iter().enumerate() {
+        if table.get(part).is_none() {
+            // Implicit, so setting `a.b.c` doesn't leave an empty `[a]` header.
+            let mut new_table = toml_edit::Table::new();
+            new_table.set_implicit(true);
+            table.insert(part, Item::Table(new_table));
+            if inline {
+                table.fmt();
+            }
+        }
+        let item = table.get_mut(part).expect("inserted above");
+        inline = item.is_value();
+        table = item.as_table_like_mut().ok_or_else(|| {
+            NotifallError::ConfigInvalid(format!(
+                "{key}: {} is not a table",
+                parts[..=depth].join(".")
+            ))
+        })?;
+    }
+    table.insert(last, Item::Value(value));
+    // Inline tables otherwise keep the spacing of their old last entry.
+    if inline {
+        table.fmt();
+    }
+    Ok(())
+}
+
+/// A change to `[forward]`, as `ding forward` makes it.
+#[derive(Debug, Clone)]
+pub enum ForwardChange {
+    /// Forward to `targets`, added to the configured ones with `append`.
+    /// Forwarding to remote can set the listener's host and port on the way.
+    On {
+        targets: Vec<String>,
+        append: bool,
+        host: Option<String>,
+        port: Option<u16>,
+    },
+    Off,
+    Toggle,
+}
+
+/// Whether forwarding is on and where to, as `ding forward status` shows it.
+pub fn forward_status(doc: &DocumentMut) -> String {
+    let targets = forward_targets(doc);
+    let targets = if targets.is_empty() {
+        "(none)".to_string()
+    } else {
+        targets.join(", ")
+    };
+    format!(
+        "forwarding: {}\ntargets: {targets}",
+        if forward_enabled(doc) { "on" } else { "off" }
+    )
+}
+
+/// Applies `change` to the config file at `path` and returns the new state,
+/// e.g. "forwarding enabled".
+pub fn update_forward(path: &Path, change: ForwardChange) -> Result<String, NotifallError> {
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let mut doc = DocumentMut::from_str(&existing)?;
+    apply_forward(&mut doc, path, change)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, doc.to_string())?;
+    Ok(format!(
+        "forwarding {}",
+        if forward_enabled(&doc) {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    ))
+}
+
+/// Applies `change` to the config document at `path`. The path only appears
+/// in the setup hints when a target is missing.
+pub fn apply_forward(
+    doc: &mut DocumentMut,
+    path: &Path,
+    change: ForwardChange,
+) -> Result<(), NotifallError> {
+    match change {
+        ForwardChange::Off => set_forward_enabled(doc, false),
+        ForwardChange::Toggle => {
+            if forward_enabled(doc) {
+                set_forward_enabled(doc, false);
+            } else {
+                if forward_targets(doc).is_empty() {
+                    return Err(NotifallError::ForwardSetup(
+                        forward_missing_targets_message(path),
+                    ));
+                }
+                set_forward_enabled(doc, true);
+            }
+        }
+        ForwardChange::On {
+            targets: provided,
+            append,
+            host,
+            port,
+        } => forward_on(doc, path, provided, append, host, port)?,
+    }
+    Ok(())
+}
+
+fn forward_on(
+    doc: &mut DocumentMut,
+    path: &Path,
+    provided: Vec<String>,
+    append: bool,
+    host: Option<String>,
+    port: Option<u16>,
+) -> Result<(), NotifallError> {
+    let mut targets = forward_targets(doc);
+    if append {
+        for target in provided {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    } else if !provided.is_empty() {
+        targets = provided;
+    }
+
+    if targets.is_empty() {
+        return Err(NotifallError::ForwardSetup(
+            forward_missing_targets_message(path),
+        ));
+    }
+
+    if targets.iter().any(|t| t == "remote") {
+        if let Some(host) = host.as_deref() {
+            set_remote_field(doc, "host", Value::from(host));
+        }
+        if let Some(port) = port {
+            set_remote_field(doc, "port", Value::from(port as i64));
+        }
+        if remote_host(doc).is_none() {
+            return Err(NotifallError::ForwardSetup(forward_missing_remote_message(
+                path,
+            )));
+        }
+        if remote_port(doc).is_none() {
+            set_remote_field(doc, "port", Value::from(4280i64));
+        }
+    }
+
+    set_forward_targets(doc, &targets);
+    set_forward_enabled(doc, true);
+    Ok(())
+}
+
+pub fn forward_enabled(doc: &DocumentMut) -> bool {
+    doc.get("forward")
+        .and_then(|v| v.get("enabled"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+pub fn forward_targets(doc: &DocumentMut) -> Vec<String> {
+    doc.get("forward")
+        .and_then(|v| v.get("targets"))
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|item| item.as_str().map(|s| s.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+}
+
+fn set_forward_enabled(doc: &mut DocumentMut, enabled: bool) {
+    let table = doc.entry("forward").or_insert(toml_edit::table());
+    if let Some(table) = table.as_table_mut() {
+        table["enabled"] = Item::Value(Value::from(enabled));
+    }
+}
+
+fn set_forward_targets(doc: &mut DocumentMut, targets: &[String]) {
+    let table = doc.entry("forward").or_insert(toml_edit::table());
+    if let Some(table) = table.as_table_mut() {
+        let mut arr = toml_edit::Array::new();
+        for target in targets {
+            arr.push(target.as_str());
+        }
+        table["targets"] = Item::Value(Value::Array(arr));
+    }
+}
+
+fn remote_url(doc: &DocumentMut) -> Option<String> {
+    doc.get("remote")
+        .and_then(|v| v.get("url"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+fn remote_host(doc: &DocumentMut) -> Option<String> {
+    doc.get("remote")
+        .and_then(|v| v.get("host"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| remote_url(doc).and_then(|url| parse_remote_url(&url).map(|t| t.0)))
+}
+
+fn remote_port(doc: &DocumentMut) -> Option<u16> {
+    doc.get("remote")
+        .and_then(|v| v.get("port"))
+        .and_then(|v| v.as_integer())
+        .and_then(|v| u16::try_from(v).ok())
+        .or_else(|| remote_url(doc).and_then(|url| parse_remote_url(&url).map(|t| t.1)))
+}
+
+fn set_remote_field(doc: &mut DocumentMut, key: &str, value: Value) {
+    let table = doc.entry("remote").or_insert(toml_edit::table());
+    if let Some(table) = table.as_table_mut() {
+        table[key] = Item::Value(value);
+    }
+}
+
+fn forward_missing_targets_message(path: &Path) -> String {
+    format!(
+        "Forwarding needs at least one target.\n\n\
+Set it with:\n  ding forward on remote --host mba --port 4280\n\
+or:\n  ding forward on telegram\n\n\
+Config file: {}\n\
+If missing, run: ding config init",
+        path.display()
+    )
+}
+
+fn forward_missing_remote_message(path: &Path) -> String {
+    format!(
+        "Forwarding to remote needs a host.\n\n\
+Set it with:\n  ding forward on remote --host mba --port 4280\n\
+or:\n  ding config set remote.host mba\n  ding config set remote.port 4280\n\n\
+Config file: {}\n\
+If missing, run: ding config init",
+        path.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_drops_tables_it_empties() {
+        let mut doc = DocumentMut::from_str(
+            "[remote]\nhost = \"mac\"\n\n[telegram]\nchat_id = \"1\"\n\n[telegram.chats.alerts]\nchat_id = \"2\"\n",
+        )
+        .unwrap();
+
+        assert!(remove_key(&mut doc, "remote.host").is_some());
+        assert!(remove_key(&mut doc, "telegram.chats.alerts.chat_id").is_some());
+        assert_eq!(
+            doc.to_string().trim_start(),
+            "[telegram]\nchat_id = \"1\"\n"
+        );
+
+        assert!(remove_key(&mut doc, "telegram.chat_id").is_some());
+        assert_eq!(doc.to_string().trim(), "");
+        assert!(remove_key(&mut doc, "telegram.chat_id").is_none());
+    }
+
+    #[test]
+    fn set_keeps_inline_tables_and_refuses_scalars() {
+        let mut doc = DocumentMut::from_str(
+            "[telegram]\nchat_id = \"1\"\nchats = { alerts = { chat_id = \"2\" } }\n",
+        )
+        .unwrap();
+
+        set_value(
+            &mut doc,
+            "telegram.chats.alerts.message_thread_id",
+            "5",
+            ListEdit::Replace,
+        )
+        .unwrap();
+        set_value(
+            &mut doc,
+            "telegram.chats.ops.chat_id",
+            "3",
+            ListEdit::Replace,
+        )
+        .unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "[telegram]\nchat_id = \"1\"\nchats = { alerts = { chat_id = \"2\", message_thread_id = 5 }, ops = { chat_id = \"3\" } }\n"
+        );
+
+        let err =
+            set_value(&mut doc, "telegram.chat_id.thread", "x", ListEdit::Replace).unwrap_err();
+        assert!(err.to_string().contains("telegram.chat_id is not a table"));
+        assert_eq!(
+            get_key(&doc, "telegram.chat_id").and_then(Item::as_str),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn get_reads_through_tables_of_either_kind() {
+        let doc = DocumentMut::from_str("listener = { port = 4000 }\n\n[remote]\nhost = \"mac\"\n")
+            .unwrap();
+        assert_eq!(
+            get_key(&doc, "listener.port").and_then(Item::as_integer),
+            Some(4000)
+        );
+        assert_eq!(
+            get_key(&doc, "remote.host").and_then(Item::as_str),
+            Some("mac")
+        );
+        assert!(get_key(&doc, "remote.port").is_none());
+        assert!(get_key(&doc, "remote.host.name").is_none());
+    }
+
+    #[test]
+    fn append_and_remove_edit_one_list_item() {
+        let mut doc = DocumentMut::from_str("listener = { port = 4000 }\n").unwrap();
+        let hosts = |doc: &DocumentMut| {
+            get_key(doc, "listener.allow_hosts")
+                .and_then(Item::as_array)
+                .map(|list| {
+                    list.iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+        };
+
+        set_value(&mut doc, "listener.allow_hosts", "mac", ListEdit::Append).unwrap();
+        set_value(&mut doc, "listener.allow_hosts", "pi", ListEdit::Append).unwrap();
+        set_value(&mut doc, "listener.allow_hosts", "mac", ListEdit::Append).unwrap();
+        assert_eq!(hosts(&doc).as_deref(), Some("mac,pi"));
+        assert_eq!(
+            get_key(&doc, "listener.port").and_then(Item::as_integer),
+            Some(4000)
+        );
+
+        set_value(&mut doc, "listener.allow_hosts", "mac", ListEdit::Remove).unwrap();
+        assert_eq!(hosts(&doc).as_deref(), Some("pi"));
+        let err = set_value(&mut doc, "listener.allow_hosts", "mac", ListEdit::Remove).unwrap_err();
+        assert!(err.to_string().contains("doesn't contain \"mac\""));
+
+        set_value(&mut doc, "telegram.allowed_users", "42", ListEdit::Append).unwrap();
+        assert_eq!(
+            get_key(&doc, "telegram.allowed_users")
+                .and_then(Item::as_array)
+                .and_then(|list| list.get(0))
+                .and_then(Value::as_integer),
+            Some(42)
+        );
+        assert!(set_value(&mut doc, "telegram.allowed_users", "me", ListEdit::Append).is_err());
+        assert!(set_value(&mut doc, "listener.port", "1", ListEdit::Append).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn drafts_are_private_and_new() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("ding-draft-{}", std::process::id()));
+        let config = dir.join("config.toml");
+
+        let draft = create_draft(&config, "[remote]\n").unwrap();
+        assert_eq!(draft.parent(), Some(dir.as_path()));
+        assert_eq!(fs::read_to_string(&draft).unwrap(), "[remote]\n");
+        assert_eq!(
+            fs::metadata(&draft).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(create_draft(&config, "").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
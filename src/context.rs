@@ -30,9 +30,7 @@ pub fn detect_context() -> Option<Context> {
 }
 
 fn detect_tmux() -> Option<TmuxContext> {
-    if std::env::var("TMUX").ok().is_none() {
-        return None;
-    }
+    std::env::var("TMUX").ok()?;
 
     let output = Command::new("tmux")
         .args([
@@ -57,7 +55,10 @@ fn detect_tmux() -> Option<TmuxContext> {
         session: parts[0].to_string(),
         window: parts[1].to_string(),
         pane: parts[2].to_string(),
-        client: parts.get(3).map(|s| s.to_string()).filter(|s| !s.is_empty()),
+        client: parts
+            .get(3)
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty()),
     })
 }
 
@@ -65,3 +66,31 @@ fn detect_terminal() -> Option<TerminalContext> {
     let app = std::env::var("TERM_PROGRAM").ok()?;
     Some(TerminalContext { app: Some(app) })
 }
+
+/// The macOS app a `TERM_PROGRAM` value names, if ding knows how to bring it
+/// to the front when a notification is clicked.
+pub fn focusable_app(term_program: &str) -> Option<&'static str> {
+    let name = term_program.to_ascii_lowercase();
+    match name.trim_end_matches(".app") {
+        "ghostty" => Some("Ghostty"),
+        "iterm" => Some("iTerm"),
+        "terminal" | "apple_terminal" => Some("Terminal"),
+        _ => None,
+    }
+}
+
+/// Brings the terminal named by `terminal` (a `TERM_PROGRAM` value) to the
+/// front. Does nothing off macOS or for terminals ding doesn't know.
+pub fn activate_terminal(terminal: Option<&str>) {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+
+    let app = terminal.and_then(focusable_app);
+
+    if let Some(app) = app {
+        let _ = Command::new("osascript")
+            .args(["-e", &format!("tell application \"{}\" to activate", app)])
+            .status();
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::Write;
+
+/// Whether `DING_DEBUG` asks for debug logging.
+pub fn debug_enabled() -> bool {
+    std::env::var("DING_DEBUG")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Writes a debug line to `DING_DEBUG_FILE`, or stderr when it isn't set.
+pub fn debug_log(message: &str) {
+    if !debug_enabled() {
+        return;
+    }
+    if let Ok(path) = std::env::var("DING_DEBUG_FILE")
+        && !path.trim().is_empty()
+        && let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+    {
+        let _ = writeln!(file, "[ding debug] {message}");
+        return;
+    }
+    eprintln!("[ding debug] {message}");
+}
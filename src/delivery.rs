@@ -0,0 +1,755 @@
+use crate::attachment;
+use crate::click::{self, Interaction};
+use crate::config::Config;
+use crate::context::detect_context;
+use crate::debug::debug_log;
+use crate::error::NotifallError;
+use crate::history::{HistoryEntry, HistoryKind, TargetResult, record_history};
+use crate::notification::Notification;
+use crate::payload::{PendingStore, WaitPayload};
+use crate::progress::{ProgressStep, ProgressTask};
+use crate::provider::macos::MacosProvider;
+use crate::provider::registry::{LOCAL_PROVIDER, ProviderRegistry};
+use crate::provider::telegram::SentMessage;
+use crate::provider::{DeliveryOutcome, DeliveryReport, Provider, ProviderError, SendOptions};
+use crate::remote::{RemoteClient, RemoteContext, RemoteEnvelope, RemoteOverrides};
+use crate::resolve::{
+    TelegramOverrides, expand_provider, resolve_macos_config, resolve_provider,
+    resolve_source_config, resolve_telegram_config, telegram_provider, truncate_message,
+};
+use serde::Deserialize;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Per-send settings that win over the config, e.g. from command-line flags.
+#[derive(Debug, Clone, Default)]
+pub struct SendOverrides {
+    pub telegram: TelegramOverrides,
+    pub remote: RemoteOverrides,
+    /// Don't fall back to a local notification when the listener is
+    /// unreachable, whatever `remote.fallback_to_local` says.
+    pub no_fallback: bool,
+}
+
+/// Everything a provider needs besides the notification itself.
+pub struct SendContext<'a> {
+    pub config: Option<&'a Config>,
+    pub overrides: &'a SendOverrides,
+    /// Where the notification was first raised, so replays and forwarded
+    /// notifications keep the original host and tmux pane.
+    pub origin: &'a RemoteContext,
+    /// Whether and how to wait for the notification to be clicked.
+    pub interaction: &'a Interaction,
+    /// For providers that deliver through others, like forwarding.
+    pub registry: &'a ProviderRegistry,
+}
+
+/// Delivers `notification` through the registered provider `provider_name`,
+/// adding one result per target it tried to `targets`.
+pub fn deliver(
+    cx: &SendContext,
+    provider_name: &str,
+    notification: Notification,
+    targets: &mut Vec<TargetResult>,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let result = match cx.registry.get(provider_name) {
+        Some(provider) => provider.deliver(cx, notification, targets),
+        None => Err(NotifallError::ProviderUnsupported(
+            provider_name.to_string(),
+        )),
+    };
+
+    if targets.is_empty() {
+        targets.push(match &result {
+            Ok(_) => TargetResult::ok(provider_name, None),
+            Err(err) => TargetResult::failed(provider_name, err),
+        });
+    }
+    result
+}
+
+/// Shows a macOS notification. With `--background` a detached process waits
+/// for the click, and the target's id is the payload file it reads.
+pub(crate) fn deliver_macos(
+    cx: &SendContext,
+    notification: Notification,
+    targets: &mut Vec<TargetResult>,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let source = notification.source.clone();
+    let source_config = resolve_source_config(cx.config, source.as_deref());
+    let macos_config = resolve_macos_config(cx.config, source_config, source.as_deref());
+    let notification = truncate_message(cx.config, "macos", notification);
+    let interaction = cx.interaction;
+    let context = cx.origin.to_context();
+
+    if interaction.background {
+        let payload = WaitPayload {
+            notification,
+            macos: macos_config,
+            on_click: interaction.on_click.clone(),
+            context,
+            ask: false,
+            wait: false,
+        };
+        let payload_path = click::spawn_background_wait(payload)?;
+        targets.push(TargetResult::ok(
+            "macos",
+            Some(payload_path.to_string_lossy().to_string()),
+        ));
+        return Ok(None);
+    }
+
+    let wait_for_click = interaction.waits(&notification);
+    let provider = MacosProvider::new(macos_config)?;
+    let report = provider.send(&notification, SendOptions { wait_for_click })?;
+    if wait_for_click {
+        click::handle_click(
+            report.outcome.clone(),
+            interaction.on_click.as_deref(),
+            &notification,
+            context.as_ref(),
+        )?;
+    }
+    Ok(report.outcome)
+}
+
+/// Sends to Telegram. When a click has to be handled the message gets
+/// callback buttons: in the foreground ding waits for a press or reply
+/// itself, in the background it leaves the message for `ding telegram bot`.
+pub(crate) fn send_telegram(
+    cx: &SendContext,
+    notification: Notification,
+) -> Result<DeliveryReport, NotifallError> {
+    let telegram_config = resolve_telegram_config(cx.config, &cx.overrides.telegram);
+    debug_log(&format!(
+        "telegram chat_id={} parse_mode={} silent={}",
+        telegram_config.chat_id.as_deref().unwrap_or("(unset)"),
+        telegram_config.parse_mode.as_deref().unwrap_or("(unset)"),
+        telegram_config.silent.unwrap_or(false)
+    ));
+    let provider = telegram_provider(telegram_config)?;
+    let notification = truncate_message(cx.config, "telegram", notification);
+    let interaction = cx.interaction;
+    let interactive = interaction.handles_clicks(&notification);
+    let context = cx.origin.to_context();
+
+    if interaction.background || !interaction.waits(&notification) {
+        let sent = provider.send_message(&notification, interactive)?;
+        if interactive {
+            let payload = WaitPayload {
+                notification,
+                macos: None,
+                on_click: interaction.on_click.clone(),
+                context,
+                ask: false,
+                wait: false,
+            };
+            PendingStore::default_location().save(sent, &payload)?;
+        }
+        return Ok(DeliveryReport {
+            provider: "telegram",
+            id: Some(sent.message_id.to_string()),
+            outcome: Some(DeliveryOutcome::Delivered),
+        });
+    }
+
+    // While `ding telegram bot` runs it owns getUpdates, so the press or
+    // reply is read back from it, as for `ding ask`.
+    let pending = PendingStore::default_location();
+    let report = if pending.bot_running() {
+        let sent = provider.send_message(&notification, true)?;
+        let payload = WaitPayload {
+            notification: notification.clone(),
+            macos: None,
+            on_click: None,
+            context: None,
+            ask: false,
+            wait: true,
+        };
+        pending.save(sent, &payload)?;
+        debug_log(&format!(
+            "wait via telegram bot chat={} message={}",
+            sent.chat_id, sent.message_id
+        ));
+        DeliveryReport {
+            provider: "telegram",
+            id: Some(sent.message_id.to_string()),
+            outcome: pending.wait_for_answer(sent, None),
+        }
+    } else {
+        provider.send(
+            &notification,
+            SendOptions {
+                wait_for_click: true,
+            },
+        )?
+    };
+    click::handle_click(
+        report.outcome.clone(),
+        interaction.on_click.as_deref(),
+        &notification,
+        context.as_ref(),
+    )?;
+    Ok(report)
+}
+
+/// Sends to the listener in `[remote]`. When it can't be reached and
+/// `remote.fallback_to_local` allows, shows the notification locally instead.
+pub fn send_remote(
+    cx: &SendContext,
+    notification: Notification,
+    targets: &mut Vec<TargetResult>,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let remote_cfg = cx.config.and_then(|c| c.remote.as_ref());
+    let fallback =
+        !cx.overrides.no_fallback && remote_cfg.and_then(|r| r.fallback_to_local).unwrap_or(true);
+
+    let mut remote_notification = notification.clone();
+    remote_notification.icon = None;
+    embed_attachments(&mut remote_notification);
+    let envelope = RemoteEnvelope {
+        notification: truncate_message(cx.config, "remote", remote_notification),
+        context: Some(cx.origin.clone()),
+        timeout_ms: None,
+        progress: None,
+    };
+
+    let send_result = RemoteClient::from_config(remote_cfg, &cx.overrides.remote)
+        .and_then(|client| client.send(&envelope))
+        .map_err(NotifallError::Provider);
+    let err = match send_result {
+        Ok(()) => {
+            targets.push(TargetResult::ok("remote", None));
+            return Ok(None);
+        }
+        Err(err) => err,
+    };
+    targets.push(TargetResult::failed("remote", &err));
+
+    // Only fall back when the listener was unreachable or overloaded; an
+    // auth failure or a rejected envelope would fail the same way next time,
+    // and the user should hear about it rather than get a local copy.
+    let recoverable = err.retryable()
+        || matches!(
+            err,
+            NotifallError::Provider(ProviderError::NotConfigured { .. })
+        );
+    let local = cx.registry.get(LOCAL_PROVIDER).filter(|p| p.available());
+    if fallback
+        && recoverable
+        && let Some(local) = local
+    {
+        let result = local.deliver(cx, notification, &mut Vec::new());
+        targets.push(match &result {
+            Ok(_) => TargetResult::ok(local.name(), None),
+            Err(err) => TargetResult::failed(local.name(), err),
+        });
+        return result;
+    }
+
+    Err(err)
+}
+
+/// Sends to every `forward.targets` entry, succeeding if any of them did.
+pub(crate) fn forward(
+    cx: &SendContext,
+    notification: Notification,
+    results: &mut Vec<TargetResult>,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let targets = cx
+        .config
+        .and_then(|c| c.forward.as_ref())
+        .and_then(|f| f.targets.clone())
+        .unwrap_or_default();
+
+    if targets.is_empty() {
+        return Err(NotifallError::Provider(ProviderError::not_configured(
+            "forward.targets",
+        )));
+    }
+
+    let mut successes = 0usize;
+    let mut errors = Vec::new();
+
+    for target in targets {
+        let mut attempts = Vec::new();
+        let result = match cx.registry.get(&target) {
+            Some(provider) if provider.name() != "forward" => {
+                provider.deliver(cx, notification.clone(), &mut attempts)
+            }
+            _ => Err(NotifallError::ProviderUnsupported(target.clone())),
+        };
+
+        // Keep what the target recorded itself, like a remote send that
+        // failed before falling back to local, so replays retry it.
+        if attempts.is_empty() {
+            attempts.push(match &result {
+                Ok(_) => TargetResult::ok(&target, None),
+                Err(err) => TargetResult::failed(&target, err),
+            });
+        }
+        results.append(&mut attempts);
+        match result {
+            Ok(_) => successes += 1,
+            Err(err) => errors.push((target, err)),
+        }
+    }
+
+    if successes == 0 {
+        return Err(NotifallError::AllTargetsFailed {
+            action: "forwarding",
+            errors,
+        });
+    }
+
+    Ok(None)
+}
+
+/// Shows `notification` with its actions as buttons and waits up to `timeout`
+/// for one to be chosen, recording the question and its answer in history.
+/// With forwarding, every target is asked at once and the first answer wins.
+pub fn ask(
+    config: Option<&Config>,
+    registry: &ProviderRegistry,
+    provider: Option<&str>,
+    notification: Notification,
+    timeout: Duration,
+) -> Result<Option<String>, NotifallError> {
+    let provider_name = resolve_provider(provider, config)?;
+    let origin = RemoteContext::from_local(detect_context());
+    let names = expand_provider(config, &provider_name)?;
+
+    let (tx, rx) = mpsc::channel();
+    for name in names.iter().cloned() {
+        let tx = tx.clone();
+        let config = config.cloned();
+        let notification = notification.clone();
+        let origin = origin.clone();
+        let registry = registry.clone();
+        std::thread::spawn(move || {
+            let cx = SendContext {
+                config: config.as_ref(),
+                overrides: &SendOverrides::default(),
+                origin: &origin,
+                interaction: &Interaction::default(),
+                registry: &registry,
+            };
+            let result = ask_target(&cx, &name, notification, timeout);
+            let _ = tx.send((name, result));
+        });
+    }
+    drop(tx);
+
+    let deadline = Instant::now() + timeout;
+    let mut targets = Vec::new();
+    let mut answer = None;
+    let mut errors = Vec::new();
+    while answer.is_none() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok((name, result)) = rx.recv_timeout(remaining) else {
+            break;
+        };
+        match result {
+            Ok(label) => {
+                targets.push(TargetResult::ok(&name, None));
+                answer = label;
+            }
+            Err(err) => {
+                debug_log(&format!("ask via {name} failed: {err}"));
+                targets.push(TargetResult::failed(&name, &err));
+                errors.push((name, err));
+            }
+        }
+    }
+
+    let result = if errors.len() < names.len() {
+        Ok(answer.clone().map(DeliveryOutcome::ActionButton))
+    } else if errors.len() == 1 {
+        Err(errors.remove(0).1)
+    } else {
+        Err(NotifallError::AllTargetsFailed {
+            action: "asking",
+            errors,
+        })
+    };
+    let mut history_entry = HistoryEntry::new(HistoryKind::Sent, &provider_name, notification);
+    history_entry.context = Some(origin);
+    record_history(config, history_entry, targets, &result);
+    result.map(|_| answer)
+}
+
+/// Asks through one provider, if it supports asking.
+pub fn ask_target(
+    cx: &SendContext,
+    name: &str,
+    notification: Notification,
+    timeout: Duration,
+) -> Result<Option<String>, NotifallError> {
+    match cx.registry.get(name) {
+        Some(provider) if provider.capabilities().ask => provider.ask(cx, notification, timeout),
+        _ => Err(NotifallError::ProviderUnsupported(name.to_string())),
+    }
+}
+
+/// Shows a macOS notification with the actions as buttons and blocks for at
+/// most `timeout`. Returns the chosen label, or `None` if nobody answered.
+pub fn ask_macos(
+    config: Option<&Config>,
+    notification: Notification,
+    timeout: Duration,
+) -> Result<Option<String>, NotifallError> {
+    let source = notification.source.clone();
+    let source_config = resolve_source_config(config, source.as_deref());
+    let macos_config = resolve_macos_config(config, source_config, source.as_deref());
+    let notification = truncate_message(config, "macos", notification);
+    // Fails here on other systems, where the helper could only report text.
+    MacosProvider::new(macos_config.clone())?;
+    let payload = WaitPayload {
+        notification,
+        macos: macos_config,
+        on_click: None,
+        context: None,
+        ask: true,
+        wait: false,
+    };
+    Ok(match click::wait_in_helper(payload, timeout)? {
+        Some(DeliveryOutcome::ActionButton(label)) => Some(label),
+        _ => None,
+    })
+}
+
+/// Asks in Telegram. While `ding telegram bot` is running it owns getUpdates,
+/// so the question is left to the bot like any pending message and the answer
+/// read back from it; only without the bot does ding poll for the press itself.
+pub(crate) fn ask_telegram(
+    cx: &SendContext,
+    notification: Notification,
+    timeout: Duration,
+) -> Result<Option<String>, NotifallError> {
+    let telegram_config = resolve_telegram_config(cx.config, &cx.overrides.telegram);
+    let provider = telegram_provider(telegram_config)?;
+    let notification = truncate_message(cx.config, "telegram", notification);
+    let pending = PendingStore::default_location();
+    if !pending.bot_running() {
+        return Ok(provider.ask(&notification, timeout)?);
+    }
+    let deadline = Instant::now() + timeout;
+    let sent = provider.send_message(&notification, true)?;
+    let payload = WaitPayload {
+        notification,
+        macos: None,
+        on_click: None,
+        context: None,
+        ask: true,
+        wait: false,
+    };
+    pending.save(sent, &payload)?;
+    debug_log(&format!(
+        "ask via telegram bot chat={} message={}",
+        sent.chat_id, sent.message_id
+    ));
+    match pending.wait_for_answer(sent, Some(deadline)) {
+        Some(DeliveryOutcome::ActionButton(label)) => Ok(Some(label)),
+        _ => {
+            provider.clear_buttons(sent);
+            Ok(None)
+        }
+    }
+}
+
+/// Asks on the listener's machine, which answers once a button is pressed
+/// there or `timeout` passes.
+pub(crate) fn ask_remote(
+    cx: &SendContext,
+    notification: Notification,
+    timeout: Duration,
+) -> Result<Option<String>, NotifallError> {
+    let mut notification = notification;
+    notification.icon = None;
+    let envelope = RemoteEnvelope {
+        notification: truncate_message(cx.config, "remote", notification),
+        context: Some(cx.origin.clone()),
+        timeout_ms: Some(timeout.as_millis() as u64),
+        progress: None,
+    };
+    let client = RemoteClient::from_config(
+        cx.config.and_then(|c| c.remote.as_ref()),
+        &cx.overrides.remote,
+    )?;
+    Ok(client.ask(&envelope, timeout)?)
+}
+
+/// Shows the latest state of a `ding progress` task in Telegram by editing
+/// the message sent for it before, or sending one if there is none yet.
+pub(crate) fn progress_telegram(
+    cx: &SendContext,
+    notification: Notification,
+    previous: Option<&serde_json::Value>,
+) -> Result<Option<serde_json::Value>, NotifallError> {
+    let telegram_config = resolve_telegram_config(cx.config, &cx.overrides.telegram);
+    let provider = telegram_provider(telegram_config)?;
+    let notification = truncate_message(cx.config, "telegram", notification);
+    let previous = previous.and_then(|value| SentMessage::deserialize(value).ok());
+    let edited = match previous {
+        Some(message) => match provider.edit_message(message, &notification) {
+            Ok(()) => Some(message),
+            // Deleted from the chat; start over with a new message.
+            Err(ProviderError::Rejected { body, .. })
+                if body.contains("message to edit not found") =>
+            {
+                None
+            }
+            Err(err) => return Err(err.into()),
+        },
+        None => None,
+    };
+    let message = match edited {
+        Some(message) => message,
+        None => provider.send_message(&notification, false)?,
+    };
+    Ok(Some(serde_json::to_value(message)?))
+}
+
+/// Shows a `ding progress` call in Notification Center. Each call replaces
+/// the last one under the task's group; without a way to replace, only the
+/// start and finish are shown, as updates would stack up.
+pub fn progress_macos(
+    cx: &SendContext,
+    task: &ProgressTask,
+    notification: Notification,
+) -> Result<(), NotifallError> {
+    let source = notification.source.clone();
+    let source_config = resolve_source_config(cx.config, source.as_deref());
+    let macos_config = resolve_macos_config(cx.config, source_config, source.as_deref());
+    let provider = MacosProvider::new(macos_config)?;
+    let notification = truncate_message(cx.config, "macos", notification);
+    match provider.replace(&task.group(), &notification) {
+        Ok(()) => Ok(()),
+        Err(ProviderError::Unsupported) if task.step == ProgressStep::Update => Ok(()),
+        Err(ProviderError::Unsupported) => {
+            provider.send(&notification, SendOptions::default())?;
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Passes a `ding progress` call on to the listener, which shows it with
+/// [`progress_macos`] so that it replaces the task's previous notification.
+pub(crate) fn progress_remote(
+    cx: &SendContext,
+    task: &ProgressTask,
+    mut notification: Notification,
+) -> Result<(), NotifallError> {
+    notification.icon = None;
+    let envelope = RemoteEnvelope {
+        notification: truncate_message(cx.config, "remote", notification),
+        context: Some(cx.origin.clone()),
+        timeout_ms: None,
+        progress: Some(task.clone()),
+    };
+    RemoteClient::from_config(
+        cx.config.and_then(|c| c.remote.as_ref()),
+        &cx.overrides.remote,
+    )?
+    .send(&envelope)?;
+    Ok(())
+}
+
+/// Embeds attachments in a notification headed for another machine, as long
+/// as they fit in the envelope budget and count; the rest are sent by name
+/// only.
+fn embed_attachments(notification: &mut Notification) {
+    let mut budget = attachment::MAX_ENVELOPE_ATTACHMENT_BYTES;
+    for (i, attachment) in notification.attachments.iter_mut().enumerate() {
+        if i >= attachment::MAX_ENVELOPE_ATTACHMENTS {
+            budget = 0;
+        }
+        attachment.embed(budget);
+        if attachment.data.is_some() {
+            budget -= attachment.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForwardConfig;
+    use crate::provider::registry::{Capabilities, ProviderPlugin};
+
+    struct Failing(&'static str, fn() -> ProviderError);
+
+    impl ProviderPlugin for Failing {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn deliver(
+            &self,
+            _cx: &SendContext,
+            _notification: Notification,
+            _targets: &mut Vec<TargetResult>,
+        ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+            Err((self.1)().into())
+        }
+    }
+
+    /// A remote target whose listener timed out, shown locally instead.
+    struct FellBack;
+
+    impl ProviderPlugin for FellBack {
+        fn name(&self) -> &'static str {
+            "remote"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn deliver(
+            &self,
+            _cx: &SendContext,
+            _notification: Notification,
+            targets: &mut Vec<TargetResult>,
+        ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+            let err = ProviderError::Timeout("connect".into()).into();
+            targets.push(TargetResult::failed("remote", &err));
+            targets.push(TargetResult::ok("macos", None));
+            Ok(None)
+        }
+    }
+
+    fn forward_to(registry: &ProviderRegistry, targets: &[&str]) -> NotifallError {
+        let config = Config {
+            forward: Some(ForwardConfig {
+                enabled: Some(true),
+                targets: Some(targets.iter().map(|t| t.to_string()).collect()),
+            }),
+            ..Default::default()
+        };
+        let cx = SendContext {
+            config: Some(&config),
+            overrides: &SendOverrides::default(),
+            origin: &RemoteContext::default(),
+            interaction: &Interaction::default(),
+            registry,
+        };
+        let mut results = Vec::new();
+        let err = forward(&cx, Notification::new("Build", "done"), &mut results)
+            .expect_err("every target fails");
+        assert_eq!(results.len(), targets.len());
+        assert!(results.iter().all(|result| !result.ok));
+        err
+    }
+
+    #[test]
+    fn forward_keeps_the_retryable_error_when_every_target_fails() {
+        let mut registry = ProviderRegistry::empty();
+        registry.register(Failing("slow", || ProviderError::Timeout("read".into())));
+        registry.register(Failing("refused", || ProviderError::Auth("token".into())));
+
+        let err = forward_to(&registry, &["refused", "slow"]);
+        assert!(err.retryable());
+        assert_eq!(err.kind(), "timeout");
+        assert!(err.to_string().contains("slow: provider error: timed out"));
+    }
+
+    #[test]
+    fn forward_records_a_remote_fallback_as_two_targets() {
+        let mut registry = ProviderRegistry::empty();
+        registry.register(FellBack);
+        let config = Config {
+            forward: Some(ForwardConfig {
+                enabled: Some(true),
+                targets: Some(vec!["remote".to_string()]),
+            }),
+            ..Default::default()
+        };
+        let cx = SendContext {
+            config: Some(&config),
+            overrides: &SendOverrides::default(),
+            origin: &RemoteContext::default(),
+            interaction: &Interaction::default(),
+            registry: &registry,
+        };
+        let mut results = Vec::new();
+        forward(&cx, Notification::new("Build", "done"), &mut results).unwrap();
+        let recorded = results
+            .iter()
+            .map(|r| (r.provider.as_str(), r.ok, r.kind.as_deref()))
+            .collect::<Vec<_>>();
+        // The listener is still owed the notification, so replays retry it.
+        assert_eq!(
+            recorded,
+            [("remote", false, Some("timeout")), ("macos", true, None)]
+        );
+    }
+
+    #[test]
+    fn forward_is_not_retryable_when_no_target_is() {
+        let mut registry = ProviderRegistry::empty();
+        registry.register(Failing("refused", || ProviderError::Auth("token".into())));
+
+        let err = forward_to(&registry, &["refused", "missing"]);
+        assert!(!err.retryable());
+        assert_eq!(err.kind(), "unsupported");
+    }
+
+    #[test]
+    fn attachments_are_embedded_until_the_envelope_budget_runs_out() {
+        let dir = std::env::temp_dir().join(format!("ding-embed-{}", std::process::id()));
+        let big = vec![b'x'; (attachment::MAX_ENVELOPE_ATTACHMENT_BYTES / 2 + 1) as usize];
+        let mut notification = Notification::new("Build", "failed");
+        for name in ["a.log", "b.log", "c.log"] {
+            let bytes = if name == "c.log" {
+                &b"tiny"[..]
+            } else {
+                &big[..]
+            };
+            notification
+                .attachments
+                .push(attachment::Attachment::from_bytes(&dir, name, bytes).unwrap());
+        }
+        embed_attachments(&mut notification);
+        let embedded = notification
+            .attachments
+            .iter()
+            .map(|a| (a.name.as_str(), a.data.is_some(), a.path.is_some()))
+            .collect::<Vec<_>>();
+        // b.log no longer fits next to a.log; the small c.log still does.
+        assert_eq!(
+            embedded,
+            [
+                ("a.log", true, false),
+                ("b.log", false, false),
+                ("c.log", true, false)
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_so_many_attachments_are_embedded() {
+        let dir = std::env::temp_dir().join(format!("ding-embed-count-{}", std::process::id()));
+        let mut notification = Notification::new("Build", "failed");
+        for i in 0..attachment::MAX_ENVELOPE_ATTACHMENTS + 2 {
+            notification.attachments.push(
+                attachment::Attachment::from_bytes(&dir, &format!("{i}.log"), b"tiny").unwrap(),
+            );
+        }
+        embed_attachments(&mut notification);
+        let embedded = notification
+            .attachments
+            .iter()
+            .filter(|a| a.data.is_some())
+            .count();
+        assert_eq!(embedded, attachment::MAX_ENVELOPE_ATTACHMENTS);
+        assert!(notification.attachments.iter().all(|a| a.path.is_none()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
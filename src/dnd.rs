@@ -0,0 +1,74 @@
+use crate::duration::format_duration;
+use crate::history::{format_timestamp, now_secs};
+use crate::notification::{Notification, Urgency};
+use crate::state::default_state_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Do-not-disturb state, kept in the state directory so every ding process
+/// (hooks, the listener, the Telegram bot) sees the same setting.
+#[derive(Debug, Clone)]
+pub struct DndStore {
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DndState {
+    until: u64,
+}
+
+impl DndStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The store every ding process shares, in the state directory.
+    pub fn default_location() -> Self {
+        Self::new(default_state_dir().join("dnd.json"))
+    }
+
+    /// Whether do-not-disturb holds `notification` back. High urgency always
+    /// gets through.
+    pub fn suppresses(&self, notification: &Notification) -> bool {
+        !matches!(notification.urgency, Some(Urgency::High)) && self.active_until().is_some()
+    }
+
+    /// When do-not-disturb ends, if it is currently on.
+    pub fn active_until(&self) -> Option<u64> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        let state: DndState = serde_json::from_str(&contents).ok()?;
+        (state.until > now_secs()).then_some(state.until)
+    }
+
+    /// Turns do-not-disturb on for `duration` and returns when it ends.
+    pub fn enable(&self, duration: Duration) -> Result<u64, std::io::Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let until = now_secs().saturating_add(duration.as_secs());
+        fs::write(&self.path, serde_json::to_vec(&DndState { until })?)?;
+        Ok(until)
+    }
+
+    /// Whether do-not-disturb is on and for how long, as `ding dnd status`
+    /// shows it.
+    pub fn status(&self) -> String {
+        match self.active_until() {
+            Some(until) => format!(
+                "do not disturb: on until {} ({} left)",
+                format_timestamp(until),
+                format_duration(Duration::from_secs(until.saturating_sub(now_secs())))
+            ),
+            None => "do not disturb: off".to_string(),
+        }
+    }
+
+    pub fn disable(&self) -> Result<(), std::io::Error> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use crate::config::Config;
+use crate::context::{detect_context, focusable_app};
+use crate::error::NotifallError;
+use crate::install::{self, Agent, HookState, Scope};
+use crate::provider::ProviderError;
+use crate::provider::registry::{ProviderPlugin, ProviderRegistry};
+use crate::remote::{RemoteClient, RemoteOverrides};
+use crate::resolve::{
+    LSREGISTER, TelegramOverrides, resolve_provider, resolve_telegram_config, source_bundles,
+};
+use crate::schema;
+use crate::state::{cache_dir, default_state_dir};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// How long `ding doctor` waits for the listener and the Bot API.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Works, but something the user probably wants is off.
+    Warn,
+    /// Broken; notifications through this path will fail.
+    Fail,
+    /// Doesn't apply here, e.g. macOS checks on Linux.
+    Skip,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+/// One line of the `ding doctor` report.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// The group the check is listed under, e.g. "remote".
+    pub section: &'static str,
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    pub fn new(
+        section: &'static str,
+        name: impl Into<String>,
+        status: Status,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            section,
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Parses and validates the config at `path`. Returns the config, if it
+/// loads at all, so later checks can use it.
+pub fn check_config(path: &Path, registry: &ProviderRegistry) -> (Option<Config>, Vec<Check>) {
+    let section = "config";
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let check = Check::new(
+                section,
+                "file",
+                Status::Ok,
+                format!("{} not found; using defaults", path.display()),
+            );
+            return (None, vec![check]);
+        }
+        Err(err) => {
+            let check = Check::new(section, "file", Status::Fail, err.to_string());
+            return (None, vec![check]);
+        }
+    };
+
+    let diagnostics = schema::validate(&contents, registry);
+    let mut checks = vec![if diagnostics.is_empty() {
+        Check::new(section, "file", Status::Ok, path.display().to_string())
+    } else {
+        Check::new(
+            section,
+            "file",
+            Status::Fail,
+            format!("{} has {} problem(s)", path.display(), diagnostics.len()),
+        )
+        .with_hint("fix them, then run `ding config validate`")
+    }];
+    checks.extend(diagnostics.into_iter().map(|diagnostic| {
+        let name = format!("line {}", diagnostic.line);
+        Check::new(section, name, Status::Fail, diagnostic.message)
+    }));
+    (lenient_config(&contents), checks)
+}
+
+/// The config in `contents`, keeping the top-level sections that load when
+/// others don't, so one bad value doesn't make every later check report
+/// "not configured". `None` if the file isn't TOML at all.
+fn lenient_config(contents: &str) -> Option<Config> {
+    if let Ok(config) = toml::from_str(contents) {
+        return Some(config);
+    }
+    let table: toml::Table = toml::from_str(contents).ok()?;
+    let valid = table
+        .into_iter()
+        .filter(|(key, value)| {
+            let section = toml::Table::from_iter([(key.clone(), value.clone())]);
+            Config::deserialize(section).is_ok()
+        })
+        .collect::<toml::Table>();
+    Config::deserialize(valid).ok()
+}
+
+/// The terminal and tmux pane a notification clicked now would focus.
+pub fn check_context() -> Vec<Check> {
+    let section = "context";
+    let context = detect_context().unwrap_or_default();
+    let mut checks = Vec::new();
+
+    checks.push(match std::env::var("TERM_PROGRAM").ok() {
+        Some(program) => match focusable_app(&program) {
+            Some(app) => Check::new(
+                section,
+                "terminal",
+                Status::Ok,
+                format!("{program} ({app})"),
+            ),
+            None => Check::new(
+                section,
+                "terminal",
+                Status::Warn,
+                format!("{program} can't be focused on click"),
+            )
+            .with_hint("ding focuses Ghostty, iTerm, and Terminal"),
+        },
+        None => Check::new(section, "terminal", Status::Warn, "TERM_PROGRAM is not set")
+            .with_hint("clicking a notification won't bring the terminal to the front"),
+    });
+
+    let inside_tmux = std::env::var("TMUX").is_ok();
+    let tmux_version = Command::new("tmux")
+        .arg("-V")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    checks.push(match (&context.tmux, inside_tmux, tmux_version) {
+        (Some(tmux), _, _) => Check::new(
+            section,
+            "tmux",
+            Status::Ok,
+            format!(
+                "session {} window {} pane {}",
+                tmux.session, tmux.window, tmux.pane
+            ),
+        ),
+        (None, true, None) => Check::new(
+            section,
+            "tmux",
+            Status::Fail,
+            "TMUX is set but tmux is not on PATH",
+        )
+        .with_hint("clicks can't switch to the pane a notification came from"),
+        (None, true, Some(_)) => Check::new(
+            section,
+            "tmux",
+            Status::Fail,
+            "TMUX is set but `tmux display-message` failed",
+        )
+        .with_hint("check that the tmux server is still running"),
+        (None, false, Some(version)) => Check::new(
+            section,
+            "tmux",
+            Status::Skip,
+            format!("not inside tmux ({version})"),
+        ),
+        (None, false, None) => Check::new(section, "tmux", Status::Skip, "tmux not found"),
+    });
+    checks
+}
+
+/// Whether each provider can send from here. Missing settings only fail for
+/// providers the config actually sends through.
+pub fn check_providers(config: Option<&Config>, registry: &ProviderRegistry) -> Vec<Check> {
+    let section = "providers";
+    let mut in_use: Vec<String> = config
+        .and_then(|c| c.forward.as_ref())
+        .and_then(|f| f.targets.clone())
+        .unwrap_or_default();
+    if let Ok(provider) = resolve_provider(None, config) {
+        in_use.push(provider);
+    }
+
+    registry
+        .iter()
+        .map(|provider| {
+            let name = provider.name();
+            let used = in_use.iter().any(|p| p == name);
+            if !provider.available() {
+                let status = if used { Status::Fail } else { Status::Skip };
+                return Check::new(
+                    section,
+                    name,
+                    status,
+                    format!("not available on {}", std::env::consts::OS),
+                );
+            }
+            match provider.check_config(config) {
+                Ok(()) => Check::new(section, name, Status::Ok, "ready"),
+                Err(err) if used => Check::new(section, name, Status::Fail, err.to_string())
+                    .with_hint(setting_hint(provider, &err)),
+                Err(err) => Check::new(section, name, Status::Skip, err.to_string()),
+            }
+        })
+        .collect()
+}
+
+fn setting_hint(provider: &dyn ProviderPlugin, err: &ProviderError) -> String {
+    match err {
+        ProviderError::NotConfigured { field } => format!("ding config set {field} <VALUE>"),
+        _ => format!("check the [{}] config", provider.name()),
+    }
+}
+
+/// Pings the listener in `[remote]` with the configured token.
+pub fn check_remote(config: Option<&Config>) -> Vec<Check> {
+    let section = "remote";
+    let remote = config.and_then(|c| c.remote.as_ref());
+    let client = match RemoteClient::from_config(remote, &RemoteOverrides::default()) {
+        Ok(client) => client.with_timeout(NETWORK_TIMEOUT),
+        Err(_) => {
+            return vec![Check::new(
+                section,
+                "listener",
+                Status::Skip,
+                "remote.host is not set",
+            )];
+        }
+    };
+    let url = client.url().to_string();
+    let check = match client.ping() {
+        Ok(()) => {
+            let detail = if remote.and_then(|r| r.token.as_ref()).is_some() {
+                format!("{url} accepted the token")
+            } else {
+                format!("{url} is reachable")
+            };
+            Check::new(section, "listener", Status::Ok, detail)
+        }
+        Err(ProviderError::Auth(_)) => Check::new(
+            section,
+            "listener",
+            Status::Fail,
+            format!("{url} rejected the token"),
+        )
+        .with_hint("remote.token must match the listener's --token"),
+        Err(ProviderError::Rejected { status: 403, .. }) => Check::new(
+            section,
+            "listener",
+            Status::Fail,
+            format!("{url} doesn't allow this host"),
+        )
+        .with_hint("add this machine to the listener's listener.allow_hosts"),
+        Err(err) => Check::new(
+            section,
+            "listener",
+            Status::Fail,
+            format!("{url} is unreachable: {err}"),
+        )
+        .with_hint("start `ding listen` on the receiving machine and check the firewall"),
+    };
+    vec![check]
+}
+
+/// Asks the Bot API who the configured bot is.
+pub fn check_telegram(config: Option<&Config>) -> Vec<Check> {
+    let section = "telegram";
+    let mut telegram_config = resolve_telegram_config(config, &TelegramOverrides::default());
+    if telegram_config.bot_token.is_none() {
+        return vec![Check::new(
+            section,
+            "bot_token",
+            Status::Skip,
+            "telegram.bot_token is not set",
+        )];
+    }
+    telegram_config.timeout_ms = Some(NETWORK_TIMEOUT.as_millis() as u64);
+    telegram_config.retries = Some(0);
+    // getMe needs no chat; a placeholder keeps the constructor happy when
+    // only the token is set.
+    telegram_config
+        .chat_id
+        .get_or_insert_with(|| "0".to_string());
+    let check = match crate::provider::telegram::TelegramProvider::new(telegram_config)
+        .and_then(|provider| provider.get_me())
+    {
+        Ok(username) => Check::new(section, "bot_token", Status::Ok, format!("@{username}")),
+        Err(err @ (ProviderError::Auth(_) | ProviderError::Rejected { .. })) => Check::new(
+            section,
+            "bot_token",
+            Status::Fail,
+            format!("rejected: {err}"),
+        )
+        .with_hint("get a new token from @BotFather"),
+        Err(err) => Check::new(
+            section,
+            "bot_token",
+            Status::Warn,
+            format!("couldn't reach Telegram: {err}"),
+        ),
+    };
+    vec![check]
+}
+
+/// The directories ding writes to: temp for stdin attachments in flight,
+/// cache for app bundles, and state for history and queues.
+/// Checks that the Claude and Codex hooks, if installed, run this binary.
+/// Anything that stops a check is reported as a failed check, so the rest
+/// of the report still runs.
+pub fn check_hooks() -> Vec<Check> {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => {
+            let message = format!("can't locate the ding binary: {err}");
+            return vec![Check::new("hooks", "binary", Status::Fail, message)];
+        }
+    };
+    let mut checks = Vec::new();
+    for agent in [Agent::Claude, Agent::Codex] {
+        let name = agent.name();
+        let path = match install::config_path(agent, Scope::User) {
+            Ok(path) => path,
+            Err(err) => {
+                checks.push(Check::new("hooks", name, Status::Fail, err.to_string()));
+                continue;
+            }
+        };
+        let statuses = match install::statuses(agent, &path, &exe) {
+            Ok(statuses) => statuses.unwrap_or_default(),
+            Err(err) => {
+                checks.push(Check::new(
+                    "hooks",
+                    name,
+                    Status::Fail,
+                    format!("can't read {}: {err}", path.display()),
+                ));
+                continue;
+            }
+        };
+        let fix = format!("ding install {name} --apply");
+        let missing = statuses
+            .iter()
+            .find(|status| status.state == HookState::Missing);
+        let stale = statuses
+            .iter()
+            .find(|status| status.state == HookState::Stale);
+        checks.push(if statuses.is_empty() {
+            Check::new("hooks", name, Status::Skip, "not installed")
+        } else if let Some(status) = missing {
+            Check::new(
+                "hooks",
+                name,
+                Status::Fail,
+                format!("{} no longer exists", status.binary.display()),
+            )
+            .with_hint(fix)
+        } else if let Some(status) = stale {
+            Check::new(
+                "hooks",
+                name,
+                Status::Warn,
+                format!("runs another ding at {}", status.binary.display()),
+            )
+            .with_hint(fix)
+        } else {
+            Check::new(
+                "hooks",
+                name,
+                Status::Ok,
+                format!("{} hooks in {}", statuses.len(), path.display()),
+            )
+        });
+    }
+    checks
+}
+
+pub fn check_directories() -> Vec<Check> {
+    [
+        ("temp", std::env::temp_dir()),
+        ("cache", cache_dir()),
+        ("state", default_state_dir()),
+    ]
+    .into_iter()
+    .map(|(name, dir)| match probe_writable(&dir) {
+        Ok(()) => Check::new("directories", name, Status::Ok, dir.display().to_string()),
+        Err(err) => Check::new(
+            "directories",
+            name,
+            Status::Fail,
+            format!("{} is not writable: {err}", dir.display()),
+        ),
+    })
+    .collect()
+}
+
+fn probe_writable(dir: &Path) -> Result<(), NotifallError> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".ding-doctor-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+/// Whether the generated agent app bundles exist and Launch Services knows
+/// them; without that, agent notifications fall back to the terminal's icon.
+pub fn check_bundles() -> Vec<Check> {
+    let section = "bundles";
+    if !cfg!(target_os = "macos") {
+        return vec![Check::new(section, "apps", Status::Skip, "macOS only")];
+    }
+    let registered = Command::new(LSREGISTER)
+        .arg("-dump")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+
+    source_bundles()
+        .into_iter()
+        .map(|(source, bundle_id, path)| {
+            if !path.join("Contents/Info.plist").exists() {
+                return Check::new(
+                    section,
+                    source,
+                    Status::Skip,
+                    format!("created on the first {source} notification"),
+                );
+            }
+            match &registered {
+                Some(dump) if dump.contains(bundle_id) => {
+                    Check::new(section, source, Status::Ok, bundle_id)
+                }
+                Some(_) => Check::new(
+                    section,
+                    source,
+                    Status::Warn,
+                    format!("{bundle_id} is not registered"),
+                )
+                .with_hint(format!("ding send --source {source} test re-registers it")),
+                None => Check::new(section, source, Status::Warn, "lsregister not found"),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_config_keeps_the_sections_that_load() {
+        let path = std::env::temp_dir().join(format!("ding-doctor-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[remote]\nport = \"many\"\n\n[telegram]\nbot_token = \"1:x\"\nchat_id = \"5\"\n",
+        )
+        .unwrap();
+        let (config, checks) = check_config(&path, &ProviderRegistry::builtin());
+        let _ = fs::remove_file(&path);
+
+        assert!(checks.iter().any(|check| check.status == Status::Fail));
+        let config = config.expect("the telegram section still loads");
+        assert!(config.remote.is_none());
+        assert_eq!(
+            config.telegram.and_then(|t| t.chat_id).as_deref(),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn check_config_reports_unparsable_files() {
+        let path =
+            std::env::temp_dir().join(format!("ding-doctor-bad-{}.toml", std::process::id()));
+        fs::write(&path, "[remote\n").unwrap();
+        let (config, checks) = check_config(&path, &ProviderRegistry::builtin());
+        let _ = fs::remove_file(&path);
+
+        assert!(config.is_none());
+        assert_eq!(checks[0].status, Status::Fail);
+        assert!(checks.iter().any(|check| check.name == "line 1"));
+    }
+}
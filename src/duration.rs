@@ -0,0 +1,34 @@
+use crate::error::NotifallError;
+use std::time::Duration;
+
+/// Parses a duration like `30s`, `5m`, `2h`, or `7d`; a bare number is seconds.
+pub fn parse_duration(raw: &str) -> Result<Duration, NotifallError> {
+    let raw = raw.trim();
+    let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (digits, unit) = raw.split_at(split);
+    let value = digits
+        .parse::<u64>()
+        .map_err(|_| NotifallError::InvalidDuration(raw.to_string()))?;
+    let secs = match unit.trim() {
+        "" | "s" | "sec" | "secs" => value,
+        "m" | "min" | "mins" => value * 60,
+        "h" | "hr" | "hrs" => value * 3600,
+        "d" | "day" | "days" => value * 86_400,
+        "w" | "wk" | "wks" => value * 604_800,
+        _ => return Err(NotifallError::InvalidDuration(raw.to_string())),
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+/// A short human-readable duration: `4.2s`, `3m 12s`, or `1h 5m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        return format!("{:.1}s", duration.as_secs_f64());
+    }
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        return format!("{hours}h {minutes}m");
+    }
+    format!("{minutes}m {seconds}s")
+}
@@ -9,12 +9,23 @@ pub enum NotifallError {
     ConfigWrite(#[from] toml::ser::Error),
     #[error("config edit error: {0}")]
     ConfigEdit(#[from] toml_edit::TomlError),
+    #[error("invalid config: {0}")]
+    ConfigInvalid(String),
+    #[error("{0} is not set")]
+    ConfigKeyNotSet(String),
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
     #[error("provider error: {0}")]
     Provider(#[from] ProviderError),
+    /// Forwarding or asking tried several targets and none succeeded; holds
+    /// each target's error.
+    #[error("{action} failed for all targets ({})", describe_target_errors(.errors))]
+    AllTargetsFailed {
+        action: &'static str,
+        errors: Vec<(String, NotifallError)>,
+    },
     #[error("unsupported provider: {0}")]
     ProviderUnsupported(String),
     #[error("config file already exists: {0}")]
@@ -24,7 +35,84 @@ pub enum NotifallError {
     #[error("--background requires --on-click")]
     BackgroundRequiresOnClick,
     #[error("{0}")]
-    RemoteForwardMissingHost(String),
+    ForwardSetup(String),
     #[error("HOME is not set")]
     MissingHome,
+    #[error("invalid duration: {0} (expected e.g. 30s, 5m, 2h, 7d)")]
+    InvalidDuration(String),
+    #[error("no history entry matches {0}")]
+    HistoryNotFound(String),
+    #[error("history id {id} is ambiguous; it matches {}", .matches.join(", "))]
+    HistoryAmbiguous { id: String, matches: Vec<String> },
+    #[error("invalid metadata: {0} (expected KEY=VALUE)")]
+    InvalidMetadata(String),
+    #[error("invalid action: {0} (expected LABEL=url:URL or LABEL=cmd:COMMAND)")]
+    InvalidAction(String),
+    #[error("invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("{0}")]
+    WatchTargetMissing(String),
+    #[error("{0}")]
+    Install(String),
+    #[error("attachment {0}")]
+    Attachment(String),
+    #[error("suppressed by do not disturb")]
+    Suppressed,
+    #[error("no [hook_mappings.{0}] in config")]
+    UnknownHookMapping(String),
+    #[error("unknown codex event: {0} (expected failed, approval, file-change, plan, complete)")]
+    UnknownCodexEvent(String),
+    #[error("{0} replay(s) failed")]
+    ReplayFailed(usize),
+    #[error("another ding telegram bot is already running")]
+    BotAlreadyRunning,
+    #[error("no chat_id found in getUpdates (send a message to the bot first)")]
+    NoTelegramChat,
+}
+
+impl NotifallError {
+    /// Machine-readable failure category, as shown in `--json` output and
+    /// history: the provider error kind, or a few ding-level ones.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Provider(err) => err.kind(),
+            Self::AllTargetsFailed { errors, .. } => {
+                most_retryable(errors).map_or("other", |err| err.kind())
+            }
+            Self::ProviderUnsupported(_) | Self::NoProviderAvailable => "unsupported",
+            Self::Suppressed => "suppressed",
+            Self::Io(_) => "io",
+            _ => "other",
+        }
+    }
+
+    /// Whether delivery may succeed if tried again later. When every target
+    /// failed, that holds if any of them may succeed.
+    pub fn retryable(&self) -> bool {
+        match self {
+            Self::Provider(err) => err.retryable(),
+            Self::AllTargetsFailed { errors, .. } => {
+                most_retryable(errors).is_some_and(|err| err.retryable())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The first retryable error, or else the last one, which stands for the
+/// whole failure in `kind` and `retryable`.
+fn most_retryable(errors: &[(String, NotifallError)]) -> Option<&NotifallError> {
+    errors
+        .iter()
+        .map(|(_, err)| err)
+        .find(|err| err.retryable())
+        .or_else(|| errors.last().map(|(_, err)| err))
+}
+
+fn describe_target_errors(errors: &[(String, NotifallError)]) -> String {
+    errors
+        .iter()
+        .map(|(target, err)| format!("{target}: {err}"))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
@@ -0,0 +1,525 @@
+use crate::config::{Config, HistoryConfig};
+use crate::debug::debug_log;
+use crate::error::NotifallError;
+use crate::notification::Notification;
+use crate::provider::DeliveryOutcome;
+use crate::remote::RemoteContext;
+use crate::state::default_state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+const DEFAULT_MAX_AGE_DAYS: u64 = 30;
+
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Sent,
+    Received,
+}
+
+impl HistoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetResult {
+    pub provider: String,
+    pub ok: bool,
+    pub id: Option<String>,
+    pub error: Option<String>,
+    /// Machine-readable failure category, see `NotifallError::kind`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+impl TargetResult {
+    pub fn ok(provider: &str, id: Option<String>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            ok: true,
+            id,
+            error: None,
+            kind: None,
+        }
+    }
+
+    pub fn failed(provider: &str, error: &NotifallError) -> Self {
+        Self {
+            provider: provider.to_string(),
+            ok: false,
+            id: None,
+            error: Some(error.to_string()),
+            kind: Some(error.kind().to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub timestamp: u64,
+    pub kind: HistoryKind,
+    pub provider: String,
+    pub notification: Notification,
+    pub context: Option<RemoteContext>,
+    pub targets: Vec<TargetResult>,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub outcome: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(kind: HistoryKind, provider: &str, notification: Notification) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        let id = format!(
+            "{:x}{:04x}{:02x}",
+            now.as_millis(),
+            std::process::id() & 0xffff,
+            seq & 0xff
+        );
+        Self {
+            id,
+            timestamp: now.as_secs(),
+            kind,
+            provider: provider.to_string(),
+            notification,
+            context: None,
+            targets: Vec::new(),
+            ok: true,
+            error: None,
+            outcome: None,
+            replay_of: None,
+        }
+    }
+}
+
+impl HistoryEntry {
+    /// The providers this entry didn't reach: the forwarding targets that
+    /// failed, or the provider itself when the whole send failed.
+    pub fn failed_targets(&self) -> Vec<String> {
+        if self.provider == "forward" && !self.targets.is_empty() {
+            return self
+                .targets
+                .iter()
+                .filter(|target| !target.ok)
+                .map(|target| target.provider.clone())
+                .collect();
+        }
+        if self.ok {
+            Vec::new()
+        } else {
+            vec![self.provider.clone()]
+        }
+    }
+}
+
+/// Entries that still have providers to reach, as `ding replay --failed`
+/// sends them: each failed target minus those a successful replay of the
+/// entry has reached since.
+pub fn pending_replays(entries: &[HistoryEntry]) -> Vec<(&HistoryEntry, Vec<String>)> {
+    let mut delivered: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for entry in entries.iter().filter(|e| e.ok) {
+        if let Some(original) = entry.replay_of.as_deref() {
+            delivered
+                .entry(original)
+                .or_default()
+                .insert(entry.provider.as_str());
+        }
+    }
+    entries
+        .iter()
+        .filter(|entry| entry.replay_of.is_none())
+        .filter_map(|entry| {
+            let done = delivered.get(entry.id.as_str());
+            if done.is_some_and(|done| done.contains(entry.provider.as_str())) {
+                return None;
+            }
+            let remaining = entry
+                .failed_targets()
+                .into_iter()
+                .filter(|target| done.is_none_or(|done| !done.contains(target.as_str())))
+                .collect::<Vec<_>>();
+            (!remaining.is_empty()).then_some((entry, remaining))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    path: PathBuf,
+    max_entries: usize,
+    max_age_secs: u64,
+}
+
+impl HistoryStore {
+    /// The store `[history]` points at, by default in the state directory.
+    pub fn from_config(config: Option<&Config>) -> Self {
+        let history_cfg = config.and_then(|c| c.history.as_ref());
+        let path = history_cfg
+            .and_then(|c| c.path.clone())
+            .unwrap_or_else(|| default_state_dir().join("history.jsonl"));
+        Self::new(path, history_cfg)
+    }
+
+    pub fn new(path: PathBuf, config: Option<&HistoryConfig>) -> Self {
+        let max_entries = config
+            .and_then(|c| c.max_entries)
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+        let max_age_days = config
+            .and_then(|c| c.max_age_days)
+            .unwrap_or(DEFAULT_MAX_AGE_DAYS);
+        Self {
+            path,
+            max_entries,
+            max_age_secs: max_age_days.saturating_mul(86_400),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<(), std::io::Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let _lock = self.lock()?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        drop(file);
+        self.prune()
+    }
+
+    /// Entries in the order they were recorded (oldest first).
+    /// Lines that fail to parse are skipped so one bad write never hides the rest.
+    pub fn load(&self) -> Result<Vec<HistoryEntry>, std::io::Error> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Looks up an entry by exact id, falling back to a unique id prefix.
+    pub fn find(&self, id: &str) -> Result<HistoryEntry, NotifallError> {
+        let entries = self.load()?;
+        if let Some(entry) = entries.iter().find(|e| e.id == id) {
+            return Ok(entry.clone());
+        }
+        let mut matches = entries
+            .into_iter()
+            .filter(|e| e.id.starts_with(id))
+            .collect::<Vec<_>>();
+        match matches.len() {
+            0 => Err(NotifallError::HistoryNotFound(id.to_string())),
+            1 => Ok(matches.remove(0)),
+            _ => Err(NotifallError::HistoryAmbiguous {
+                id: id.to_string(),
+                matches: matches.into_iter().map(|e| e.id).collect(),
+            }),
+        }
+    }
+
+    /// Serializes writers, so a prune never renames away another process's
+    /// append. Released when the returned file is closed; readers don't take
+    /// it since the rename is atomic.
+    fn lock(&self) -> Result<fs::File, std::io::Error> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("lock"))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    /// Drops entries past the age and count limits. Callers hold [`Self::lock`].
+    fn prune(&self) -> Result<(), std::io::Error> {
+        let entries = self.load()?;
+        let cutoff = now_secs().saturating_sub(self.max_age_secs);
+        let expired = self.max_age_secs > 0 && entries.iter().any(|e| e.timestamp < cutoff);
+        if entries.len() <= self.max_entries && !expired {
+            return Ok(());
+        }
+
+        let mut kept = entries
+            .into_iter()
+            .filter(|e| self.max_age_secs == 0 || e.timestamp >= cutoff)
+            .collect::<Vec<_>>();
+        if kept.len() > self.max_entries {
+            kept.drain(..kept.len() - self.max_entries);
+        }
+
+        let mut contents = String::new();
+        for entry in &kept {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+        let tmp = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Appends a delivery attempt and its per-target results to history, unless
+/// `history.enabled` is off. Failing to write history never fails a send.
+pub fn record_history(
+    config: Option<&Config>,
+    mut entry: HistoryEntry,
+    targets: Vec<TargetResult>,
+    result: &Result<Option<DeliveryOutcome>, NotifallError>,
+) {
+    let enabled = config
+        .and_then(|c| c.history.as_ref())
+        .and_then(|h| h.enabled)
+        .unwrap_or(true);
+    if !enabled {
+        return;
+    }
+
+    entry.targets = targets;
+    match result {
+        Ok(outcome) => entry.outcome = outcome.as_ref().map(DeliveryOutcome::to_json),
+        Err(err) => {
+            entry.ok = false;
+            entry.error = Some(err.to_string());
+        }
+    }
+
+    let store = HistoryStore::from_config(config);
+    if let Err(err) = store.append(&entry) {
+        debug_log(&format!(
+            "history write failed path={} err={err}",
+            store.path().display()
+        ));
+    }
+}
+
+/// Records a notification held back by do-not-disturb as a failed history
+/// entry, so `ding replay --failed` delivers it afterwards.
+pub fn record_suppressed(config: Option<&Config>, entry: HistoryEntry) {
+    debug_log(&format!(
+        "do not disturb: suppressed \"{}\"",
+        entry.notification.title
+    ));
+    record_history(config, entry, Vec::new(), &Err(NotifallError::Suppressed));
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM:SS` (UTC).
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, provider: &str, targets: &[(&str, bool)]) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(
+            HistoryKind::Sent,
+            provider,
+            Notification::new("Build", "failed"),
+        );
+        entry.id = id.to_string();
+        entry.targets = targets
+            .iter()
+            .map(|&(provider, ok)| {
+                if ok {
+                    TargetResult::ok(provider, None)
+                } else {
+                    TargetResult::failed(provider, &NotifallError::Suppressed)
+                }
+            })
+            .collect();
+        entry.ok = targets.iter().any(|&(_, ok)| ok);
+        entry
+    }
+
+    fn replay(id: &str, of: &str, provider: &str, ok: bool) -> HistoryEntry {
+        let mut entry = entry(id, provider, &[(provider, ok)]);
+        entry.replay_of = Some(of.to_string());
+        entry
+    }
+
+    fn pending(entries: &[HistoryEntry]) -> Vec<(String, Vec<String>)> {
+        pending_replays(entries)
+            .into_iter()
+            .map(|(entry, targets)| (entry.id.clone(), targets))
+            .collect()
+    }
+
+    #[test]
+    fn partial_forward_failures_replay_only_the_failed_targets() {
+        let entries = vec![entry(
+            "a",
+            "forward",
+            &[("telegram", true), ("remote", false)],
+        )];
+        assert!(entries[0].ok);
+        assert_eq!(
+            pending(&entries),
+            vec![("a".to_string(), vec!["remote".to_string()])]
+        );
+    }
+
+    #[test]
+    fn successful_replays_settle_their_targets() {
+        let mut entries = vec![
+            entry(
+                "a",
+                "forward",
+                &[("telegram", false), ("remote", false), ("macos", true)],
+            ),
+            replay("b", "a", "remote", true),
+            replay("c", "a", "telegram", false),
+        ];
+        assert_eq!(
+            pending(&entries),
+            vec![("a".to_string(), vec!["telegram".to_string()])]
+        );
+
+        entries.push(replay("d", "a", "telegram", true));
+        assert!(pending(&entries).is_empty());
+    }
+
+    #[test]
+    fn whole_failures_replay_through_the_original_provider() {
+        let entries = vec![
+            entry("a", "telegram", &[("telegram", false)]),
+            entry("b", "macos", &[("macos", true)]),
+        ];
+        assert_eq!(
+            pending(&entries),
+            vec![("a".to_string(), vec!["telegram".to_string()])]
+        );
+    }
+
+    #[test]
+    fn concurrent_appends_survive_pruning() {
+        let dir = std::env::temp_dir().join(format!("ding-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = HistoryConfig {
+            max_entries: Some(50),
+            ..HistoryConfig::default()
+        };
+        let store = HistoryStore::new(dir.join("history.jsonl"), Some(&config));
+        for n in 0..20 {
+            store
+                .append(&entry(&format!("old-{n}"), "telegram", &[]))
+                .unwrap();
+        }
+
+        // The last appends each prune the oldest entry while others write.
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let store = &store;
+                scope.spawn(move || {
+                    for n in 0..10 {
+                        store
+                            .append(&entry(&format!("{thread}-{n}"), "telegram", &[]))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let entries = store.load().unwrap();
+        assert_eq!(entries.len(), 50);
+        for thread in 0..4 {
+            for n in 0..10 {
+                let id = format!("{thread}-{n}");
+                assert!(entries.iter().any(|e| e.id == id), "lost {id}");
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn find_reports_ambiguous_prefixes() {
+        let dir = std::env::temp_dir().join(format!("ding-history-find-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = HistoryStore::new(dir.join("history.jsonl"), None);
+        for id in ["abc1", "abc2", "def1"] {
+            store.append(&entry(id, "telegram", &[])).unwrap();
+        }
+
+        assert_eq!(store.find("abc2").unwrap().id, "abc2");
+        assert_eq!(store.find("def").unwrap().id, "def1");
+        match store.find("abc") {
+            Err(NotifallError::HistoryAmbiguous { matches, .. }) => {
+                assert_eq!(matches, ["abc1", "abc2"]);
+            }
+            other => panic!("expected an ambiguous id, got {other:?}"),
+        }
+        assert!(matches!(
+            store.find("zzz"),
+            Err(NotifallError::HistoryNotFound(_))
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::claude;
+use crate::config::Config;
+use crate::debug::debug_log;
+use crate::delivery;
+use crate::duration::format_duration;
+use crate::error::NotifallError;
+use crate::mapping::MappedEvent;
+use crate::notification::{Action, Notification, Urgency};
+use crate::provider::registry::ProviderRegistry;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The hook payload from `--json`, or else stdin; `Null` when stdin is empty.
+pub fn read_payload(json: Option<&str>) -> Result<Value, NotifallError> {
+    if let Some(raw) = json {
+        return Ok(serde_json::from_str(raw)?);
+    }
+    let mut stdin = std::io::stdin();
+    let mut buf = Vec::new();
+    use std::io::Read;
+    stdin.read_to_end(&mut buf)?;
+    if buf.is_empty() {
+        return Ok(Value::Null);
+    }
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+/// Adds the fields the built-in Claude mapping reads under `/ding`, since
+/// templates can't compute them: `summary`, the last assistant message of the
+/// transcript for `Stop`; `tool`, what a tool call acts on; and `subtype`, the
+/// notification type, or `failed` for a tool call that failed.
+pub fn claude_payload(mut payload: Value) -> Value {
+    let hook = payload
+        .get("hook_event_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let mut fields = serde_json::Map::new();
+    if hook == "Stop"
+        && let Some(summary) = payload
+            .get("transcript_path")
+            .and_then(|v| v.as_str())
+            .and_then(stop_summary)
+    {
+        fields.insert("summary".to_string(), summary.into());
+    }
+    let tool = tool_summary(payload.get("tool_input"));
+    if !tool.is_empty() {
+        fields.insert("tool".to_string(), tool.into());
+    }
+    let subtype = match hook {
+        "PostToolUse" if tool_failed(payload.get("tool_response")) => Some("failed"),
+        _ => payload.get("notification_type").and_then(|v| v.as_str()),
+    };
+    if let Some(subtype) = subtype {
+        fields.insert("subtype".to_string(), subtype.into());
+    }
+    if let Some(object) = payload.as_object_mut() {
+        object.insert("ding".to_string(), Value::Object(fields));
+    }
+    payload
+}
+
+/// Answers a `PreToolUse` hook: asks Approve/Deny for the tool call in
+/// `payload` on the configured providers, waiting up to `timeout`, and returns
+/// the hook output with the matching `permissionDecision` (`allow` or `deny`). With no answer in
+/// time, or if asking fails, the decision is `ask`, so Claude Code falls back
+/// to its own terminal prompt instead of blocking the tool.
+pub fn permission_decision(
+    config: Option<&Config>,
+    registry: &ProviderRegistry,
+    payload: &Value,
+    timeout: Duration,
+) -> Value {
+    let tool = payload
+        .get("tool_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("tool");
+    let mut message = tool_summary(payload.get("tool_input"));
+    if message.is_empty() {
+        message = tool.to_string();
+    }
+    let notification = Notification {
+        title: format!("Claude Code: approve {tool}?"),
+        message,
+        source: Some("claude".to_string()),
+        urgency: Some(Urgency::High),
+        tag: Some("permission".to_string()),
+        metadata: Some(metadata(payload, &["session_id", "cwd", "tool_use_id"])),
+        actions: ["Approve", "Deny"]
+            .into_iter()
+            .map(|label| Action {
+                label: label.to_string(),
+                url: None,
+                command: None,
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    let (decision, reason) = match delivery::ask(config, registry, None, notification, timeout) {
+        Ok(Some(label)) if label == "Approve" => ("allow", "Approved via ding".to_string()),
+        Ok(Some(_)) => ("deny", "Denied via ding".to_string()),
+        Ok(None) => (
+            "ask",
+            format!("No answer via ding within {}", format_duration(timeout)),
+        ),
+        Err(err) => ("ask", format!("ding could not ask: {err}")),
+    };
+    serde_json::json!({
+        "hookSpecificOutput": {
+            "hookEventName": "PreToolUse",
+            "permissionDecision": decision,
+            "permissionDecisionReason": reason,
+        }
+    })
+}
+
+/// Summarizes the final assistant turn from the session transcript, if readable.
+fn stop_summary(transcript_path: &str) -> Option<String> {
+    if transcript_path.is_empty() {
+        return None;
+    }
+    let path = PathBuf::from(transcript_path);
+    match claude::summarize_transcript(&path) {
+        Ok(summary) => summary.to_message(),
+        Err(err) => {
+            debug_log(&format!("claude transcript {}: {err}", path.display()));
+            None
+        }
+    }
+}
+
+/// One-line description of a tool call: the command, file, or pattern it acts on.
+pub fn tool_summary(input: Option<&Value>) -> String {
+    let Some(input) = input else {
+        return String::new();
+    };
+    [
+        "command",
+        "file_path",
+        "path",
+        "pattern",
+        "url",
+        "description",
+    ]
+    .iter()
+    .find_map(|key| input.get(*key).and_then(|v| v.as_str()))
+    .unwrap_or("")
+    .to_string()
+}
+
+fn tool_failed(response: Option<&Value>) -> bool {
+    let Some(response) = response else {
+        return false;
+    };
+    response.get("is_error").and_then(|v| v.as_bool()) == Some(true)
+        || response.get("success").and_then(|v| v.as_bool()) == Some(false)
+        || response
+            .get("exit_code")
+            .or_else(|| response.get("exitCode"))
+            .and_then(|v| v.as_i64())
+            .is_some_and(|code| code != 0)
+}
+
+pub fn gemini_event(payload: &Value) -> Option<MappedEvent> {
+    let hook = payload
+        .get("hook_event_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Unknown");
+    let str_field = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or("");
+
+    let (title, message, urgency, tag) = match hook {
+        "Notification" => {
+            let ntype = str_field("notification_type");
+            let (urgency, tag) = if ntype == "ToolPermission" {
+                (Urgency::High, "permission")
+            } else {
+                (Urgency::Normal, "notification")
+            };
+            let title = if ntype == "ToolPermission" {
+                "Gemini CLI: permission needed".to_string()
+            } else {
+                "Gemini CLI: notification".to_string()
+            };
+            (title, str_field("message").to_string(), urgency, tag)
+        }
+        "AfterAgent" => (
+            "Gemini CLI: finished".to_string(),
+            collapse_whitespace(str_field("prompt_response")),
+            Urgency::Normal,
+            "stop",
+        ),
+        "SessionEnd" => (
+            "Gemini CLI: session ended".to_string(),
+            str_field("reason").to_string(),
+            Urgency::Low,
+            "session",
+        ),
+        other => (
+            format!("Gemini CLI: {other}"),
+            str_field("message").to_string(),
+            Urgency::Normal,
+            "gemini",
+        ),
+    };
+    let message = if message.trim().is_empty() && hook == "AfterAgent" {
+        "Task completed".to_string()
+    } else {
+        message
+    };
+
+    Some(MappedEvent {
+        title,
+        message,
+        urgency: Some(urgency),
+        tag: Some(tag.to_string()),
+        metadata: metadata(payload, &["session_id", "cwd", "transcript_path"]),
+    })
+}
+
+pub fn aider_event() -> Option<MappedEvent> {
+    let cwd = std::env::current_dir().ok()?;
+    let project = cwd
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| cwd.display().to_string());
+    Some(MappedEvent {
+        title: "Aider: waiting for input".to_string(),
+        message: format!("Ready in {project}"),
+        urgency: Some(Urgency::Normal),
+        tag: Some("idle".to_string()),
+        metadata: [("cwd".to_string(), cwd.display().to_string())].into(),
+    })
+}
+
+pub fn opencode_event(payload: &Value) -> Option<MappedEvent> {
+    let etype = payload.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let properties = payload.get("properties").cloned().unwrap_or(Value::Null);
+    let str_prop = |key: &str| properties.get(key).and_then(|v| v.as_str()).unwrap_or("");
+
+    let (title, message, urgency, tag) = match etype {
+        "session.idle" => (
+            "opencode: finished",
+            "Session is idle".to_string(),
+            Urgency::Normal,
+            "stop",
+        ),
+        "session.error" => {
+            let error = properties.get("error");
+            let message = error
+                .and_then(|e| e.get("data"))
+                .and_then(|d| d.get("message"))
+                .or_else(|| error.and_then(|e| e.get("name")))
+                .and_then(|v| v.as_str())
+                .unwrap_or("Session failed");
+            (
+                "opencode: error",
+                message.to_string(),
+                Urgency::High,
+                "error",
+            )
+        }
+        "permission.updated" | "permission.asked" => {
+            let message = [str_prop("title"), str_prop("pattern"), str_prop("type")]
+                .into_iter()
+                .find(|value| !value.is_empty())
+                .unwrap_or("Permission requested");
+            (
+                "opencode: permission needed",
+                message.to_string(),
+                Urgency::High,
+                "permission",
+            )
+        }
+        _ => return None,
+    };
+
+    let metadata = properties
+        .get("sessionID")
+        .and_then(|v| v.as_str())
+        .map(|id| [("session_id".to_string(), id.to_string())].into())
+        .unwrap_or_default();
+    Some(MappedEvent {
+        title: title.to_string(),
+        message,
+        urgency: Some(urgency),
+        tag: Some(tag.to_string()),
+        metadata,
+    })
+}
+
+/// String fields of a hook payload to carry along as notification metadata.
+pub fn metadata(payload: &Value, keys: &[&str]) -> BTreeMap<String, String> {
+    keys.iter()
+        .filter_map(|key| {
+            payload
+                .get(*key)
+                .and_then(|v| v.as_str())
+                .map(|value| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::builtin_event;
+
+    const CLAUDE_BASH_FAILED: &str = r#"{
+        "session_id": "3f1c9a2e",
+        "transcript_path": "/home/dev/.claude/projects/app/3f1c9a2e.jsonl",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "PostToolUse",
+        "tool_name": "Bash",
+        "tool_input": { "command": "cargo test", "description": "Run the tests" },
+        "tool_response": { "stdout": "", "stderr": "error[E0433]", "exitCode": 101 }
+    }"#;
+
+    const CLAUDE_IDLE: &str = r#"{
+        "session_id": "3f1c9a2e",
+        "transcript_path": "/home/dev/.claude/projects/app/3f1c9a2e.jsonl",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "Notification",
+        "notification_type": "idle_prompt",
+        "message": "Claude is waiting for your input"
+    }"#;
+
+    const CLAUDE_STOP: &str = r#"{
+        "session_id": "3f1c9a2e",
+        "transcript_path": "",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "Stop",
+        "stop_hook_active": false
+    }"#;
+
+    const GEMINI_PERMISSION: &str = r#"{
+        "session_id": "c2a7e1",
+        "transcript_path": "/home/dev/.gemini/tmp/c2a7e1/chats/session.json",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "Notification",
+        "timestamp": "2025-10-02T09:12:44.120Z",
+        "notification_type": "ToolPermission",
+        "message": "Allow execution of: rm -rf build?",
+        "details": { "type": "exec", "command": "rm -rf build" }
+    }"#;
+
+    const GEMINI_AFTER_AGENT: &str = r#"{
+        "session_id": "c2a7e1",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "AfterAgent",
+        "prompt": "tidy the build script",
+        "prompt_response": "Removed the stale\n  targets from build.sh."
+    }"#;
+
+    const OPENCODE_ERROR: &str = r#"{
+        "type": "session.error",
+        "properties": {
+            "sessionID": "ses_8f2",
+            "error": { "name": "ProviderAuthError", "data": { "message": "API key is invalid" } }
+        }
+    }"#;
+
+    const OPENCODE_PERMISSION: &str = r#"{
+        "type": "permission.updated",
+        "properties": {
+            "id": "per_1",
+            "type": "bash",
+            "pattern": "git push",
+            "sessionID": "ses_8f2",
+            "title": "git push origin main"
+        }
+    }"#;
+
+    fn claude(raw: &str) -> MappedEvent {
+        let payload = claude_payload(serde_json::from_str(raw).unwrap());
+        builtin_event("claude", &payload).expect("mapped")
+    }
+
+    #[test]
+    fn claude_payload_adds_tool_and_subtype() {
+        let payload = claude_payload(serde_json::from_str(CLAUDE_BASH_FAILED).unwrap());
+        assert_eq!(
+            payload["ding"],
+            serde_json::json!({ "tool": "cargo test", "subtype": "failed" })
+        );
+
+        let payload = claude_payload(serde_json::from_str(CLAUDE_IDLE).unwrap());
+        assert_eq!(
+            payload["ding"],
+            serde_json::json!({ "subtype": "idle_prompt" })
+        );
+        assert_eq!(claude_payload(Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn claude_events_map_to_urgency_and_tag() {
+        let failed = claude(CLAUDE_BASH_FAILED);
+        assert_eq!(failed.title, "Claude Code: Bash failed");
+        assert_eq!(failed.message, "cargo test");
+        assert!(matches!(failed.urgency, Some(Urgency::High)));
+        assert_eq!(failed.tag.as_deref(), Some("error"));
+        assert_eq!(
+            failed.metadata.keys().collect::<Vec<_>>(),
+            ["cwd", "session_id", "transcript_path"]
+        );
+
+        let passed = claude(&CLAUDE_BASH_FAILED.replace("101", "0"));
+        assert_eq!(passed.title, "Claude Code: Bash finished");
+        assert!(matches!(passed.urgency, Some(Urgency::Low)));
+
+        let idle = claude(CLAUDE_IDLE);
+        assert_eq!(idle.title, "Claude Code: idle_prompt");
+        assert_eq!(idle.message, "Claude is waiting for your input");
+        assert_eq!(idle.tag.as_deref(), Some("idle"));
+
+        let stop = claude(CLAUDE_STOP);
+        assert_eq!(stop.title, "Claude Code: finished");
+        assert_eq!(stop.message, "Task completed");
+        assert_eq!(stop.tag.as_deref(), Some("stop"));
+        // An empty transcript path is not carried along.
+        assert!(!stop.metadata.contains_key("transcript_path"));
+    }
+
+    #[test]
+    fn tool_calls_fail_by_flag_or_exit_code() {
+        let failed = |raw: &str| tool_failed(Some(&serde_json::from_str(raw).unwrap()));
+        assert!(failed(r#"{"is_error": true}"#));
+        assert!(failed(r#"{"success": false}"#));
+        assert!(failed(r#"{"exit_code": 2}"#));
+        assert!(!failed(r#"{"exitCode": 0, "stdout": "ok"}"#));
+        assert!(!tool_failed(None));
+        assert_eq!(
+            tool_summary(Some(&serde_json::json!({ "file_path": "src/lib.rs" }))),
+            "src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn gemini_events_map_to_urgency_and_tag() {
+        let permission = gemini_event(&serde_json::from_str(GEMINI_PERMISSION).unwrap()).unwrap();
+        assert_eq!(permission.title, "Gemini CLI: permission needed");
+        assert_eq!(permission.message, "Allow execution of: rm -rf build?");
+        assert!(matches!(permission.urgency, Some(Urgency::High)));
+        assert_eq!(permission.tag.as_deref(), Some("permission"));
+        assert_eq!(
+            permission.metadata.keys().collect::<Vec<_>>(),
+            ["cwd", "session_id", "transcript_path"]
+        );
+
+        let finished = gemini_event(&serde_json::from_str(GEMINI_AFTER_AGENT).unwrap()).unwrap();
+        assert_eq!(finished.title, "Gemini CLI: finished");
+        assert_eq!(finished.message, "Removed the stale targets from build.sh.");
+        assert_eq!(finished.tag.as_deref(), Some("stop"));
+
+        let silent = serde_json::json!({ "hook_event_name": "AfterAgent", "prompt_response": " " });
+        assert_eq!(gemini_event(&silent).unwrap().message, "Task completed");
+        let ended = serde_json::json!({ "hook_event_name": "SessionEnd", "reason": "exit" });
+        let ended = gemini_event(&ended).unwrap();
+        assert_eq!(
+            (ended.title.as_str(), ended.message.as_str()),
+            ("Gemini CLI: session ended", "exit")
+        );
+        assert!(matches!(ended.urgency, Some(Urgency::Low)));
+    }
+
+    #[test]
+    fn opencode_events_map_to_urgency_and_tag() {
+        let error = opencode_event(&serde_json::from_str(OPENCODE_ERROR).unwrap()).unwrap();
+        assert_eq!(error.title, "opencode: error");
+        assert_eq!(error.message, "API key is invalid");
+        assert!(matches!(error.urgency, Some(Urgency::High)));
+        assert_eq!(error.metadata["session_id"], "ses_8f2");
+
+        let permission =
+            opencode_event(&serde_json::from_str(OPENCODE_PERMISSION).unwrap()).unwrap();
+        assert_eq!(permission.title, "opencode: permission needed");
+        assert_eq!(permission.message, "git push origin main");
+        assert_eq!(permission.tag.as_deref(), Some("permission"));
+
+        let idle =
+            serde_json::json!({ "type": "session.idle", "properties": { "sessionID": "ses_8f2" } });
+        assert_eq!(opencode_event(&idle).unwrap().message, "Session is idle");
+        let unnamed = serde_json::json!({ "type": "session.error", "properties": {} });
+        assert_eq!(opencode_event(&unnamed).unwrap().message, "Session failed");
+        let chatter = serde_json::json!({ "type": "message.part.updated", "properties": {} });
+        assert!(opencode_event(&chatter).is_none());
+    }
+
+    #[test]
+    fn aider_names_the_project_it_waits_in() {
+        let event = aider_event().unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(event.title, "Aider: waiting for input");
+        assert_eq!(
+            event.message,
+            format!("Ready in {}", cwd.file_name().unwrap().to_string_lossy())
+        );
+        assert_eq!(event.metadata["cwd"], cwd.display().to_string());
+    }
+}
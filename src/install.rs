@@ -0,0 +1,887 @@
+use crate::error::NotifallError;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where an installed ding hook points, relative to the running binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookState {
+    /// Points at the binary currently running.
+    Current,
+    /// Points at another ding binary that still exists.
+    Stale,
+    /// Points at a path that no longer exists.
+    Missing,
+}
+
+impl HookState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Current => "ok",
+            Self::Stale => "stale",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HookStatus {
+    pub event: String,
+    pub binary: PathBuf,
+    pub state: HookState,
+}
+
+pub fn hook_command(exe: &Path, target: &str) -> String {
+    format!("{} hook {}", exe.display(), target)
+}
+
+/// Returns the binary path if `command` is a `<path>/ding hook <target>`
+/// command, with or without trailing arguments such as `--ask`.
+pub fn ding_binary(command: &str, target: &str) -> Option<PathBuf> {
+    parse_hook_command(command, target).map(|(binary, _)| binary)
+}
+
+/// Splits a ding hook command into the binary path and the arguments after
+/// `hook <target>`.
+fn parse_hook_command<'a>(command: &'a str, target: &str) -> Option<(PathBuf, &'a str)> {
+    let command = command.trim();
+    let marker = format!(" hook {target}");
+    command.match_indices(&marker).find_map(|(at, _)| {
+        let args = &command[at + marker.len()..];
+        if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let binary = command[..at].trim().trim_matches('"');
+        is_ding_path(binary).then(|| (PathBuf::from(binary), args.trim()))
+    })
+}
+
+fn is_ding_path(binary: &str) -> bool {
+    Path::new(binary)
+        .file_name()
+        .is_some_and(|name| name == "ding")
+}
+
+pub fn hook_state(binary: &Path, exe: &Path) -> HookState {
+    if !binary.exists() {
+        return HookState::Missing;
+    }
+    let same = binary == exe
+        || matches!(
+            (binary.canonicalize(), exe.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        );
+    if same {
+        HookState::Current
+    } else {
+        HookState::Stale
+    }
+}
+
+/// Adds ding's hook to each of `events` in a Claude Code or Gemini CLI
+/// settings document, keeping any other hooks intact. Existing ding hooks
+/// that point at another binary are repointed at `exe`. New matcher groups
+/// get `matcher` when given. Returns a description of each change.
+pub fn merge_settings_hooks(
+    settings: &mut Value,
+    exe: &Path,
+    target: &str,
+    events: &[&str],
+    matcher: Option<&str>,
+) -> Result<Vec<String>, NotifallError> {
+    let command = hook_command(exe, target);
+    let hooks = settings
+        .as_object_mut()
+        .ok_or_else(|| NotifallError::Install("settings must be a JSON object".to_string()))?
+        .entry("hooks")
+        .or_insert_with(|| Value::Object(Default::default()))
+        .as_object_mut()
+        .ok_or_else(|| NotifallError::Install("`hooks` must be a JSON object".to_string()))?;
+
+    let mut changes = Vec::new();
+    for event in events {
+        let groups = hooks
+            .entry(event.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .ok_or_else(|| NotifallError::Install(format!("`hooks.{event}` must be an array")))?;
+
+        let mut found = false;
+        for group in groups.iter_mut() {
+            let Some(entries) = group.get_mut("hooks").and_then(|h| h.as_array_mut()) else {
+                continue;
+            };
+            entries.retain_mut(|entry| {
+                let Some((binary, args)) = entry_hook(entry, target) else {
+                    return true;
+                };
+                if found {
+                    changes.push(format!(
+                        "{event}: removed duplicate hook ({})",
+                        binary.display()
+                    ));
+                    return false;
+                }
+                found = true;
+                if hook_state(&binary, exe) != HookState::Current {
+                    let command = if args.is_empty() {
+                        command.clone()
+                    } else {
+                        format!("{command} {args}")
+                    };
+                    entry["command"] = Value::String(command);
+                    changes.push(format!(
+                        "{event}: updated hook from {} to {}",
+                        binary.display(),
+                        exe.display()
+                    ));
+                }
+                true
+            });
+        }
+        groups.retain(|group| !is_empty_group(group));
+
+        if !found {
+            let mut group = serde_json::json!({
+                "hooks": [{ "type": "command", "command": command }]
+            });
+            if let Some(matcher) = matcher {
+                group["matcher"] = Value::String(matcher.to_string());
+            }
+            groups.push(group);
+            changes.push(format!("{event}: added hook"));
+        }
+    }
+    Ok(changes)
+}
+
+/// Removes every ding hook for `target` from a settings document, dropping
+/// matcher groups and events that end up empty.
+pub fn remove_settings_hooks(settings: &mut Value, target: &str) -> Vec<String> {
+    let mut changes = Vec::new();
+    let Some(hooks) = settings.get_mut("hooks").and_then(|h| h.as_object_mut()) else {
+        return changes;
+    };
+    for (event, groups) in hooks.iter_mut() {
+        let Some(groups) = groups.as_array_mut() else {
+            continue;
+        };
+        for group in groups.iter_mut() {
+            let Some(entries) = group.get_mut("hooks").and_then(|h| h.as_array_mut()) else {
+                continue;
+            };
+            entries.retain(|entry| match entry_binary(entry, target) {
+                Some(binary) => {
+                    changes.push(format!("{event}: removed hook ({})", binary.display()));
+                    false
+                }
+                None => true,
+            });
+        }
+        groups.retain(|group| !is_empty_group(group));
+    }
+    hooks.retain(|_, groups| groups.as_array().is_none_or(|g| !g.is_empty()));
+    if hooks.is_empty()
+        && let Some(settings) = settings.as_object_mut()
+    {
+        settings.remove("hooks");
+    }
+    changes
+}
+
+pub fn settings_hook_status(settings: &Value, exe: &Path, target: &str) -> Vec<HookStatus> {
+    let Some(hooks) = settings.get("hooks").and_then(|h| h.as_object()) else {
+        return Vec::new();
+    };
+    let mut statuses = Vec::new();
+    for (event, groups) in hooks {
+        let entries = groups
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|group| group.get("hooks").and_then(|h| h.as_array()))
+            .flatten();
+        for entry in entries {
+            if let Some(binary) = entry_binary(entry, target) {
+                statuses.push(HookStatus {
+                    event: event.clone(),
+                    state: hook_state(&binary, exe),
+                    binary,
+                });
+            }
+        }
+    }
+    statuses
+}
+
+fn entry_binary(entry: &Value, target: &str) -> Option<PathBuf> {
+    entry_hook(entry, target).map(|(binary, _)| binary)
+}
+
+/// The binary and trailing arguments of a settings entry running ding.
+fn entry_hook(entry: &Value, target: &str) -> Option<(PathBuf, String)> {
+    let command = entry.get("command")?.as_str()?;
+    parse_hook_command(command, target).map(|(binary, args)| (binary, args.to_string()))
+}
+
+fn is_empty_group(group: &Value) -> bool {
+    group
+        .get("hooks")
+        .and_then(|h| h.as_array())
+        .is_some_and(|entries| entries.is_empty())
+}
+
+/// Sets Codex's `notify` to ding unless it already runs another program
+/// (Codex only supports a single notify command), in which case `force` is
+/// required to replace it.
+pub fn merge_codex_notify(
+    doc: &mut toml_edit::DocumentMut,
+    exe: &Path,
+    force: bool,
+) -> Result<Vec<String>, NotifallError> {
+    match codex_notify_binary(doc) {
+        Some(Ok(binary)) if hook_state(&binary, exe) == HookState::Current => {
+            return Ok(Vec::new());
+        }
+        Some(Err(existing)) if !force => {
+            return Err(NotifallError::Install(format!(
+                "codex notify is already set to `{existing}`; re-run with --force to replace it \
+                 (or use `ding watch codex` alongside it)"
+            )));
+        }
+        _ => {}
+    }
+
+    let change = match codex_notify_binary(doc) {
+        Some(Ok(binary)) => format!(
+            "notify: updated from {} to {}",
+            binary.display(),
+            exe.display()
+        ),
+        Some(Err(existing)) => format!("notify: replaced `{existing}`"),
+        None => "notify: added".to_string(),
+    };
+    let mut notify = toml_edit::Array::default();
+    notify.push(exe.display().to_string());
+    notify.push("hook");
+    notify.push("codex");
+    doc["notify"] = toml_edit::value(notify);
+    Ok(vec![change])
+}
+
+pub fn remove_codex_notify(doc: &mut toml_edit::DocumentMut) -> Vec<String> {
+    match codex_notify_binary(doc) {
+        Some(Ok(binary)) => {
+            doc.remove("notify");
+            vec![format!("notify: removed ({})", binary.display())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub fn codex_notify_status(doc: &toml_edit::DocumentMut, exe: &Path) -> Vec<HookStatus> {
+    match codex_notify_binary(doc) {
+        Some(Ok(binary)) => vec![HookStatus {
+            event: "notify".to_string(),
+            state: hook_state(&binary, exe),
+            binary,
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// `Some(Ok(path))` when `notify` runs ding, `Some(Err(command))` when it
+/// runs something else, `None` when it is unset.
+fn codex_notify_binary(doc: &toml_edit::DocumentMut) -> Option<Result<PathBuf, String>> {
+    let notify = doc.get("notify")?;
+    let Some(notify) = notify.as_array() else {
+        return Some(Err(notify.to_string().trim().to_string()));
+    };
+    let argv = notify.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>();
+    match argv.as_slice() {
+        [binary, "hook", "codex"] if is_ding_path(binary) => Some(Ok(PathBuf::from(binary))),
+        _ => Some(Err(argv.join(" "))),
+    }
+}
+
+/// Enables Aider's notifications and points `notifications-command` at ding.
+/// `.aider.conf.yml` is edited line by line so comments and ordering survive.
+pub fn merge_aider_config(
+    contents: &str,
+    exe: &Path,
+    force: bool,
+) -> Result<(String, Vec<String>), NotifallError> {
+    let command = hook_command(exe, "aider");
+    let mut changes = Vec::new();
+    match aider_value(contents, AIDER_COMMAND_KEY) {
+        Some(existing) => match ding_binary(&existing, "aider") {
+            Some(binary) if hook_state(&binary, exe) == HookState::Current => {}
+            Some(binary) => changes.push(format!(
+                "{AIDER_COMMAND_KEY}: updated from {} to {}",
+                binary.display(),
+                exe.display()
+            )),
+            None if !force => {
+                return Err(NotifallError::Install(format!(
+                    "aider {AIDER_COMMAND_KEY} is already set to `{existing}`; \
+                     re-run with --force to replace it"
+                )));
+            }
+            None => changes.push(format!("{AIDER_COMMAND_KEY}: replaced `{existing}`")),
+        },
+        None => changes.push(format!("{AIDER_COMMAND_KEY}: added")),
+    }
+    if aider_value(contents, AIDER_ENABLED_KEY).as_deref() != Some("true") {
+        changes.push(format!("{AIDER_ENABLED_KEY}: set to true"));
+    }
+    if changes.is_empty() {
+        return Ok((contents.to_string(), changes));
+    }
+
+    let command_line = format!("{AIDER_COMMAND_KEY}: {}", serde_json::to_string(&command)?);
+    let enabled_line = format!("{AIDER_ENABLED_KEY}: true");
+    let mut lines = contents
+        .lines()
+        .filter(|line| {
+            !is_aider_key(line, AIDER_COMMAND_KEY) && !is_aider_key(line, AIDER_ENABLED_KEY)
+        })
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    lines.push(enabled_line);
+    lines.push(command_line);
+    Ok((lines.join("\n") + "\n", changes))
+}
+
+/// Removes `notifications-command` when it runs ding. `notifications` is left
+/// as is, so Aider falls back to its terminal bell.
+pub fn remove_aider_config(contents: &str) -> (String, Vec<String>) {
+    let Some(binary) =
+        aider_value(contents, AIDER_COMMAND_KEY).and_then(|command| ding_binary(&command, "aider"))
+    else {
+        return (contents.to_string(), Vec::new());
+    };
+    let mut new_contents = contents
+        .lines()
+        .filter(|line| !is_aider_key(line, AIDER_COMMAND_KEY))
+        .collect::<Vec<_>>()
+        .join("\n");
+    new_contents.push('\n');
+    (
+        new_contents,
+        vec![format!(
+            "{AIDER_COMMAND_KEY}: removed ({})",
+            binary.display()
+        )],
+    )
+}
+
+pub fn aider_config_status(contents: &str, exe: &Path) -> Vec<HookStatus> {
+    aider_value(contents, AIDER_COMMAND_KEY)
+        .and_then(|command| ding_binary(&command, "aider"))
+        .map(|binary| HookStatus {
+            event: AIDER_COMMAND_KEY.to_string(),
+            state: hook_state(&binary, exe),
+            binary,
+        })
+        .into_iter()
+        .collect()
+}
+
+const AIDER_ENABLED_KEY: &str = "notifications";
+const AIDER_COMMAND_KEY: &str = "notifications-command";
+
+fn is_aider_key(line: &str, key: &str) -> bool {
+    line.strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
+fn aider_value(contents: &str, key: &str) -> Option<String> {
+    let line = contents.lines().find(|line| is_aider_key(line, key))?;
+    let value = line[key.len()..].trim_start().strip_prefix(':')?.trim();
+    let value = if value.starts_with('"') {
+        serde_json::from_str::<String>(value).unwrap_or_else(|_| value.to_string())
+    } else {
+        value.trim_matches('\'').to_string()
+    };
+    Some(value)
+}
+
+const OPENCODE_BINARY_PREFIX: &str = "const DING = ";
+
+/// Source of the opencode plugin that forwards session events to ding.
+pub fn opencode_plugin(exe: &Path) -> Result<String, NotifallError> {
+    let binary = serde_json::to_string(&exe.display().to_string())?;
+    Ok(format!(
+        r#"// Installed by `ding install opencode`. Forwards opencode events to `ding hook opencode`.
+{OPENCODE_BINARY_PREFIX}{binary};
+const EVENTS = new Set(["session.idle", "session.error", "permission.updated", "permission.asked"]);
+
+export const DingPlugin = async ({{ $ }}) => ({{
+  event: async ({{ event }}) => {{
+    if (!EVENTS.has(event.type)) return;
+    await $`${{DING}} hook opencode ${{JSON.stringify(event)}}`.quiet().nothrow();
+  }},
+}});
+"#
+    ))
+}
+
+/// The ding binary an installed opencode plugin runs, or `None` if the file
+/// was not written by ding.
+pub fn opencode_plugin_binary(contents: &str) -> Option<PathBuf> {
+    let value = contents
+        .lines()
+        .find_map(|line| line.strip_prefix(OPENCODE_BINARY_PREFIX))?
+        .trim()
+        .trim_end_matches(';');
+    serde_json::from_str::<String>(value)
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// An agent ding can install hooks into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Agent {
+    Claude,
+    Codex,
+    Gemini,
+    Aider,
+    Opencode,
+}
+
+impl Agent {
+    pub const ALL: [Agent; 5] = [
+        Agent::Claude,
+        Agent::Codex,
+        Agent::Gemini,
+        Agent::Aider,
+        Agent::Opencode,
+    ];
+
+    /// The name used on the command line and as the `ding hook` target.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Claude => "claude",
+            Self::Codex => "codex",
+            Self::Gemini => "gemini",
+            Self::Aider => "aider",
+            Self::Opencode => "opencode",
+        }
+    }
+}
+
+/// Whether hooks go into the user's config or the current project's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    User,
+    Project,
+}
+
+/// What installing or uninstalling would do to an agent's config.
+#[derive(Debug, Clone)]
+pub enum Plan {
+    /// Nothing to change; says why, e.g. that the hooks are already there.
+    Unchanged(String),
+    Change(IntegrationChange),
+}
+
+/// A pending edit of one integration file, to show as a diff or apply.
+#[derive(Debug, Clone)]
+pub struct IntegrationChange {
+    pub path: PathBuf,
+    pub old: String,
+    /// The new contents, or `None` to delete the file.
+    pub new: Option<String>,
+    /// One line per hook added, updated, or removed.
+    pub changes: Vec<String>,
+    /// What applying does, followed by the path, e.g. "Installed Claude hooks in".
+    pub done: String,
+}
+
+impl IntegrationChange {
+    /// Writes or deletes the file, keeping a timestamped backup of the old one.
+    pub fn apply(&self) -> Result<(), NotifallError> {
+        if self.path.exists() {
+            backup_file(&self.path)?;
+        } else if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match &self.new {
+            Some(contents) => fs::write(&self.path, contents)?,
+            None => fs::remove_file(&self.path)?,
+        }
+        Ok(())
+    }
+}
+
+/// Plans installing hooks that run `exe` for `agent`. `events` picks the
+/// Claude hooks (default `Notification` and `Stop`); `force` replaces a
+/// notify command or plugin ding didn't write.
+pub fn plan_install(
+    agent: Agent,
+    scope: Scope,
+    exe: &Path,
+    events: &[&str],
+    force: bool,
+) -> Result<Plan, NotifallError> {
+    let path = config_path(agent, scope)?;
+    match agent {
+        Agent::Claude => {
+            let events = if events.is_empty() {
+                &["Notification", "Stop"][..]
+            } else {
+                events
+            };
+            plan_settings_hooks("Claude", path, exe, "claude", events, Some(""))
+        }
+        Agent::Gemini => plan_settings_hooks(
+            "Gemini CLI",
+            path,
+            exe,
+            "gemini",
+            &["Notification", "AfterAgent"],
+            None,
+        ),
+        Agent::Codex => {
+            let config = fs::read_to_string(&path).unwrap_or_default();
+            let mut doc = toml_edit::DocumentMut::from_str(&config)?;
+            let changes = merge_codex_notify(&mut doc, exe, force)?;
+            if changes.is_empty() {
+                return Ok(Plan::Unchanged(format!(
+                    "Codex notify already installed in {}",
+                    path.display()
+                )));
+            }
+            Ok(change(
+                path,
+                config,
+                doc.to_string(),
+                changes,
+                "Installed Codex notify in",
+            ))
+        }
+        Agent::Aider => {
+            let config = fs::read_to_string(&path).unwrap_or_default();
+            let (new_contents, changes) = merge_aider_config(&config, exe, force)?;
+            if changes.is_empty() {
+                return Ok(Plan::Unchanged(format!(
+                    "Aider notifications already installed in {}",
+                    path.display()
+                )));
+            }
+            Ok(change(
+                path,
+                config,
+                new_contents,
+                changes,
+                "Installed Aider notifications in",
+            ))
+        }
+        Agent::Opencode => {
+            let existing = fs::read_to_string(&path).unwrap_or_default();
+            let plugin = opencode_plugin(exe)?;
+            if existing == plugin {
+                return Ok(Plan::Unchanged(format!(
+                    "opencode plugin already installed at {}",
+                    path.display()
+                )));
+            }
+            let summary = match opencode_plugin_binary(&existing) {
+                Some(binary) => format!(
+                    "plugin: updated from {} to {}",
+                    binary.display(),
+                    exe.display()
+                ),
+                None if existing.is_empty() => "plugin: added".to_string(),
+                None if !force => {
+                    return Err(NotifallError::Install(format!(
+                        "{} was not written by ding; re-run with --force to replace it",
+                        path.display()
+                    )));
+                }
+                None => "plugin: replaced".to_string(),
+            };
+            Ok(change(
+                path,
+                existing,
+                plugin,
+                vec![summary],
+                "Installed opencode plugin at",
+            ))
+        }
+    }
+}
+
+/// Plans removing ding's hooks for `agent`, leaving everything else in place.
+pub fn plan_uninstall(agent: Agent, scope: Scope) -> Result<Plan, NotifallError> {
+    let path = config_path(agent, scope)?;
+    match agent {
+        Agent::Claude => unplan_settings_hooks("Claude", path, "claude"),
+        Agent::Gemini => unplan_settings_hooks("Gemini CLI", path, "gemini"),
+        Agent::Codex => {
+            let Ok(config) = fs::read_to_string(&path) else {
+                return Ok(Plan::Unchanged(format!(
+                    "No Codex config at {}",
+                    path.display()
+                )));
+            };
+            let mut doc = toml_edit::DocumentMut::from_str(&config)?;
+            let changes = remove_codex_notify(&mut doc);
+            if changes.is_empty() {
+                return Ok(Plan::Unchanged(format!(
+                    "No ding notify command in {}",
+                    path.display()
+                )));
+            }
+            Ok(change(
+                path,
+                config,
+                doc.to_string(),
+                changes,
+                "Removed Codex notify from",
+            ))
+        }
+        Agent::Aider => {
+            let Ok(config) = fs::read_to_string(&path) else {
+                return Ok(Plan::Unchanged(format!(
+                    "No Aider config at {}",
+                    path.display()
+                )));
+            };
+            let (new_contents, changes) = remove_aider_config(&config);
+            if changes.is_empty() {
+                return Ok(Plan::Unchanged(format!(
+                    "No ding notifications command in {}",
+                    path.display()
+                )));
+            }
+            Ok(change(
+                path,
+                config,
+                new_contents,
+                changes,
+                "Removed Aider notifications from",
+            ))
+        }
+        Agent::Opencode => {
+            let existing = fs::read_to_string(&path).unwrap_or_default();
+            if opencode_plugin_binary(&existing).is_none() {
+                return Ok(Plan::Unchanged(format!(
+                    "No ding plugin at {}",
+                    path.display()
+                )));
+            }
+            Ok(Plan::Change(IntegrationChange {
+                path,
+                old: existing,
+                new: None,
+                changes: vec!["plugin: removed".to_string()],
+                done: "Removed opencode plugin".to_string(),
+            }))
+        }
+    }
+}
+
+fn change(path: PathBuf, old: String, new: String, changes: Vec<String>, done: &str) -> Plan {
+    Plan::Change(IntegrationChange {
+        path,
+        old,
+        new: Some(new),
+        changes,
+        done: done.to_string(),
+    })
+}
+
+/// Claude Code and Gemini CLI share the same `hooks` layout in settings.json.
+fn plan_settings_hooks(
+    agent: &str,
+    path: PathBuf,
+    exe: &Path,
+    target: &str,
+    events: &[&str],
+    matcher: Option<&str>,
+) -> Result<Plan, NotifallError> {
+    let settings = fs::read_to_string(&path).unwrap_or_else(|_| "{}".to_string());
+    let mut json: Value = serde_json::from_str(&settings)?;
+    let changes = merge_settings_hooks(&mut json, exe, target, events, matcher)?;
+    if changes.is_empty() {
+        return Ok(Plan::Unchanged(format!(
+            "{agent} hooks already installed in {}",
+            path.display()
+        )));
+    }
+    let new_contents = serde_json::to_string_pretty(&json)?;
+    Ok(change(
+        path,
+        settings,
+        new_contents,
+        changes,
+        &format!("Installed {agent} hooks in"),
+    ))
+}
+
+fn unplan_settings_hooks(agent: &str, path: PathBuf, target: &str) -> Result<Plan, NotifallError> {
+    let Ok(settings) = fs::read_to_string(&path) else {
+        return Ok(Plan::Unchanged(format!(
+            "No {agent} settings at {}",
+            path.display()
+        )));
+    };
+    let mut json: Value = serde_json::from_str(&settings)?;
+    let changes = remove_settings_hooks(&mut json, target);
+    if changes.is_empty() {
+        return Ok(Plan::Unchanged(format!(
+            "No ding hooks in {}",
+            path.display()
+        )));
+    }
+    let new_contents = serde_json::to_string_pretty(&json)?;
+    Ok(change(
+        path,
+        settings,
+        new_contents,
+        changes,
+        &format!("Removed {agent} hooks from"),
+    ))
+}
+
+/// ding's hooks in `agent`'s config at `path`, or `None` if the file doesn't
+/// exist.
+pub fn statuses(
+    agent: Agent,
+    path: &Path,
+    exe: &Path,
+) -> Result<Option<Vec<HookStatus>>, NotifallError> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    Ok(Some(match agent {
+        Agent::Claude | Agent::Gemini => {
+            settings_hook_status(&serde_json::from_str(&contents)?, exe, agent.name())
+        }
+        Agent::Codex => codex_notify_status(&toml_edit::DocumentMut::from_str(&contents)?, exe),
+        Agent::Aider => aider_config_status(&contents, exe),
+        Agent::Opencode => opencode_plugin_binary(&contents)
+            .map(|binary| HookStatus {
+                event: "plugin".to_string(),
+                state: hook_state(&binary, exe),
+                binary,
+            })
+            .into_iter()
+            .collect(),
+    }))
+}
+
+/// The file each integration is installed into, per scope.
+pub fn config_path(agent: Agent, scope: Scope) -> Result<PathBuf, NotifallError> {
+    let base = match scope {
+        Scope::User => home_dir()?,
+        Scope::Project => std::env::current_dir()?,
+    };
+    Ok(match (agent, scope) {
+        (Agent::Claude, _) => base.join(".claude").join("settings.json"),
+        (Agent::Gemini, _) => base.join(".gemini").join("settings.json"),
+        (Agent::Aider, _) => base.join(".aider.conf.yml"),
+        (Agent::Codex, Scope::User) => base.join(".codex").join("config.toml"),
+        (Agent::Codex, Scope::Project) => {
+            return Err(NotifallError::Install(
+                "codex reads notify from ~/.codex/config.toml only; --scope project is not supported"
+                    .to_string(),
+            ));
+        }
+        (Agent::Opencode, Scope::User) => {
+            let config_home = std::env::var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| base.join(".config"));
+            config_home.join("opencode").join("plugin").join("ding.js")
+        }
+        (Agent::Opencode, Scope::Project) => base.join(".opencode").join("plugin").join("ding.js"),
+    })
+}
+
+fn home_dir() -> Result<PathBuf, NotifallError> {
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .map_err(|_| NotifallError::MissingHome)
+}
+
+fn backup_file(path: &Path) -> Result<(), NotifallError> {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let backup = path.with_extension(format!("bak-{}", ts));
+    fs::copy(path, &backup)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ding_binary_accepts_trailing_args() {
+        assert_eq!(
+            ding_binary("/usr/local/bin/ding hook claude --ask", "claude"),
+            Some(PathBuf::from("/usr/local/bin/ding"))
+        );
+        assert_eq!(
+            ding_binary("\"/Users/me/My Tools/ding\" hook claude", "claude"),
+            Some(PathBuf::from("/Users/me/My Tools/ding"))
+        );
+        assert_eq!(
+            ding_binary("/usr/local/bin/ding hook claudex", "claude"),
+            None
+        );
+        assert_eq!(
+            ding_binary("/usr/local/bin/other hook claude", "claude"),
+            None
+        );
+    }
+
+    fn settings_with(command: &str) -> Value {
+        serde_json::json!({
+            "hooks": {
+                "PreToolUse": [{
+                    "matcher": "Bash",
+                    "hooks": [{ "type": "command", "command": command }]
+                }],
+                "Stop": [{
+                    "hooks": [{ "type": "command", "command": "afplay done.aiff" }]
+                }]
+            }
+        })
+    }
+
+    #[test]
+    fn status_and_uninstall_see_hooks_with_flags() {
+        let exe = Path::new("/nonexistent/ding");
+        let mut settings = settings_with("/nonexistent/ding hook claude --ask");
+
+        let statuses = settings_hook_status(&settings, exe, "claude");
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].event, "PreToolUse");
+
+        let changes = remove_settings_hooks(&mut settings, "claude");
+        assert_eq!(changes.len(), 1);
+        assert!(settings["hooks"].get("PreToolUse").is_none());
+        assert!(settings["hooks"].get("Stop").is_some());
+    }
+
+    #[test]
+    fn merge_keeps_flags_when_repointing() {
+        let exe = Path::new("/opt/new/ding");
+        let mut settings = settings_with("/opt/old/ding hook claude --ask");
+
+        let changes =
+            merge_settings_hooks(&mut settings, exe, "claude", &["PreToolUse"], None).unwrap();
+        assert_eq!(changes.len(), 1);
+        let entries = settings["hooks"]["PreToolUse"][0]["hooks"]
+            .as_array()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["command"], "/opt/new/ding hook claude --ask");
+    }
+}
@@ -0,0 +1,165 @@
+//! Notifications from scripts, coding agents, and remote machines to macOS
+//! and Telegram.
+//!
+//! The `ding` binary is a thin layer over this crate. For one-off sends,
+//! [`Ding`] loads the user's config and delivers through the same providers,
+//! forwarding, and history as `ding send`:
+//!
+//! ```no_run
+//! use ding::{Ding, Notification};
+//!
+//! # fn main() -> Result<(), ding::NotifallError> {
+//! Ding::from_config()?
+//!     .provider("telegram")
+//!     .notify(Notification::new("Deploy", "Finished in 42s"))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The pieces are public too: [`provider`] for the providers themselves and
+//! the registry that names them, [`delivery`] for the send pipeline, and
+//! [`remote`] for the listener protocol, client, and server.
+
+pub mod attachment;
+pub mod bot;
+pub mod claude;
+pub mod click;
+pub mod codex;
+pub mod config;
+pub mod config_edit;
+pub mod context;
+pub mod debug;
+pub mod delivery;
+pub mod dnd;
+pub mod doctor;
+pub mod duration;
+pub mod error;
+pub mod history;
+pub mod hook;
+pub mod install;
+pub mod mapping;
+pub mod notification;
+pub mod payload;
+pub mod progress;
+pub mod provider;
+pub mod remote;
+pub mod resolve;
+pub mod run;
+pub mod schema;
+pub mod state;
+pub mod watch;
+
+pub use config::Config;
+pub use error::NotifallError;
+pub use notification::{Action, Notification, Urgency};
+pub use provider::{DeliveryOutcome, Provider, ProviderError};
+
+use crate::click::Interaction;
+use crate::context::detect_context;
+use crate::delivery::{SendContext, SendOverrides};
+use crate::dnd::DndStore;
+use crate::history::{HistoryEntry, HistoryKind, TargetResult};
+use crate::provider::registry::{ProviderPlugin, ProviderRegistry};
+use crate::remote::RemoteContext;
+use std::path::Path;
+
+/// Sends notifications the way `ding send` does: provider resolution,
+/// truncation, forwarding, do-not-disturb, and history all follow the config.
+#[derive(Debug, Clone, Default)]
+pub struct Ding {
+    config: Option<Config>,
+    provider: Option<String>,
+    overrides: SendOverrides,
+    interaction: Interaction,
+    registry: ProviderRegistry,
+}
+
+/// What happened to a notification sent with [`Ding::notify`].
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// The provider the notification went to, e.g. "forward".
+    pub provider: String,
+    pub outcome: Option<DeliveryOutcome>,
+    /// One result per provider tried; forwarding and remote fallback can try
+    /// several.
+    pub targets: Vec<TargetResult>,
+}
+
+impl Ding {
+    /// Uses the config at the default path, or none if it doesn't exist.
+    pub fn from_config() -> Result<Self, NotifallError> {
+        Ok(Self::new(Config::load(None)?))
+    }
+
+    pub fn from_config_path(path: &Path) -> Result<Self, NotifallError> {
+        Ok(Self::new(Config::load(Some(path))?))
+    }
+
+    pub fn new(config: Option<Config>) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Sends through `name` instead of the configured default.
+    pub fn provider(mut self, name: &str) -> Self {
+        self.provider = Some(name.to_lowercase());
+        self
+    }
+
+    /// Overrides Telegram and remote settings for every send.
+    pub fn overrides(mut self, overrides: SendOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Waits for clicks and runs click commands the way `--on-click`,
+    /// `--wait-for-click`, and `--background` do for `ding send`.
+    pub fn interaction(mut self, interaction: Interaction) -> Self {
+        self.interaction = interaction;
+        self
+    }
+
+    /// Adds a provider next to the built-in ones, or replaces the built-in
+    /// one with the same name.
+    pub fn register(mut self, provider: impl ProviderPlugin + 'static) -> Self {
+        self.registry.register(provider);
+        self
+    }
+
+    pub fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+
+    /// Delivers `notification` and records it in history. Returns
+    /// [`NotifallError::Suppressed`] when do-not-disturb holds it back.
+    pub fn notify(&self, notification: Notification) -> Result<Delivery, NotifallError> {
+        let config = self.config.as_ref();
+        let provider = resolve::resolve_provider(self.provider.as_deref(), config)?;
+        let origin = RemoteContext::from_local(detect_context());
+        let mut entry = HistoryEntry::new(HistoryKind::Sent, &provider, notification.clone());
+        entry.context = Some(origin.clone());
+
+        if DndStore::default_location().suppresses(&notification) {
+            history::record_suppressed(config, entry);
+            return Err(NotifallError::Suppressed);
+        }
+
+        let mut targets = Vec::new();
+        let cx = SendContext {
+            config,
+            overrides: &self.overrides,
+            origin: &origin,
+            interaction: &self.interaction,
+            registry: &self.registry,
+        };
+        let result = delivery::deliver(&cx, &provider, notification, &mut targets);
+        history::record_history(config, entry, targets.clone(), &result);
+        result.map(|outcome| Delivery {
+            provider,
+            outcome,
+            targets,
+        })
+    }
+}
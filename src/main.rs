@@ -1,34 +1,65 @@
 mod cli;
-mod config;
-mod context;
-mod error;
-mod notification;
-mod payload;
-mod provider;
-mod remote;
 
 use crate::cli::{
-    Cli, Commands, ConfigCmd, ConfigSetArgs, FocusArgs, ForwardCmd, ForwardOnArgs, ForwardTarget,
-    HookArgs, InstallArgs, ListenArgs, ProvidersCmd, RemoteCmd, RemotePingArgs, SendArgs,
-    SourcesCmd, TelegramChatIdArgs, TelegramCmd, UrgencyArg,
+    AskArgs, ClaudeEventArg, Cli, CodexEventArg, Commands, ConfigCmd, ConfigKeyArgs, ConfigSetArgs,
+    DndCmd, DoctorArgs, FocusArgs, ForwardCmd, ForwardTarget, HistoryArgs, HistoryCmd,
+    HistoryShowArgs, HookArgs, HookTarget, InstallArgs, InstallScope, InstallTarget, ListenArgs,
+    ProgressCmd, ProvidersCmd, ProvidersTestArgs, RemoteCmd, RemotePingArgs, ReplayArgs, RunArgs,
+    SendArgs, SourcesCmd, TelegramChatIdArgs, TelegramCmd, UninstallArgs, UrgencyArg, WatchCmd,
+    WatchCodexArgs, WatchFileArgs, WatchOptions, WatchPidArgs, WatchProcArgs,
 };
-use crate::config::{Config, MacosConfig, SourceConfig, TelegramConfig};
-use crate::context::{detect_context, Context};
-use crate::error::NotifallError;
-use crate::notification::{Notification, Urgency};
-use crate::payload::WaitPayload;
-use crate::provider::{
-    macos::MacosProvider, telegram::TelegramProvider, DeliveryOutcome, Provider, ProviderError,
-    SendOptions,
-};
-use crate::remote::{RemoteContext, RemoteEnvelope};
 use clap::Parser;
+use ding::attachment::{self, Attachment};
+use ding::bot::Bot;
+use ding::click::{Interaction, default_focus_command, handle_click, has_click_actions};
+use ding::codex::{self, CodexEventKind};
+use ding::config::{Config, default_config_path};
+use ding::config_edit::{self, ForwardChange, ListEdit};
+use ding::context::{activate_terminal, detect_context};
+use ding::debug::{debug_enabled, debug_log};
+use ding::delivery::{self, SendContext, SendOverrides};
+use ding::dnd::DndStore;
+use ding::doctor::{self, Check, Status};
+use ding::duration::{format_duration, parse_duration};
+use ding::error::NotifallError;
+use ding::history::{
+    self, HistoryEntry, HistoryKind, HistoryStore, TargetResult, record_history, record_suppressed,
+};
+use ding::mapping::{self, MappedEvent};
+use ding::notification::{Action, Notification, Urgency};
+use ding::payload::WaitPayload;
+use ding::progress::{
+    self, ProgressState, ProgressStep, ProgressStore, ProgressTarget, ProgressTask,
+};
+use ding::provider::{
+    DeliveryOutcome, Provider, ProviderError, SendOptions,
+    macos::MacosProvider,
+    registry::{LOCAL_PROVIDER, ProviderPlugin, ProviderRegistry},
+    telegram::TelegramProvider,
+};
+use ding::remote::{
+    Listener, ListenerRequest, RemoteClient, RemoteContext, RemoteEnvelope, RemoteOverrides,
+};
+use ding::resolve::{
+    TelegramOverrides, allow_image_icons, expand_provider, resolve_icon, resolve_provider,
+    resolve_source_config, resolve_telegram_config, resolve_title, telegram_provider,
+    title_from_source, truncate_message, truncate_to,
+};
+use ding::schema;
+use ding::state::{ATTACHMENT_MAX_AGE, attachments_dir, default_state_dir, prune_dir};
+use ding::{hook, install, run, watch};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::time::{SystemTime, UNIX_EPOCH};
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Questions the listener shows at once; more are turned away with 429.
+const MAX_PENDING_ASKS: usize = 8;
+/// When a turned-away question may be asked again.
+const ASK_RETRY_AFTER: Duration = Duration::from_secs(30);
 
 fn main() {
     if let Err(err) = run() {
@@ -37,32 +68,6 @@ fn main() {
     }
 }
 
-fn debug_enabled() -> bool {
-    std::env::var("DING_DEBUG")
-        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
-        .unwrap_or(false)
-}
-
-fn debug_log(message: &str) {
-    if !debug_enabled() {
-        return;
-    }
-    if let Ok(path) = std::env::var("DING_DEBUG_FILE") {
-        if !path.trim().is_empty() {
-            if let Ok(mut file) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&path)
-            {
-                use std::io::Write;
-                let _ = writeln!(file, "[ding debug] {message}");
-                return;
-            }
-        }
-    }
-    eprintln!("[ding debug] {message}");
-}
-
 fn run() -> Result<(), NotifallError> {
     let cli = Cli::parse();
 
@@ -70,39 +75,61 @@ fn run() -> Result<(), NotifallError> {
 
     match cli.command {
         Commands::Send(args) => handle_send(config_path.as_ref(), args),
-        Commands::Config { command: ConfigCmd::Init(args) } => {
-            handle_config_init(config_path.as_ref(), args)
-        }
-        Commands::Config { command: ConfigCmd::Set(args) } => {
-            handle_config_set(config_path.as_ref(), args)
-        }
-        Commands::Config { command: ConfigCmd::Path } => {
-            handle_config_path(config_path.as_ref())
-        }
-        Commands::Config { command: ConfigCmd::List } => {
-            handle_config_list(config_path.as_ref())
-        }
+        Commands::Ask(args) => handle_ask(config_path.as_ref(), args),
+        Commands::Config {
+            command: ConfigCmd::Init(args),
+        } => handle_config_init(config_path.as_ref(), args),
+        Commands::Config {
+            command: ConfigCmd::Set(args),
+        } => handle_config_set(config_path.as_ref(), args),
+        Commands::Config {
+            command: ConfigCmd::Get(args),
+        } => handle_config_get(config_path.as_ref(), args),
+        Commands::Config {
+            command: ConfigCmd::Unset(args),
+        } => handle_config_unset(config_path.as_ref(), args),
+        Commands::Config {
+            command: ConfigCmd::Edit,
+        } => handle_config_edit(config_path.as_ref()),
+        Commands::Config {
+            command: ConfigCmd::Path,
+        } => handle_config_path(config_path.as_ref()),
+        Commands::Config {
+            command: ConfigCmd::List,
+        } => handle_config_list(config_path.as_ref()),
+        Commands::Config {
+            command: ConfigCmd::Validate,
+        } => handle_config_validate(config_path.as_ref()),
         Commands::Providers {
             command: ProvidersCmd::List,
-        } => handle_providers_list(),
+        } => handle_providers_list(config_path.as_ref()),
+        Commands::Providers {
+            command: ProvidersCmd::Test(args),
+        } => handle_providers_test(config_path.as_ref(), args),
+        Commands::Doctor(args) => handle_doctor(config_path.as_ref(), args),
         Commands::Sources {
             command: SourcesCmd::List,
         } => handle_sources_list(config_path.as_ref()),
         Commands::Install(args) => handle_install(args),
-        Commands::Hook(args) => handle_hook(args),
+        Commands::Uninstall(args) => handle_uninstall(args),
+        Commands::Hook(args) => handle_hook(config_path.as_ref(), args),
         Commands::Focus(args) => handle_focus(args),
         Commands::WaitMacos(args) => handle_wait_macos(args),
         Commands::Listen(args) => handle_listen(config_path.as_ref(), args),
         Commands::Remote { command } => handle_remote(command, config_path.as_ref()),
         Commands::Forward { command } => handle_forward(command, config_path.as_ref()),
+        Commands::Dnd { command } => handle_dnd(command),
+        Commands::Progress { command } => handle_progress(config_path.as_ref(), command),
         Commands::Telegram { command } => handle_telegram(command, config_path.as_ref()),
+        Commands::History(args) => handle_history(config_path.as_ref(), args),
+        Commands::Replay(args) => handle_replay(config_path.as_ref(), args),
+        Commands::Run(args) => handle_run(config_path.as_ref(), args),
+        Commands::Watch { command } => handle_watch(command, config_path.as_ref()),
     }
 }
 
 fn handle_send(config_path: Option<&PathBuf>, args: SendArgs) -> Result<(), NotifallError> {
-    let resolved_path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
+    let resolved_path = config_path.cloned().unwrap_or_else(default_config_path);
     debug_log(&format!("config_path={}", resolved_path.display()));
     let config = load_config(Some(&resolved_path))?;
     let provider_name = resolve_provider(args.provider.as_deref(), config.as_ref())?;
@@ -114,7 +141,9 @@ fn handle_send(config_path: Option<&PathBuf>, args: SendArgs) -> Result<(), Noti
     let source_config = resolve_source_config(config.as_ref(), source.as_deref());
     let context = detect_context();
 
-    if args.background && args.on_click.is_none() && provider_name == "macos" {
+    let actions = parse_actions(&args.actions)?;
+    if args.background && args.on_click.is_none() && actions.is_empty() && provider_name == "macos"
+    {
         return Err(NotifallError::BackgroundRequiresOnClick);
     }
 
@@ -143,114 +172,229 @@ fn handle_send(config_path: Option<&PathBuf>, args: SendArgs) -> Result<(), Noti
         tag: args.tag.clone(),
         sender: None,
         dedupe_key: None,
-        metadata: None,
-        actions: Vec::new(),
+        metadata: parse_metadata(&args.metadata)?,
+        actions,
+        attachments: load_attachments(&args)?,
     };
-    let mut remote_notification = notification.clone();
-    remote_notification.icon = None;
-
-    match provider_name.as_str() {
-        "macos" => {
-            let macos_config = resolve_macos_config(config.as_ref(), source_config, source.as_deref());
-            deliver_macos(
-                notification,
-                macos_config,
-                args.on_click.clone(),
-                args.background,
-                args.wait_for_click,
-                args.json,
-                context,
-            )?;
-        }
-        "forward" => {
-            handle_forward_send(
-                config.as_ref(),
-                &args,
-                notification,
-                remote_notification,
-                context,
-                source_config,
-                source.as_deref(),
-            )?;
-        }
-        "telegram" => {
-            let telegram_config = resolve_telegram_config(config.as_ref(), &args)?;
-            debug_log(&format!(
-                "telegram chat_id={} parse_mode={} silent={}",
-                telegram_config
-                    .chat_id
-                    .as_deref()
-                    .unwrap_or("(unset)"),
-                telegram_config
-                    .parse_mode
-                    .as_deref()
-                    .unwrap_or("(unset)"),
-                telegram_config.silent.unwrap_or(false)
-            ));
-            let provider = TelegramProvider::new(telegram_config)?;
-            let report = provider.send(&notification, SendOptions { wait_for_click: false })?;
-            if args.json {
-                print_send_output("telegram", report.outcome, false, None)?;
+
+    if DndStore::default_location().suppresses(&notification) {
+        let mut entry = HistoryEntry::new(HistoryKind::Sent, &provider_name, notification);
+        entry.context = Some(RemoteContext::from_local(context));
+        record_suppressed(config.as_ref(), entry);
+        return Ok(());
+    }
+
+    dispatch_send(
+        config.as_ref(),
+        &provider_name,
+        &args,
+        notification,
+        RemoteContext::from_local(context),
+        None,
+    )
+    .map(|_| ())
+}
+
+/// Delivers an already-built notification through `provider_name` and records
+/// the attempt in history. `origin` describes where the notification was first
+/// raised, so replays keep the original host and tmux pane.
+fn dispatch_send(
+    config: Option<&Config>,
+    provider_name: &str,
+    args: &SendArgs,
+    notification: Notification,
+    origin: RemoteContext,
+    replay_of: Option<&str>,
+) -> Result<Option<DeliveryOutcome>, NotifallError> {
+    let mut history_entry =
+        HistoryEntry::new(HistoryKind::Sent, provider_name, notification.clone());
+    history_entry.replay_of = replay_of.map(|id| id.to_string());
+    let mut targets = Vec::new();
+
+    let registry = ProviderRegistry::builtin();
+    let cx = SendContext {
+        config,
+        overrides: &send_overrides(args),
+        origin: &origin,
+        interaction: &interaction(args),
+        registry: &registry,
+    };
+    let result = delivery::deliver(&cx, provider_name, notification, &mut targets);
+
+    if args.json {
+        match (&result, provider_name) {
+            (_, "forward") => {
+                let output = serde_json::json!({
+                    "provider": "forward",
+                    "results": targets,
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            (Err(err), _) => print_send_error(provider_name, err)?,
+            // The provider that delivered, which is the local one when remote
+            // fell back to it.
+            (Ok(outcome), _) => {
+                if let Some(target) = targets.iter().rev().find(|t| t.ok) {
+                    let background = args.background && target.provider == LOCAL_PROVIDER;
+                    let payload = target.id.clone().filter(|_| background);
+                    print_send_output(&target.provider, outcome.clone(), background, payload)?;
+                }
             }
         }
-        "remote" => {
-            handle_remote_send(
-                config.as_ref(),
-                &args,
-                notification,
-                remote_notification,
-                context,
-                source_config,
-                source.as_deref(),
-            )?;
+    } else if provider_name == "forward" && result.is_ok() {
+        let failed = targets.iter().filter(|r| !r.ok).count();
+        if failed > 0 {
+            eprintln!("forwarding: {} target(s) failed", failed);
         }
-        other => return Err(NotifallError::ProviderUnsupported(other.to_string())),
     }
+    history_entry.context = Some(origin);
+    record_history(config, history_entry, targets, &result);
 
-    Ok(())
+    result
 }
 
-fn deliver_macos(
-    notification: Notification,
-    macos_config: Option<MacosConfig>,
-    on_click: Option<String>,
-    background: bool,
-    wait_for_click: bool,
-    json: bool,
-    context: Option<Context>,
-) -> Result<(), NotifallError> {
-    if background {
-        let payload = WaitPayload {
-            notification,
-            macos: macos_config,
-            on_click,
-            context,
-        };
-        let payload_path = spawn_background_wait(payload)?;
-        if json {
-            print_send_output(
-                "macos",
-                None,
-                true,
-                Some(payload_path.to_string_lossy().to_string()),
-            )?;
-        }
-        return Ok(());
+fn interaction(args: &SendArgs) -> Interaction {
+    Interaction {
+        on_click: args.on_click.clone(),
+        wait_for_click: args.wait_for_click,
+        background: args.background,
     }
+}
 
-    let wait_for_click = wait_for_click || on_click.is_some();
-    let provider = MacosProvider::new(macos_config)?;
-    let report = provider.send(&notification, SendOptions { wait_for_click })?;
-    if wait_for_click {
-        handle_click(
-            report.outcome.clone(),
-            on_click.as_deref(),
-            &notification,
-            context.as_ref(),
-        )?;
+fn send_overrides(args: &SendArgs) -> SendOverrides {
+    SendOverrides {
+        telegram: TelegramOverrides {
+            token: args.telegram_token.clone(),
+            chat: args.telegram_chat_id.clone(),
+            parse_mode: args.telegram_parse_mode.clone(),
+            silent: args.telegram_silent,
+        },
+        remote: RemoteOverrides {
+            host: args.remote_host.clone(),
+            port: args.remote_port,
+            token: args.remote_token.clone(),
+            timeout_ms: args.remote_timeout_ms,
+            retries: args.remote_retries,
+        },
+        no_fallback: args.no_fallback,
+    }
+}
+
+fn parse_metadata(
+    entries: &[String],
+) -> Result<Option<std::collections::BTreeMap<String, String>>, NotifallError> {
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let mut metadata = std::collections::BTreeMap::new();
+    for entry in entries {
+        let (key, value) = entry
+            .split_once('=')
+            .filter(|(key, _)| !key.trim().is_empty())
+            .ok_or_else(|| NotifallError::InvalidMetadata(entry.clone()))?;
+        metadata.insert(key.trim().to_string(), value.to_string());
+    }
+    Ok(Some(metadata))
+}
+
+fn load_attachments(args: &SendArgs) -> Result<Vec<Attachment>, NotifallError> {
+    let mut attachments = args
+        .attachments
+        .iter()
+        .map(|path| {
+            Attachment::from_path(path)
+                .map_err(|err| NotifallError::Attachment(format!("{}: {err}", path.display())))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.attach_stdin {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)?;
+        prune_dir(&attachments_dir(), ATTACHMENT_MAX_AGE);
+        attachments.push(Attachment::from_bytes(
+            &attachments_dir(),
+            &args.attach_name,
+            &bytes,
+        )?);
+    }
+    if let Some(large) = attachments
+        .iter()
+        .find(|a| a.size > attachment::MAX_ATTACHMENT_BYTES)
+    {
+        return Err(NotifallError::Attachment(format!(
+            "{} is {} (limit {})",
+            large.name,
+            attachment::format_size(large.size),
+            attachment::format_size(attachment::MAX_ATTACHMENT_BYTES)
+        )));
+    }
+    Ok(attachments)
+}
+
+fn parse_actions(entries: &[String]) -> Result<Vec<Action>, NotifallError> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (label, target) = entry
+                .split_once('=')
+                .filter(|(label, _)| !label.trim().is_empty())
+                .ok_or_else(|| NotifallError::InvalidAction(entry.clone()))?;
+            let (url, command) = if let Some(url) = target.strip_prefix("url:") {
+                (Some(url.to_string()), None)
+            } else if let Some(command) = target.strip_prefix("cmd:") {
+                (None, Some(command.to_string()))
+            } else {
+                return Err(NotifallError::InvalidAction(entry.clone()));
+            };
+            Ok(Action {
+                label: label.trim().to_string(),
+                url,
+                command,
+            })
+        })
+        .collect()
+}
+
+fn handle_ask(config_path: Option<&PathBuf>, args: AskArgs) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let timeout = parse_duration(&args.timeout)?;
+    let source = args.source.as_ref().map(|s| s.to_lowercase());
+    let source_config = resolve_source_config(config.as_ref(), source.as_deref());
+    let notification = Notification {
+        title: resolve_title(args.title.clone(), source_config, source.as_deref()),
+        message: args.message.clone(),
+        source,
+        urgency: Some(args.urgency.map(map_urgency).unwrap_or(Urgency::High)),
+        tag: args.tag.clone(),
+        actions: args
+            .actions
+            .iter()
+            .map(|label| Action {
+                label: label.trim().to_string(),
+                url: None,
+                command: None,
+            })
+            .filter(|action| !action.label.is_empty())
+            .collect(),
+        ..Default::default()
+    };
+
+    let answer = delivery::ask(
+        config.as_ref(),
+        &ProviderRegistry::builtin(),
+        args.provider.as_deref(),
+        notification,
+        timeout,
+    )?;
+    if args.json {
+        println!("{}", serde_json::json!({ "action": answer }));
+    } else if let Some(label) = answer.as_deref() {
+        println!("{label}");
     }
-    if json {
-        print_send_output("macos", report.outcome, false, None)?;
+    if answer.is_none() {
+        if !args.json {
+            eprintln!("no answer within {}", format_duration(timeout));
+        }
+        std::process::exit(2);
     }
     Ok(())
 }
@@ -277,31 +421,279 @@ fn handle_config_init(
     Ok(())
 }
 
-fn handle_providers_list() -> Result<(), NotifallError> {
-    println!("forward");
-    println!("remote");
-    println!("telegram");
-    if cfg!(target_os = "macos") {
-        println!("macos");
+fn handle_providers_list(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    // Config only knows the built-in sections, so look at the raw file to
+    // tell which tables are actually there.
+    let sections = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Table>().ok())
+        .unwrap_or_default();
+
+    let rows = ProviderRegistry::builtin()
+        .iter()
+        .map(|provider| provider_row(provider, config.as_ref(), &path, &sections))
+        .collect::<Vec<_>>();
+    print_columns(
+        &["PROVIDER", "AVAILABLE", "CONFIGURED", "FEATURES", "CONFIG"],
+        &rows,
+    );
+    Ok(())
+}
+
+/// One `providers list` row: name, availability, whether it is configured
+/// and if not what is missing, features, and where its settings come from.
+fn provider_row(
+    provider: &dyn ProviderPlugin,
+    config: Option<&Config>,
+    path: &Path,
+    sections: &toml::Table,
+) -> Vec<String> {
+    let check = provider.check_config(config);
+    let configured = match &check {
+        Ok(()) => "yes".to_string(),
+        Err(ProviderError::NotConfigured { field }) => format!("no ({field})"),
+        Err(err) => format!("no ({err})"),
+    };
+    let source = match provider.config_section() {
+        Some(section) if sections.contains_key(section) => {
+            format!("{} [{section}]", path.display())
+        }
+        _ if check.is_ok() => "defaults".to_string(),
+        _ => "-".to_string(),
+    };
+    let features = provider.capabilities().names();
+    vec![
+        provider.name().to_string(),
+        if provider.available() { "yes" } else { "no" }.to_string(),
+        configured,
+        if features.is_empty() {
+            "-".to_string()
+        } else {
+            features.join(",")
+        },
+        source,
+    ]
+}
+
+/// Sends a labeled notification through one provider, or every available
+/// one, and reports how each went. Exits 1 if any failed.
+fn handle_providers_test(
+    config_path: Option<&PathBuf>,
+    args: ProvidersTestArgs,
+) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let registry = ProviderRegistry::builtin();
+    let requested = args.provider.to_lowercase();
+    let names: Vec<&str> = if requested == "all" {
+        registry
+            .iter()
+            .filter(|provider| provider.available())
+            .map(|provider| provider.name())
+            .collect()
     } else {
-        println!("(no providers available on this platform yet)");
+        match registry.get(&requested) {
+            Some(provider) => vec![provider.name()],
+            None => return Err(NotifallError::ProviderUnsupported(args.provider)),
+        }
+    };
+
+    let origin = RemoteContext::from_local(detect_context());
+    let host = origin
+        .origin_host
+        .clone()
+        .unwrap_or_else(|| "this machine".to_string());
+    // A remote test that quietly fell back to a local notification would
+    // pass without ever reaching the listener.
+    let overrides = SendOverrides {
+        no_fallback: true,
+        ..Default::default()
+    };
+    let cx = SendContext {
+        config: config.as_ref(),
+        overrides: &overrides,
+        origin: &origin,
+        interaction: &Interaction::default(),
+        registry: &registry,
+    };
+
+    let mut rows = Vec::new();
+    let mut failed = false;
+    for name in names {
+        let notification = Notification::new(
+            "ding test",
+            format!("Test notification from {host} via {name}. No action needed."),
+        );
+        let started = Instant::now();
+        let result = delivery::deliver(&cx, name, notification, &mut Vec::new());
+        failed |= result.is_err();
+        rows.push(test_row(name, &result.map(|_| ()), started.elapsed()));
+    }
+    print_columns(&["PROVIDER", "RESULT", "LATENCY", "KIND", "ERROR"], &rows);
+    if failed {
+        std::process::exit(1);
     }
     Ok(())
 }
 
+/// One `providers test` row: name, pass or fail, latency, and for a failure
+/// its error kind and message.
+fn test_row(name: &str, result: &Result<(), NotifallError>, latency: Duration) -> Vec<String> {
+    let (status, kind, error) = match result {
+        Ok(()) => ("pass", "-", "-".to_string()),
+        Err(err) => ("fail", err.kind(), err.to_string()),
+    };
+    vec![
+        name.to_string(),
+        status.to_string(),
+        format!("{}ms", latency.as_millis()),
+        kind.to_string(),
+        error,
+    ]
+}
+
+/// Prints `rows` under `header`, padding every column but the last to its
+/// widest cell.
+fn print_columns(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|cell| cell.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let last = row.len().saturating_sub(1);
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i == last {
+                    cell.clone()
+                } else {
+                    format!("{cell:<width$}", width = widths[i])
+                }
+            })
+            .collect();
+        println!("{}", line.join("  "));
+    }
+}
+
 fn handle_install(args: InstallArgs) -> Result<(), NotifallError> {
-    match args.target {
-        crate::cli::InstallTarget::Claude => install_claude(args.apply),
-        crate::cli::InstallTarget::Codex => install_codex(args.apply),
+    if args.status {
+        return install_status(args.target, args.scope);
+    }
+    // clap requires a target unless --status is given.
+    let Some(target) = args.target else {
+        return Err(NotifallError::Install(
+            "a target is required unless --status is given".to_string(),
+        ));
+    };
+    if !args.events.is_empty() && !matches!(target, InstallTarget::Claude) {
+        return Err(NotifallError::Install(
+            "--events only applies to claude".to_string(),
+        ));
     }
+    let events = args
+        .events
+        .iter()
+        .map(|event| claude_event_name(*event))
+        .collect::<Vec<_>>();
+    let exe = std::env::current_exe()?;
+    let plan = install::plan_install(
+        install_agent(target),
+        install_scope(args.scope),
+        &exe,
+        &events,
+        args.force,
+    )?;
+    apply_integration_plan(plan, args.apply)
+}
+
+fn handle_uninstall(args: UninstallArgs) -> Result<(), NotifallError> {
+    let plan = install::plan_uninstall(install_agent(args.target), install_scope(args.scope))?;
+    apply_integration_plan(plan, args.apply)
 }
 
-fn handle_hook(args: HookArgs) -> Result<(), NotifallError> {
-    let payload = read_hook_payload(args.json.as_deref())?;
-    match args.target {
-        crate::cli::InstallTarget::Claude => handle_claude_hook(payload),
-        crate::cli::InstallTarget::Codex => handle_codex_hook(payload),
+fn handle_hook(config_path: Option<&PathBuf>, args: HookArgs) -> Result<(), NotifallError> {
+    // Aider runs its notifications command without a payload and with the
+    // terminal still attached to stdin, so there is nothing to read.
+    let payload = if matches!(args.target, HookTarget::Aider) {
+        serde_json::Value::Null
+    } else {
+        hook::read_payload(args.json.as_deref())?
+    };
+    if args.ask
+        && matches!(args.target, HookTarget::Claude)
+        && payload.get("hook_event_name").and_then(|v| v.as_str()) == Some("PreToolUse")
+    {
+        let timeout = parse_duration(&args.timeout)?;
+        let config = load_config(config_path)?;
+        let registry = ProviderRegistry::builtin();
+        let output = hook::permission_decision(config.as_ref(), &registry, &payload, timeout);
+        println!("{output}");
+        return Ok(());
     }
+    let payload = match args.target {
+        HookTarget::Claude => hook::claude_payload(payload),
+        _ => payload,
+    };
+    let (name, base) = match args.target {
+        HookTarget::Claude => ("claude", mapping::builtin_event("claude", &payload)),
+        HookTarget::Codex => ("codex", mapping::builtin_event("codex", &payload)),
+        HookTarget::Gemini => ("gemini", hook::gemini_event(&payload)),
+        HookTarget::Aider => ("aider", hook::aider_event()),
+        HookTarget::Opencode => ("opencode", hook::opencode_event(&payload)),
+        HookTarget::Generic => (args.mapping.as_deref().unwrap_or_default(), None),
+    };
+
+    let config = load_config(config_path)?;
+    let mapping = config
+        .as_ref()
+        .and_then(|c| c.hook_mappings.as_ref())
+        .and_then(|mappings| mappings.get(name));
+    if mapping.is_none() && matches!(args.target, HookTarget::Generic) {
+        return Err(NotifallError::UnknownHookMapping(name.to_string()));
+    }
+    let event = match mapping {
+        Some(mapping) => mapping::map_event(name, mapping, &payload, base),
+        None => base,
+    };
+    let Some(event) = event else {
+        debug_log(&format!("hook {name}: event skipped"));
+        return Ok(());
+    };
+    let source = mapping.and_then(|m| m.source.as_deref()).unwrap_or(name);
+    send_hook_event(config_path, source, event)
+}
+
+/// Sends a hook notification that focuses the originating terminal on click.
+fn send_hook_event(
+    config_path: Option<&PathBuf>,
+    source: &str,
+    event: MappedEvent,
+) -> Result<(), NotifallError> {
+    let args = SendArgs {
+        title: Some(event.title),
+        message: if event.message.trim().is_empty() {
+            " ".to_string()
+        } else {
+            event.message
+        },
+        urgency: event.urgency.map(urgency_arg),
+        tag: event.tag,
+        source: Some(source.to_string()),
+        metadata: event
+            .metadata
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect(),
+        on_click: default_focus_command(),
+        background: true,
+        ..Default::default()
+    };
+    handle_send(config_path, args)
 }
 
 fn handle_sources_list(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
@@ -320,15 +712,77 @@ fn handle_sources_list(config_path: Option<&PathBuf>) -> Result<(), NotifallErro
             .as_ref()
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| "-".to_string());
-        let bundle = source
-            .app_bundle_id
-            .as_deref()
-            .unwrap_or("-");
+        let bundle = source.app_bundle_id.as_deref().unwrap_or("-");
         println!("{name}\t{icon}\t{bundle}");
     }
     Ok(())
 }
 
+fn handle_doctor(config_path: Option<&PathBuf>, args: DoctorArgs) -> Result<(), NotifallError> {
+    let registry = ProviderRegistry::builtin();
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let (config, mut checks) = doctor::check_config(&path, &registry);
+    let config = config.as_ref();
+    checks.extend(doctor::check_context());
+    checks.extend(doctor::check_providers(config, &registry));
+    checks.extend(doctor::check_remote(config));
+    checks.extend(doctor::check_telegram(config));
+    checks.extend(doctor::check_hooks());
+    checks.extend(doctor::check_directories());
+    checks.extend(doctor::check_bundles());
+
+    let failed = checks.iter().any(|check| check.status == Status::Fail);
+    if args.json {
+        let report = serde_json::json!({ "ok": !failed, "checks": checks });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_doctor_report(&checks);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn print_doctor_report(checks: &[Check]) {
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut section = "";
+    for check in checks {
+        if check.section != section {
+            if !section.is_empty() {
+                println!();
+            }
+            section = check.section;
+            println!("{section}");
+        }
+        // Keep multi-line details, like TOML parse errors, in their column.
+        let indent = format!("\n  {:<4}  {:<width$}  ", "", "");
+        println!(
+            "  {:<4}  {:<width$}  {}",
+            check.status.as_str(),
+            check.name,
+            check.detail.trim_end().replace('\n', &indent)
+        );
+        if let Some(hint) = &check.hint {
+            println!("  {:<4}  {:<width$}  -> {hint}", "", "");
+        }
+    }
+
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let (warnings, failures) = (count(Status::Warn), count(Status::Fail));
+    println!();
+    if warnings == 0 && failures == 0 {
+        println!("no problems found");
+    } else {
+        let plural = if warnings == 1 { "" } else { "s" };
+        println!("{failures} failed, {warnings} warning{plural}");
+    }
+}
+
 fn handle_focus(args: FocusArgs) -> Result<(), NotifallError> {
     let terminal = args
         .terminal
@@ -373,12 +827,12 @@ fn handle_focus(args: FocusArgs) -> Result<(), NotifallError> {
     Ok(())
 }
 
-fn handle_listen(
-    config_path: Option<&PathBuf>,
-    args: ListenArgs,
-) -> Result<(), NotifallError> {
+fn handle_listen(config_path: Option<&PathBuf>, args: ListenArgs) -> Result<(), NotifallError> {
     let config = load_config(config_path)?;
-    let listener_cfg = config.as_ref().and_then(|c| c.listener.clone()).unwrap_or_default();
+    let listener_cfg = config
+        .as_ref()
+        .and_then(|c| c.listener.clone())
+        .unwrap_or_default();
 
     let bind = args
         .bind
@@ -408,1225 +862,1175 @@ fn handle_listen(
             .or(listener_cfg.on_click)
             .or_else(default_focus_command)
     };
+    let allow_remote_commands =
+        args.allow_remote_commands || listener_cfg.allow_remote_commands.unwrap_or(false);
 
+    let registry = ProviderRegistry::builtin();
     let addr = format!("{}:{}", bind, port);
-    let server = tiny_http::Server::http(&addr)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let listener = Listener::bind(&addr)?
+        .with_token(token, require_token)
+        .with_allowed_hosts(allow_hosts);
     println!("ding listener on {addr}");
 
-    for mut request in server.incoming_requests() {
-        let path = request.url().split('?').next().unwrap_or("");
-        if debug_enabled() {
-            let remote = request
-                .remote_addr()
-                .map(|addr| addr.ip().to_string())
-                .unwrap_or_else(|| "unknown".to_string());
-            debug_log(&format!(
-                "listener request path={path} remote={remote}"
-            ));
-        }
-        if path == "/ping" {
-            let response = json_response(200, r#"{"status":"ok"}"#);
-            let _ = request.respond(response);
-            continue;
-        }
-
-        if path != "/notify" {
-            let response = json_response(404, r#"{"error":"not found"}"#);
-            let _ = request.respond(response);
-            continue;
-        }
-
-        if request.method() != &tiny_http::Method::Post {
-            let response = json_response(405, r#"{"error":"method not allowed"}"#);
-            let _ = request.respond(response);
-            continue;
-        }
-
-        if !allow_hosts.is_empty() {
-            if let Some(remote) = request.remote_addr() {
-                let host = remote.ip().to_string();
-                if !allow_hosts.iter().any(|allowed| allowed == &host) {
-                    let response = json_response(403, r#"{"error":"forbidden"}"#);
-                    let _ = request.respond(response);
-                    continue;
-                }
-            }
-        }
-
-        if require_token {
-            let incoming = extract_token(request.headers());
-            if token.as_deref() != incoming.as_deref() {
-                let response = json_response(401, r#"{"error":"unauthorized"}"#);
-                let _ = request.respond(response);
-                continue;
+    let pending_asks = Arc::new(AtomicUsize::new(0));
+    listener.serve(|request, reply| {
+        let (envelope, is_ask) = match request {
+            ListenerRequest::Notify(envelope) => (envelope, false),
+            ListenerRequest::Ask(envelope) => (envelope, true),
+        };
+        let notification = received_notification(
+            config.as_ref(),
+            &envelope,
+            prefix_hostname,
+            allow_remote_commands,
+        );
+        let mut history_entry =
+            HistoryEntry::new(HistoryKind::Received, "macos", notification.clone());
+        history_entry.context = envelope.context.clone();
+
+        // Answering can take minutes; don't hold up other notifications.
+        if is_ask {
+            if pending_asks.fetch_add(1, Ordering::SeqCst) >= MAX_PENDING_ASKS {
+                pending_asks.fetch_sub(1, Ordering::SeqCst);
+                reply.busy(
+                    "too many questions are waiting for an answer",
+                    ASK_RETRY_AFTER,
+                );
+                return;
             }
+            let pending_asks = pending_asks.clone();
+            let config = config.clone();
+            let timeout = Duration::from_millis(envelope.timeout_ms.unwrap_or(120_000));
+            std::thread::spawn(move || {
+                let answer = delivery::ask_macos(config.as_ref(), notification, timeout);
+                let targets = vec![match &answer {
+                    Ok(_) => TargetResult::ok("macos", None),
+                    Err(err) => TargetResult::failed("macos", err),
+                }];
+                let result = answer.map(|label| label.map(DeliveryOutcome::ActionButton));
+                record_history(config.as_ref(), history_entry, targets, &result);
+                match result {
+                    Ok(Some(DeliveryOutcome::ActionButton(label))) => reply.answer(Some(&label)),
+                    Ok(_) => reply.answer(None),
+                    Err(err) => reply.error(&err.to_string()),
+                }
+                pending_asks.fetch_sub(1, Ordering::SeqCst);
+            });
+            return;
         }
 
-        let mut body = String::new();
-        if request.as_reader().read_to_string(&mut body).is_err() {
-            let response = json_response(400, r#"{"error":"invalid body"}"#);
-            let _ = request.respond(response);
-            continue;
+        if DndStore::default_location().suppresses(&notification) {
+            record_suppressed(config.as_ref(), history_entry);
+            reply.ok();
+            return;
         }
 
-        let envelope: RemoteEnvelope = match serde_json::from_str(&body) {
-            Ok(payload) => payload,
-            Err(_) => {
-                let response = json_response(400, r#"{"error":"invalid json"}"#);
-                let _ = request.respond(response);
-                continue;
+        let interaction = Interaction {
+            background: on_click.is_some() || has_click_actions(&notification),
+            on_click: on_click.clone(),
+            ..Default::default()
+        };
+        let cx = SendContext {
+            config: config.as_ref(),
+            overrides: &SendOverrides::default(),
+            origin: &RemoteContext::from_local(detect_context()),
+            interaction: &interaction,
+            registry: &registry,
+        };
+        let mut targets = Vec::new();
+        let result = match &envelope.progress {
+            Some(task) => {
+                let host = envelope
+                    .context
+                    .as_ref()
+                    .and_then(|c| c.origin_host.as_deref());
+                let result = delivery::progress_macos(&cx, &task.from_host(host), notification);
+                targets.push(match &result {
+                    Ok(()) => TargetResult::ok(LOCAL_PROVIDER, None),
+                    Err(err) => TargetResult::failed(LOCAL_PROVIDER, err),
+                });
+                result.map(|()| None)
             }
+            None => delivery::deliver(&cx, LOCAL_PROVIDER, notification, &mut targets),
         };
+        record_history(config.as_ref(), history_entry, targets, &result);
+        reply.ok();
+    });
 
-        let mut notification = envelope.notification;
-        if debug_enabled() {
-            let source = notification.source.as_deref().unwrap_or("(none)");
-            debug_log(&format!(
-                "listener notify title=\"{}\" source={source}",
-                notification.title
-            ));
-        }
-        notification.icon = None;
-        if notification.title.trim().is_empty() {
-            notification.title = "Notification".to_string();
-        }
+    Ok(())
+}
 
-        if prefix_hostname {
-            if let Some(host) = envelope
-                .context
-                .as_ref()
-                .and_then(|ctx| ctx.origin_host.as_deref())
-            {
-                let suffix = format!(" [{host}]");
-                if !notification.title.ends_with(&suffix) {
-                    notification.title = format!("{}{}", notification.title, suffix);
-                }
+/// Prepares a notification received by the listener for local display.
+/// Command actions are dropped unless `allow_commands` is set, leaving the
+/// button; URL actions are kept.
+fn received_notification(
+    config: Option<&Config>,
+    envelope: &RemoteEnvelope,
+    prefix_hostname: bool,
+    allow_commands: bool,
+) -> Notification {
+    // Truncate before the hostname suffix so the suffix always survives.
+    let mut notification = truncate_message(config, "macos", envelope.notification.clone());
+    if debug_enabled() {
+        let source = notification.source.as_deref().unwrap_or("(none)");
+        debug_log(&format!(
+            "listener notify title=\"{}\" source={source}",
+            notification.title
+        ));
+    }
+    notification.icon = None;
+    if !allow_commands {
+        for action in &mut notification.actions {
+            if action.command.take().is_some() {
+                debug_log(&format!(
+                    "listener dropped command for action \"{}\"",
+                    action.label
+                ));
             }
         }
-
-        let source_key = notification.source.as_deref();
-        let source_config = resolve_source_config(config.as_ref(), source_key);
-        let macos_config = resolve_macos_config(config.as_ref(), source_config, source_key);
-
-        let local_context = detect_context();
-        let _ = deliver_macos(
-            notification,
-            macos_config,
-            on_click.clone(),
-            on_click.is_some(),
-            false,
-            false,
-            local_context,
-        );
-
-        let response = json_response(200, r#"{"status":"ok"}"#);
-        let _ = request.respond(response);
+    }
+    if notification.title.trim().is_empty() {
+        notification.title = "Notification".to_string();
+    }
+    for (name, err) in attachment::store_received(&mut notification.attachments, &attachments_dir())
+    {
+        debug_log(&format!("listener attachment {name} not saved: {err}"));
     }
 
-    Ok(())
-}
-
-fn handle_remote(command: RemoteCmd, config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
-    match command {
-        RemoteCmd::Ping(args) => handle_remote_ping(args, config_path),
+    if prefix_hostname
+        && let Some(host) = envelope
+            .context
+            .as_ref()
+            .and_then(|ctx| ctx.origin_host.as_deref())
+    {
+        let suffix = format!(" [{host}]");
+        if !notification.title.ends_with(&suffix) {
+            notification.title = format!("{}{}", notification.title, suffix);
+        }
     }
+    notification
 }
 
-fn handle_telegram(
-    command: TelegramCmd,
-    config_path: Option<&PathBuf>,
-) -> Result<(), NotifallError> {
-    match command {
-        TelegramCmd::ChatId(args) => handle_telegram_chat_id(args, config_path),
+fn handle_history(config_path: Option<&PathBuf>, args: HistoryArgs) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let store = HistoryStore::from_config(config.as_ref());
+
+    if let Some(HistoryCmd::Show(show)) = args.command {
+        return handle_history_show(&store, show);
     }
-}
 
-fn handle_telegram_chat_id(
-    args: TelegramChatIdArgs,
-    config_path: Option<&PathBuf>,
-) -> Result<(), NotifallError> {
-    let path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
-    let existing = fs::read_to_string(&path).unwrap_or_default();
-    let doc = toml_edit::DocumentMut::from_str(&existing)?;
-    let token = args
-        .token
-        .or_else(|| {
-            doc.get("telegram")
-                .and_then(|v| v.get("bot_token"))
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
+    let since = args
+        .since
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?
+        .map(|d| history::now_secs().saturating_sub(d.as_secs()));
+    let source = args.source.as_ref().map(|s| s.to_lowercase());
+    let grep = args.grep.as_ref().map(|g| g.to_lowercase());
+
+    let entries = store
+        .load()?
+        .into_iter()
+        .rev()
+        .filter(|e| since.is_none_or(|ts| e.timestamp >= ts))
+        .filter(|e| {
+            source.is_none() || e.notification.source.as_deref().map(|s| s.to_lowercase()) == source
         })
-        .ok_or_else(|| {
-            NotifallError::Provider(ProviderError::Message(
-                "telegram bot_token is not configured".to_string(),
-            ))
-        })?;
-
-    let updates_url = format!("https://api.telegram.org/bot{token}/getUpdates");
-    let response = ureq::get(&updates_url).call();
-    let value = match response {
-        Ok(res) => res
-            .into_json::<serde_json::Value>()
-            .map_err(|err| NotifallError::Provider(ProviderError::Message(err.to_string())))?,
-        Err(ureq::Error::Status(code, res)) => {
-            let desc = res
-                .into_json::<serde_json::Value>()
-                .ok()
-                .and_then(|v| v.get("description").and_then(|d| d.as_str()).map(|s| s.to_string()))
-                .unwrap_or_else(|| format!("telegram error status {code}"));
-            return Err(NotifallError::Provider(ProviderError::Message(desc)));
-        }
-        Err(err) => {
-            return Err(NotifallError::Provider(ProviderError::Message(
-                err.to_string(),
-            )))
-        }
-    };
+        .filter(|e| {
+            grep.as_deref().is_none_or(|needle| {
+                e.notification.title.to_lowercase().contains(needle)
+                    || e.notification.message.to_lowercase().contains(needle)
+            })
+        })
+        .take(args.limit)
+        .collect::<Vec<_>>();
 
-    let result = value
-        .get("result")
-        .and_then(|v| v.as_array())
-        .ok_or_else(|| {
-            NotifallError::Provider(ProviderError::Message(
-                "telegram getUpdates returned no results".to_string(),
-            ))
-        })?;
-
-    let mut chat_ids = Vec::new();
-    for item in result {
-        if let Some(chat_id) = item
-            .get("message")
-            .and_then(|m| m.get("chat"))
-            .and_then(|c| c.get("id"))
-        {
-            if let Some(id) = chat_id.as_i64() {
-                chat_ids.push(id.to_string());
-            } else if let Some(id) = chat_id.as_str() {
-                chat_ids.push(id.to_string());
-            }
+    if args.json {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
         }
+        return Ok(());
     }
 
-    chat_ids.sort();
-    chat_ids.dedup();
-
-    if chat_ids.is_empty() {
-        return Err(NotifallError::Provider(ProviderError::Message(
-            "no chat_id found in getUpdates (send a message to the bot first)".to_string(),
-        )));
+    if entries.is_empty() {
+        println!("(no history entries)");
+        return Ok(());
     }
 
-    for id in &chat_ids {
-        println!("{id}");
-    }
-
-    if args.apply {
-        let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
-        set_toml_key(
-            &mut doc,
-            "telegram.chat_id",
-            toml_edit::Value::from(chat_ids[0].as_str()),
-        )?;
-        let new_contents = doc.to_string();
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        fs::write(&path, new_contents)?;
-        println!("set telegram.chat_id in {}", path.display());
+    for entry in entries {
+        let status = if entry.ok { "ok" } else { "failed" };
+        let message = entry.notification.message.lines().next().unwrap_or("");
+        let summary = truncate_to(format!("{}: {}", entry.notification.title, message), 80);
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            entry.id,
+            history::format_timestamp(entry.timestamp),
+            entry.kind.as_str(),
+            entry.provider,
+            status,
+            summary
+        );
     }
-
     Ok(())
 }
 
-fn handle_remote_ping(
-    args: RemotePingArgs,
-    config_path: Option<&PathBuf>,
-) -> Result<(), NotifallError> {
-    let config = load_config(config_path)?;
-    let remote_cfg = config.and_then(|c| c.remote).unwrap_or_default();
-    let target = resolve_remote_target(
-        args.remote_host.as_deref(),
-        args.remote_port,
-        remote_cfg.host.as_deref(),
-        remote_cfg.port,
-        remote_cfg.url.as_deref(),
-    )
-    .ok_or_else(|| {
-        NotifallError::Provider(ProviderError::Message(
-            "remote host is not configured".to_string(),
-        ))
-    })?;
-    let token = args.remote_token.or(remote_cfg.token);
-    let ping_url = to_ping_url(&target.0);
-
-    let agent = ureq::AgentBuilder::new()
-        .timeout_connect(Duration::from_millis(2000))
-        .timeout_read(Duration::from_millis(2000))
-        .build();
-    let mut request = agent.get(&ping_url);
-    if let Some(token) = token.as_deref() {
-        request = request.set("Authorization", &format!("Bearer {token}"));
-    }
-    match request.call() {
-        Ok(_) => {
-            println!("ok");
-            Ok(())
-        }
-        Err(err) => Err(NotifallError::Provider(
-            ProviderError::Message(format!("remote ping failed: {err}")),
-        )),
-    }
-}
+fn handle_history_show(store: &HistoryStore, args: HistoryShowArgs) -> Result<(), NotifallError> {
+    let entry = store.find(&args.id)?;
 
-fn handle_forward(
-    command: ForwardCmd,
-    config_path: Option<&PathBuf>,
-) -> Result<(), NotifallError> {
-    let path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
-    let existing = fs::read_to_string(&path).unwrap_or_default();
-    let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entry)?);
+        return Ok(());
+    }
 
-    match command {
-        ForwardCmd::Status => {
-            let enabled = forward_enabled_from_doc(&doc);
-            let targets = forward_targets_from_doc(&doc);
-            println!("forwarding: {}", if enabled { "on" } else { "off" });
-            if targets.is_empty() {
-                println!("targets: (none)");
-            } else {
-                println!("targets: {}", targets.join(", "));
-            }
-            return Ok(());
-        }
-        ForwardCmd::Off => {
-            set_forward_enabled(&mut doc, false);
+    let notification = &entry.notification;
+    println!("id:       {}", entry.id);
+    println!("time:     {}", history::format_timestamp(entry.timestamp));
+    println!("kind:     {}", entry.kind.as_str());
+    println!("provider: {}", entry.provider);
+    match entry.error.as_deref() {
+        Some(err) => println!("status:   failed ({err})"),
+        None => println!("status:   ok"),
+    }
+    if let Some(outcome) = entry.outcome.as_ref() {
+        println!("outcome:  {outcome}");
+    }
+    println!("title:    {}", notification.title);
+    println!("message:  {}", notification.message);
+    if let Some(source) = notification.source.as_deref() {
+        println!("source:   {source}");
+    }
+    if let Some(urgency) = notification.urgency {
+        println!(
+            "urgency:  {}",
+            serde_json::to_string(&urgency)?.trim_matches('"')
+        );
+    }
+    if let Some(tag) = notification.tag.as_deref() {
+        println!("tag:      {tag}");
+    }
+    if let Some(link) = notification.link.as_deref() {
+        println!("link:     {link}");
+    }
+    if let Some(context) = entry.context.as_ref() {
+        if let Some(host) = context.origin_host.as_deref() {
+            println!("host:     {host}");
         }
-        ForwardCmd::Toggle => {
-            let enabled = forward_enabled_from_doc(&doc);
-            if enabled {
-                set_forward_enabled(&mut doc, false);
-            } else {
-                let targets = forward_targets_from_doc(&doc);
-                if targets.is_empty() {
-                    return Err(NotifallError::RemoteForwardMissingHost(
-                        forward_missing_targets_message(&path),
-                    ));
-                }
-                set_forward_enabled(&mut doc, true);
-            }
+        if let Some(cwd) = context.cwd.as_deref() {
+            println!("cwd:      {cwd}");
         }
-        ForwardCmd::On(args) => {
-            apply_forward_on(&mut doc, &path, &args)?;
+        if let Some(tmux) = context.tmux.as_ref() {
+            println!("tmux:     {} {} {}", tmux.session, tmux.window, tmux.pane);
         }
     }
-
-    let new_contents = doc.to_string();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    fs::write(&path, new_contents)?;
-    println!(
-        "forwarding {}",
-        if forward_enabled_from_doc(&doc) { "enabled" } else { "disabled" }
-    );
-    Ok(())
-}
-
-fn handle_config_set(
-    config_path: Option<&PathBuf>,
-    args: ConfigSetArgs,
-) -> Result<(), NotifallError> {
-    let path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
-    let existing = fs::read_to_string(&path).unwrap_or_default();
-    let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
-    let value = parse_config_value(&args.value);
-    set_toml_key(&mut doc, &args.key, value)?;
-    let new_contents = doc.to_string();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+    println!("targets:");
+    for target in &entry.targets {
+        match target.error.as_deref() {
+            Some(err) => println!("  {}\tfailed\t{err}", target.provider),
+            None => println!("  {}\tok", target.provider),
+        }
     }
-    fs::write(&path, new_contents)?;
-    println!("set {} in {}", args.key, path.display());
     Ok(())
 }
 
-fn handle_config_path(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
-    let path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
-    println!("{}", path.display());
-    Ok(())
-}
+fn handle_replay(config_path: Option<&PathBuf>, args: ReplayArgs) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let store = HistoryStore::from_config(config.as_ref());
+
+    let provider = args.provider.as_deref().map(|p| p.to_lowercase());
+    // Which providers to send each entry to: an explicit --provider, or the
+    // targets the entry failed to reach, or everything it went to before.
+    let targets_for = |entry: &HistoryEntry, failed: Vec<String>| match &provider {
+        Some(provider) => vec![provider.clone()],
+        None if failed.is_empty() => vec![entry.provider.clone()],
+        None => failed,
+    };
+    let entries = if let Some(id) = args.id.as_deref() {
+        let entry = store.find(id)?;
+        let targets = targets_for(&entry, entry.failed_targets());
+        vec![(entry, targets)]
+    } else if args.last {
+        let entry = store
+            .load()?
+            .into_iter()
+            .rev()
+            .find(|e| e.kind == HistoryKind::Sent)
+            .ok_or_else(|| NotifallError::HistoryNotFound("--last".to_string()))?;
+        let targets = targets_for(&entry, entry.failed_targets());
+        vec![(entry, targets)]
+    } else {
+        let since = args
+            .since
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?
+            .map(|d| history::now_secs().saturating_sub(d.as_secs()));
+        let all = store.load()?;
+        history::pending_replays(&all)
+            .into_iter()
+            .filter(|(entry, _)| since.is_none_or(|ts| entry.timestamp >= ts))
+            .map(|(entry, failed)| (entry.clone(), targets_for(entry, failed)))
+            .collect()
+    };
 
-fn handle_config_list(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
-    let path = config_path
-        .cloned()
-        .unwrap_or_else(default_config_path);
-    if !path.exists() {
-        println!("(no config file at {})", path.display());
+    if entries.is_empty() {
+        println!("(nothing to replay)");
         return Ok(());
     }
-    let contents = fs::read_to_string(&path)?;
-    print!("{contents}");
-    Ok(())
-}
-
-fn remote_url_from_doc(doc: &toml_edit::DocumentMut) -> Option<String> {
-    doc.get("remote")
-        .and_then(|v| v.get("url"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
-}
-
-fn remote_host_from_doc(doc: &toml_edit::DocumentMut) -> Option<String> {
-    doc.get("remote")
-        .and_then(|v| v.get("host"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
-        .or_else(|| remote_url_from_doc(doc).and_then(|url| parse_remote_url(&url).map(|t| t.0)))
-}
-
-fn remote_port_from_doc(doc: &toml_edit::DocumentMut) -> Option<u16> {
-    doc.get("remote")
-        .and_then(|v| v.get("port"))
-        .and_then(|v| v.as_integer())
-        .and_then(|v| u16::try_from(v).ok())
-        .or_else(|| remote_url_from_doc(doc).and_then(|url| parse_remote_url(&url).map(|t| t.1)))
-}
-
-fn forward_enabled_from_doc(doc: &toml_edit::DocumentMut) -> bool {
-    doc.get("forward")
-        .and_then(|v| v.get("enabled"))
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false)
-}
-
-fn forward_targets_from_doc(doc: &toml_edit::DocumentMut) -> Vec<String> {
-    doc.get("forward")
-        .and_then(|v| v.get("targets"))
-        .and_then(|v| v.as_array())
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|item| item.as_str().map(|s| s.to_string()))
-                .collect::<Vec<_>>()
-        })
-        .unwrap_or_default()
-}
-
-fn set_forward_enabled(doc: &mut toml_edit::DocumentMut, enabled: bool) {
-    let table = doc.entry("forward").or_insert(toml_edit::table());
-    if let Some(table) = table.as_table_mut() {
-        table["enabled"] = toml_edit::Item::Value(toml_edit::Value::from(enabled));
-    }
-}
 
-fn set_forward_targets(doc: &mut toml_edit::DocumentMut, targets: &[String]) {
-    let table = doc.entry("forward").or_insert(toml_edit::table());
-    if let Some(table) = table.as_table_mut() {
-        let mut arr = toml_edit::Array::new();
-        for target in targets {
-            arr.push(target.as_str());
+    let mut failures = 0usize;
+    for (entry, targets) in entries {
+        if args.dry_run {
+            println!(
+                "{}\t{}\t{}",
+                entry.id,
+                targets.join(","),
+                entry.notification.title
+            );
+            continue;
         }
-        table["targets"] = toml_edit::Item::Value(toml_edit::Value::Array(arr));
-    }
-}
 
-fn apply_forward_on(
-    doc: &mut toml_edit::DocumentMut,
-    path: &PathBuf,
-    args: &ForwardOnArgs,
-) -> Result<(), NotifallError> {
-    let mut targets = forward_targets_from_doc(doc);
-    let provided = args
-        .targets
-        .iter()
-        .map(|t| match t {
-            ForwardTarget::Remote => "remote".to_string(),
-            ForwardTarget::Telegram => "telegram".to_string(),
-        })
-        .collect::<Vec<_>>();
-
-    if args.append {
-        for target in provided {
-            if !targets.contains(&target) {
-                targets.push(target);
+        let mut origin = entry
+            .context
+            .clone()
+            .unwrap_or_else(|| RemoteContext::from_local(detect_context()));
+        if entry.kind == HistoryKind::Received {
+            // The sender's tmux pane lives on another host; focus the local one instead.
+            let local = detect_context().unwrap_or_default();
+            origin.tmux = local.tmux;
+            origin.terminal = local.terminal;
+        }
+        let send_args = SendArgs {
+            message: entry.notification.message.clone(),
+            on_click: default_focus_command(),
+            background: true,
+            ..Default::default()
+        };
+        for target in targets {
+            match dispatch_send(
+                config.as_ref(),
+                &target,
+                &send_args,
+                entry.notification.clone(),
+                origin.clone(),
+                Some(&entry.id),
+            ) {
+                Ok(_) => println!("replayed {} via {target}", entry.id),
+                Err(err) => {
+                    failures += 1;
+                    eprintln!("replay {} via {target} failed: {err}", entry.id);
+                }
             }
         }
-    } else if !provided.is_empty() {
-        targets = provided;
     }
 
-    if targets.is_empty() {
-        return Err(NotifallError::RemoteForwardMissingHost(
-            forward_missing_targets_message(path),
-        ));
+    if failures > 0 {
+        return Err(NotifallError::ReplayFailed(failures));
     }
+    Ok(())
+}
 
-    if targets.iter().any(|t| t == "remote") {
-        if let Some(host) = args.host.as_deref() {
-            set_remote_field(doc, "host", toml_edit::Value::from(host));
-        }
-        if let Some(port) = args.port {
-            set_remote_field(doc, "port", toml_edit::Value::from(port as i64));
+fn handle_run(config_path: Option<&PathBuf>, args: RunArgs) -> Result<(), NotifallError> {
+    let min_duration = args
+        .min_duration
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?;
+    let program = args.command[0].clone();
+    let command_line = args.command.join(" ");
+
+    let keep_lines = args.tail_lines.max(args.attach_lines);
+    let running = match run::Running::spawn(&args.command, keep_lines) {
+        Ok(running) => running,
+        Err(err) => {
+            // Match the shell: 127 when the program isn't found, 126 when
+            // it can't be run.
+            let (reason, code) = match err.kind() {
+                std::io::ErrorKind::NotFound => ("command not found".to_string(), 127),
+                _ => (err.to_string(), 126),
+            };
+            eprintln!("ding: {program}: {reason}");
+            std::process::exit(code);
         }
-        if remote_host_from_doc(doc).is_none() {
-            return Err(NotifallError::RemoteForwardMissingHost(
-                forward_missing_remote_message(path),
-            ));
+    };
+    let finished = running.wait()?;
+    let code = finished.code;
+    debug_log(&format!(
+        "run command={command_line} code={code} elapsed={:?}",
+        finished.elapsed
+    ));
+
+    if min_duration.is_none_or(|min| finished.elapsed >= min) {
+        let mut attachments = Vec::new();
+        if !finished.success() {
+            attachments.extend(finished.attach_output(args.attach_lines));
         }
-        if remote_port_from_doc(doc).is_none() {
-            set_remote_field(doc, "port", toml_edit::Value::from(4280i64));
+        let template = if finished.success() {
+            args.on_success
+                .as_deref()
+                .unwrap_or(run::DEFAULT_ON_SUCCESS)
+        } else {
+            args.on_failure
+                .as_deref()
+                .unwrap_or(run::DEFAULT_ON_FAILURE)
+        };
+        let message = finished.message(template, &command_line, args.tail_lines);
+
+        let send_args = SendArgs {
+            title: Some(args.title.clone().unwrap_or_else(|| {
+                let name = std::path::Path::new(&program)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or(&program);
+                title_from_source(name)
+            })),
+            message: message.trim_end().to_string(),
+            urgency: Some(if finished.success() {
+                UrgencyArg::Normal
+            } else {
+                UrgencyArg::High
+            }),
+            tag: args.tag.clone(),
+            source: args.source.clone(),
+            provider: args.provider.clone(),
+            on_click: default_focus_command(),
+            background: true,
+            attachments,
+            ..Default::default()
+        };
+        if let Err(err) = handle_send(config_path, send_args) {
+            eprintln!("ding: {err}");
         }
     }
 
-    set_forward_targets(doc, &targets);
-    set_forward_enabled(doc, true);
-    Ok(())
-}
-
-fn forward_missing_targets_message(path: &PathBuf) -> String {
-    format!(
-        "Forwarding needs at least one target.\n\n\
-Set it with:\n  ding forward on remote --host mba --port 4280\n\
-or:\n  ding forward on telegram\n\n\
-Config file: {}\n\
-If missing, run: ding config init",
-        path.display()
-    )
-}
-
-fn forward_missing_remote_message(path: &PathBuf) -> String {
-    format!(
-        "Forwarding to remote needs a host.\n\n\
-Set it with:\n  ding forward on remote --host mba --port 4280\n\
-or:\n  ding config set remote.host mba\n  ding config set remote.port 4280\n\n\
-Config file: {}\n\
-If missing, run: ding config init",
-        path.display()
-    )
-}
-
-fn set_remote_field(doc: &mut toml_edit::DocumentMut, key: &str, value: toml_edit::Value) {
-    let table = doc.entry("remote").or_insert(toml_edit::table());
-    if let Some(table) = table.as_table_mut() {
-        table[key] = toml_edit::Item::Value(value);
+    if code != 0 {
+        std::process::exit(code);
     }
+    Ok(())
 }
 
-fn parse_config_value(raw: &str) -> toml_edit::Value {
-    if raw.eq_ignore_ascii_case("true") {
-        return toml_edit::Value::from(true);
-    }
-    if raw.eq_ignore_ascii_case("false") {
-        return toml_edit::Value::from(false);
-    }
-    if let Ok(int_value) = raw.parse::<i64>() {
-        return toml_edit::Value::from(int_value);
+fn handle_watch(command: WatchCmd, config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    match command {
+        WatchCmd::File(args) => handle_watch_file(args, config_path),
+        WatchCmd::Pid(args) => handle_watch_pid(args, config_path),
+        WatchCmd::Proc(args) => handle_watch_proc(args, config_path),
+        WatchCmd::Codex(args) => handle_watch_codex(args, config_path),
     }
-    toml_edit::Value::from(raw)
 }
 
-fn set_toml_key(
-    doc: &mut toml_edit::DocumentMut,
-    key: &str,
-    value: toml_edit::Value,
+fn handle_watch_file(
+    args: WatchFileArgs,
+    config_path: Option<&PathBuf>,
 ) -> Result<(), NotifallError> {
-    let parts: Vec<&str> = key.split('.').collect();
-    if parts.is_empty() {
-        return Ok(());
-    }
-    let mut table = doc.as_table_mut();
-    for part in &parts[..parts.len().saturating_sub(1)] {
-        if !table.contains_key(part) {
-            table[part] = toml_edit::Item::Table(toml_edit::Table::new());
-        }
-        if !table[part].is_table() {
-            table[part] = toml_edit::Item::Table(toml_edit::Table::new());
-        }
-        if let Some(next) = table[part].as_table_mut() {
-            table = next;
-        } else {
-            return Err(NotifallError::Provider(ProviderError::Message(
-                "invalid config path".to_string(),
-            )));
+    let pattern = regex::Regex::new(&args.pattern)?;
+    let mut watcher = watch::MatchWatcher::new(
+        watch::FileFollower::new(&args.path, args.from_start),
+        pattern,
+        parse_duration(&args.dedupe_window)?,
+    );
+    let interval = Duration::from_millis(args.watch.interval_ms);
+    let title = args.watch.title.clone().unwrap_or_else(|| {
+        let name = watcher
+            .path()
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| args.path.display().to_string());
+        format!("Match in {name}")
+    });
+
+    loop {
+        for line in watcher.poll()? {
+            debug_log(&format!("watch file match line={line}"));
+            send_watch_notification(config_path, &args.watch, title.clone(), line);
+            if args.once {
+                return Ok(());
+            }
         }
+        std::thread::sleep(interval);
     }
-    let last = parts[parts.len() - 1];
-    table[last] = toml_edit::Item::Value(value);
-    Ok(())
 }
 
-fn handle_remote_send(
-    config: Option<&Config>,
-    args: &SendArgs,
-    notification: Notification,
-    remote_notification: Notification,
-    context: Option<Context>,
-    source_config: Option<&SourceConfig>,
-    source: Option<&str>,
+fn handle_watch_pid(
+    args: WatchPidArgs,
+    config_path: Option<&PathBuf>,
 ) -> Result<(), NotifallError> {
-    let remote_cfg = config.and_then(|c| c.remote.clone()).unwrap_or_default();
-    let target = resolve_remote_target(
-        args.remote_host.as_deref(),
-        args.remote_port,
-        remote_cfg.host.as_deref(),
-        remote_cfg.port,
-        remote_cfg.url.as_deref(),
-    );
-    let token = args.remote_token.clone().or(remote_cfg.token);
-    let timeout_ms = args.remote_timeout_ms.or(remote_cfg.timeout_ms).unwrap_or(2000);
-    let retries = args.remote_retries.or(remote_cfg.retries).unwrap_or(2);
-    let fallback = !args.no_fallback && remote_cfg.fallback_to_local.unwrap_or(true);
-
-    let envelope = RemoteEnvelope {
-        notification: remote_notification,
-        context: Some(RemoteContext::from_local(context.clone())),
+    let name = watch::process_name(args.pid);
+    let interval = Duration::from_millis(args.watch.interval_ms);
+    let Some(elapsed) = watch::wait_for_pid(args.pid, interval) else {
+        return Err(NotifallError::WatchTargetMissing(format!(
+            "no process with pid {}",
+            args.pid
+        )));
     };
 
-    let send_result = match target {
-        Some((url, _host, _port)) => {
-            send_remote_request(&url, token.as_deref(), timeout_ms, retries, &envelope)
-        }
-        None => Err(NotifallError::Provider(ProviderError::Message(
-            "remote host is not configured".to_string(),
-        ))),
+    let label = match name.as_deref() {
+        Some(name) => format!("{name} ({})", args.pid),
+        None => args.pid.to_string(),
     };
-    if send_result.is_ok() {
-        if args.json {
-            print_send_output("remote", None, false, None)?;
-        }
-        return Ok(());
-    }
-
-    if fallback && cfg!(target_os = "macos") {
-        let macos_config = resolve_macos_config(config, source_config, source);
-        return deliver_macos(
-            notification,
-            macos_config,
-            args.on_click.clone(),
-            args.background,
-            args.wait_for_click,
-            args.json,
-            context,
-        );
-    }
-
-    send_result
+    let title = args
+        .watch
+        .title
+        .clone()
+        .unwrap_or_else(|| "Process exited".to_string());
+    let message = format!("{label} exited after {}", format_duration(elapsed));
+    send_watch_notification(config_path, &args.watch, title, message);
+    Ok(())
 }
 
-fn handle_forward_send(
-    config: Option<&Config>,
-    args: &SendArgs,
-    notification: Notification,
-    remote_notification: Notification,
-    context: Option<Context>,
-    source_config: Option<&SourceConfig>,
-    source: Option<&str>,
+fn handle_watch_proc(
+    args: WatchProcArgs,
+    config_path: Option<&PathBuf>,
 ) -> Result<(), NotifallError> {
-    let targets = config
-        .and_then(|c| c.forward.as_ref())
-        .and_then(|f| f.targets.clone())
-        .unwrap_or_default();
+    let interval = Duration::from_millis(args.watch.interval_ms);
+    let title = args
+        .watch
+        .title
+        .clone()
+        .unwrap_or_else(|| "Process exited".to_string());
 
-    if targets.is_empty() {
-        return Err(NotifallError::Provider(ProviderError::Message(
-            "forward targets are not configured".to_string(),
+    let Some(mut elapsed) = watch::wait_for_proc_exit(&args.name, interval) else {
+        return Err(NotifallError::WatchTargetMissing(format!(
+            "no running process named {}",
+            args.name
         )));
+    };
+    loop {
+        let message = format!("{} exited after {}", args.name, format_duration(elapsed));
+        send_watch_notification(config_path, &args.watch, title.clone(), message);
+        if args.once {
+            return Ok(());
+        }
+        // Continuous mode: wait for the process to come back before watching again.
+        loop {
+            watch::wait_for_proc_start(&args.name, interval);
+            if let Some(next) = watch::wait_for_proc_exit(&args.name, interval) {
+                elapsed = next;
+                break;
+            }
+        }
     }
+}
 
-    #[derive(serde::Serialize)]
-    struct ForwardResult {
-        provider: String,
-        ok: bool,
-        error: Option<String>,
-    }
+fn handle_watch_codex(
+    args: WatchCodexArgs,
+    config_path: Option<&PathBuf>,
+) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let codex_cfg = config
+        .as_ref()
+        .and_then(|c| c.watch.as_ref())
+        .and_then(|w| w.codex.clone())
+        .unwrap_or_default();
 
-    let mut results = Vec::new();
-    let mut successes = 0usize;
+    let events = if !args.events.is_empty() {
+        args.events.iter().map(|e| map_codex_event(*e)).collect()
+    } else if let Some(names) = codex_cfg.events.as_ref() {
+        names
+            .iter()
+            .map(|name| {
+                CodexEventKind::from_name(name)
+                    .ok_or_else(|| NotifallError::UnknownCodexEvent(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        CodexEventKind::DEFAULTS.to_vec()
+    };
 
-    for target in targets {
-        let result = match target.as_str() {
-            "remote" => handle_remote_send(
-                config,
-                args,
-                notification.clone(),
-                remote_notification.clone(),
-                context.clone(),
-                source_config,
-                source,
-            )
-            .map_err(|e| e.to_string()),
-            "telegram" => match resolve_telegram_config(config, args) {
-                Ok(telegram_config) => match TelegramProvider::new(telegram_config) {
-                    Ok(provider) => provider
-                        .send(&notification, SendOptions { wait_for_click: false })
-                        .map(|_| ())
-                        .map_err(|e| e.to_string()),
-                    Err(err) => Err(err.to_string()),
-                },
-                Err(err) => Err(err.to_string()),
-            },
-            other => Err(format!("unknown forward target: {}", other)),
+    let notify = |line: &str| {
+        let Some(event) = codex::parse_line(line) else {
+            return;
         };
+        if !events.contains(&event.kind) {
+            return;
+        }
+        debug_log(&format!("watch codex event={}", event.kind.as_str()));
+        let send_args = SendArgs {
+            title: Some(event.title),
+            message: event.message,
+            urgency: Some(urgency_arg(event.kind.urgency())),
+            tag: Some(event.kind.as_str().to_string()),
+            source: Some("codex".to_string()),
+            provider: args.provider.clone(),
+            on_click: default_focus_command(),
+            background: true,
+            ..Default::default()
+        };
+        if let Err(err) = handle_send(config_path, send_args) {
+            eprintln!("ding: {err}");
+        }
+    };
 
-        match result {
-            Ok(()) => {
-                successes += 1;
-                results.push(ForwardResult {
-                    provider: target,
-                    ok: true,
-                    error: None,
-                });
-            }
-            Err(err) => {
-                results.push(ForwardResult {
-                    provider: target,
-                    ok: false,
-                    error: Some(err),
-                });
-            }
+    if args.stdin {
+        use std::io::BufRead;
+        for line in std::io::stdin().lock().lines() {
+            notify(&line?);
         }
+        return Ok(());
     }
 
-    if args.json {
-        let output = serde_json::json!({
-            "provider": "forward",
-            "results": results,
-        });
-        println!("{}", serde_json::to_string_pretty(&output)?);
+    let dir = args
+        .sessions_dir
+        .or(codex_cfg.sessions_dir)
+        .or_else(codex::default_sessions_dir)
+        .ok_or(NotifallError::MissingHome)?;
+    let interval = Duration::from_millis(args.interval_ms);
+    let mut watcher = codex::SessionWatcher::new(dir);
+    println!("watching codex sessions in {}", watcher.dir().display());
+
+    loop {
+        watcher.poll().iter().for_each(|line| notify(line));
+        std::thread::sleep(interval);
     }
+}
 
-    if successes == 0 {
-        return Err(NotifallError::Provider(ProviderError::Message(
-            "forwarding failed for all targets".to_string(),
-        )));
+fn map_codex_event(arg: CodexEventArg) -> CodexEventKind {
+    match arg {
+        CodexEventArg::Failed => CodexEventKind::Failed,
+        CodexEventArg::Approval => CodexEventKind::Approval,
+        CodexEventArg::FileChange => CodexEventKind::FileChange,
+        CodexEventArg::Plan => CodexEventKind::Plan,
+        CodexEventArg::Complete => CodexEventKind::Complete,
     }
+}
 
-    let failed = results.iter().filter(|r| !r.ok).count();
-    if failed > 0 && !args.json {
-        eprintln!("forwarding: {} target(s) failed", failed);
+fn send_watch_notification(
+    config_path: Option<&PathBuf>,
+    options: &WatchOptions,
+    title: String,
+    message: String,
+) {
+    let args = SendArgs {
+        title: Some(title),
+        message,
+        urgency: options.urgency,
+        tag: options.tag.clone(),
+        source: options.source.clone(),
+        provider: options.provider.clone(),
+        on_click: default_focus_command(),
+        background: true,
+        ..Default::default()
+    };
+    if let Err(err) = handle_send(config_path, args) {
+        eprintln!("ding: {err}");
     }
+}
 
-    Ok(())
+fn handle_remote(command: RemoteCmd, config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    match command {
+        RemoteCmd::Ping(args) => handle_remote_ping(args, config_path),
+    }
 }
 
-fn send_remote_request(
-    url: &str,
-    token: Option<&str>,
-    timeout_ms: u64,
-    retries: u32,
-    envelope: &RemoteEnvelope,
+fn handle_telegram(
+    command: TelegramCmd,
+    config_path: Option<&PathBuf>,
 ) -> Result<(), NotifallError> {
-    debug_log(&format!("remote_url={url} timeout_ms={timeout_ms} retries={retries}"));
-    let agent = ureq::AgentBuilder::new()
-        .timeout_connect(Duration::from_millis(timeout_ms))
-        .timeout_read(Duration::from_millis(timeout_ms))
-        .build();
-    let body = serde_json::to_value(envelope)?;
-    let mut last_err = None;
-
-    for _ in 0..=retries {
-        let mut request = agent.post(url).set("Content-Type", "application/json");
-        if let Some(token) = token {
-            request = request.set("Authorization", &format!("Bearer {token}"));
-        }
-        match request.send_json(body.clone()) {
-            Ok(response) => {
-                if response.status() >= 200 && response.status() < 300 {
-                    return Ok(());
-                }
-                last_err = Some(format!("remote error: status {}", response.status()));
-            }
-            Err(ureq::Error::Status(code, _)) => {
-                last_err = Some(format!("remote error: status {}", code));
-            }
-            Err(err) => {
-                last_err = Some(format!("remote error: {err}"));
-            }
-        }
+    match command {
+        TelegramCmd::ChatId(args) => handle_telegram_chat_id(args, config_path),
+        TelegramCmd::Bot => handle_telegram_bot(config_path),
     }
-
-    Err(NotifallError::Provider(ProviderError::Message(
-        last_err.unwrap_or_else(|| "remote error".to_string()),
-    )))
 }
 
-fn to_ping_url(url: &str) -> String {
-    if url.ends_with("/notify") {
-        return url.trim_end_matches("/notify").to_string() + "/ping";
-    }
-    if url.ends_with('/') {
-        return format!("{url}ping");
+fn handle_telegram_bot(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let config = load_config(Some(&path))?;
+    let telegram_config = resolve_telegram_config(config.as_ref(), &TelegramOverrides::default());
+    let bot = Bot::start(telegram_provider(telegram_config)?, path)?;
+    if let Err(err) = bot.register_commands() {
+        eprintln!("telegram: could not register commands: {err}");
     }
-    format!("{url}/ping")
+    println!("ding telegram bot polling for button presses, replies, and commands");
+    bot.run(|err| eprintln!("telegram: {err}"))
 }
 
-fn resolve_remote_target(
-    cli_host: Option<&str>,
-    cli_port: Option<u16>,
-    cfg_host: Option<&str>,
-    cfg_port: Option<u16>,
-    cfg_url: Option<&str>,
-) -> Option<(String, String, u16)> {
-    if let Some(host) = cli_host {
-        let port = cli_port.or(cfg_port).unwrap_or(4280);
-        let url = format!("http://{host}:{port}/notify");
-        return Some((url, host.to_string(), port));
+fn handle_telegram_chat_id(
+    args: TelegramChatIdArgs,
+    config_path: Option<&PathBuf>,
+) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let config = load_config(Some(&path))?;
+    let mut telegram_config =
+        resolve_telegram_config(config.as_ref(), &TelegramOverrides::default());
+    if let Some(token) = args.token {
+        telegram_config.bot_token = Some(token);
+    }
+    let chat_ids = TelegramProvider::without_chat(telegram_config)?.recent_chats()?;
+    if chat_ids.is_empty() {
+        return Err(NotifallError::NoTelegramChat);
     }
 
-    if let Some(host) = cfg_host {
-        let port = cli_port.or(cfg_port).unwrap_or(4280);
-        let url = format!("http://{host}:{port}/notify");
-        return Some((url, host.to_string(), port));
+    for id in &chat_ids {
+        println!("{id}");
     }
 
-    if let Some(url) = cfg_url {
-        if let Some((host, port)) = parse_remote_url(url) {
-            let port = cli_port.unwrap_or(port);
-            let url = format!("http://{host}:{port}/notify");
-            return Some((url, host, port));
+    if args.apply {
+        let existing = fs::read_to_string(&path).unwrap_or_default();
+        let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
+        config_edit::set_key(
+            &mut doc,
+            "telegram.chat_id",
+            toml_edit::Value::from(chat_ids[0].to_string()),
+        )?;
+        let new_contents = doc.to_string();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        fs::write(&path, new_contents)?;
+        println!("set telegram.chat_id in {}", path.display());
     }
 
-    None
-}
-
-fn parse_remote_url(url: &str) -> Option<(String, u16)> {
-    let trimmed = url
-        .strip_prefix("http://")
-        .or_else(|| url.strip_prefix("https://"))
-        .unwrap_or(url);
-    let host_port = trimmed.split('/').next().unwrap_or(trimmed);
-    if let Some((host, port)) = host_port.rsplit_once(':') {
-        if let Ok(port) = port.parse::<u16>() {
-            return Some((host.to_string(), port));
-        }
-    }
-    None
+    Ok(())
 }
 
-fn default_focus_command() -> Option<String> {
-    let exe = std::env::current_exe().ok()?;
-    Some(format!("{} focus", exe.display()))
+fn handle_remote_ping(
+    args: RemotePingArgs,
+    config_path: Option<&PathBuf>,
+) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let overrides = RemoteOverrides {
+        host: args.remote_host,
+        port: args.remote_port,
+        token: args.remote_token,
+        ..Default::default()
+    };
+    RemoteClient::from_config(config.as_ref().and_then(|c| c.remote.as_ref()), &overrides)?
+        .with_timeout(Duration::from_millis(2000))
+        .ping()?;
+    println!("ok");
+    Ok(())
 }
 
-fn extract_token(headers: &[tiny_http::Header]) -> Option<String> {
-    for header in headers {
-        let name = header.field.as_str().to_string();
-        if name.eq_ignore_ascii_case("authorization") {
-            let value = header.value.as_str();
-            if let Some(token) = value.strip_prefix("Bearer ") {
-                return Some(token.to_string());
-            }
-        }
-        if name.eq_ignore_ascii_case("x-ding-token") {
-            return Some(header.value.as_str().to_string());
-        }
-    }
-    None
+fn handle_forward(command: ForwardCmd, config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    println!("{}", apply_forward_command(command, config_path)?);
+    Ok(())
 }
 
-fn json_response(status: u16, body: &str) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
-    let mut response = tiny_http::Response::from_string(body.to_string());
-    let header = tiny_http::Header::from_bytes("Content-Type", "application/json").ok();
-    if let Some(header) = header {
-        response.add_header(header);
-    }
-    response.with_status_code(status)
+/// Runs a forward subcommand and returns the status line to show.
+fn apply_forward_command(
+    command: ForwardCmd,
+    config_path: Option<&PathBuf>,
+) -> Result<String, NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let change = match command {
+        ForwardCmd::Status => {
+            let existing = fs::read_to_string(&path).unwrap_or_default();
+            let doc = toml_edit::DocumentMut::from_str(&existing)?;
+            return Ok(config_edit::forward_status(&doc));
+        }
+        ForwardCmd::Off => ForwardChange::Off,
+        ForwardCmd::Toggle => ForwardChange::Toggle,
+        ForwardCmd::On(args) => ForwardChange::On {
+            targets: args
+                .targets
+                .iter()
+                .map(|t| match t {
+                    ForwardTarget::Remote => "remote".to_string(),
+                    ForwardTarget::Telegram => "telegram".to_string(),
+                })
+                .collect(),
+            append: args.append,
+            host: args.host,
+            port: args.port,
+        },
+    };
+    config_edit::update_forward(&path, change)
 }
 
-fn handle_wait_macos(args: crate::cli::WaitMacosArgs) -> Result<(), NotifallError> {
-    let contents = fs::read_to_string(&args.payload)?;
-    let payload: WaitPayload = serde_json::from_str(&contents)?;
-    let provider = MacosProvider::new(payload.macos)?;
-    let report = provider.send(&payload.notification, SendOptions { wait_for_click: true })?;
-    handle_click(
-        report.outcome,
-        payload.on_click.as_deref(),
-        &payload.notification,
-        payload.context.as_ref(),
-    )?;
-    let _ = fs::remove_file(&args.payload);
+fn handle_dnd(command: DndCmd) -> Result<(), NotifallError> {
+    println!("{}", apply_dnd_command(command)?);
     Ok(())
 }
 
-fn load_config(path: Option<&PathBuf>) -> Result<Option<Config>, NotifallError> {
-    let path = path.cloned().unwrap_or_else(default_config_path);
-    if !path.exists() {
-        return Ok(None);
-    }
-    let contents = fs::read_to_string(&path)?;
-    let config: Config = toml::from_str(&contents)?;
-    Ok(Some(config))
-}
-
-fn resolve_source_config<'a>(
-    config: Option<&'a Config>,
-    source: Option<&str>,
-) -> Option<&'a SourceConfig> {
-    let source = source?;
-    if let Some(cfg) = config
-        .and_then(|c| c.sources.as_ref())
-        .and_then(|sources| sources.get(source))
-    {
-        return Some(cfg);
+/// Runs a dnd subcommand and returns the status line to show.
+fn apply_dnd_command(command: DndCmd) -> Result<String, NotifallError> {
+    let store = DndStore::default_location();
+    match command {
+        DndCmd::On(args) => {
+            store.enable(parse_duration(&args.duration)?)?;
+        }
+        DndCmd::Off => store.disable()?,
+        DndCmd::Status => {}
     }
-    None
+    Ok(store.status())
 }
 
-fn resolve_title(
-    cli_title: Option<String>,
-    source_config: Option<&SourceConfig>,
-    source: Option<&str>,
-) -> String {
-    if let Some(title) = cli_title {
-        return title;
+fn handle_progress(
+    config_path: Option<&PathBuf>,
+    command: ProgressCmd,
+) -> Result<(), NotifallError> {
+    let config = load_config(config_path)?;
+    let store = ProgressStore::new(default_state_dir().join("progress"));
+    let (args, step, notify_on_finish) = match command {
+        ProgressCmd::Start(args) => {
+            prune_dir(store.dir(), Duration::from_secs(7 * 24 * 60 * 60));
+            (args, ProgressStep::Start, None)
+        }
+        ProgressCmd::Update(args) => (args, ProgressStep::Update, None),
+        ProgressCmd::Finish(finish) => (finish.progress, ProgressStep::Finish, Some(finish.notify)),
+    };
+    // Held until the new state is saved, so a concurrent update waits for
+    // the message this one sends instead of sending its own.
+    let _lock = store.lock(&args.key)?;
+    let existing = match step {
+        ProgressStep::Start => None,
+        _ => store.load(&args.key),
+    };
+
+    let mut state = match existing {
+        Some(state) => state,
+        None => ProgressState {
+            title: args.key.clone(),
+            provider: resolve_provider(args.provider.as_deref(), config.as_ref())?,
+            source: None,
+            tag: None,
+            started: history::now_secs(),
+            targets: Vec::new(),
+        },
+    };
+    if let Some(title) = args.title.clone() {
+        state.title = title;
     }
-    if let Some(display) = source_config.and_then(|cfg| cfg.display_name.as_ref()) {
-        return display.clone();
+    if args.source.is_some() {
+        state.source = args.source.clone();
     }
-    if let Some(source) = source {
-        return title_from_source(source);
+    if args.tag.is_some() {
+        state.tag = args.tag.clone();
     }
-    "Ding".to_string()
-}
 
-fn title_from_source(source: &str) -> String {
-    let mut chars = source.chars();
-    match chars.next() {
-        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
-        None => "Ding".to_string(),
+    let first = state.targets.is_empty();
+    let task = ProgressTask {
+        key: args.key.clone(),
+        // An update or finish with nothing to update shows the task anew.
+        step: if first { ProgressStep::Start } else { step },
+    };
+    let notification = Notification {
+        title: state.title.clone(),
+        message: progress::progress_text(&args.message, args.percent),
+        source: state.source.clone(),
+        tag: state.tag.clone(),
+        // Only the first message makes a sound; updates replace it quietly.
+        sound: (!first).then(|| "none".to_string()),
+        ..Default::default()
+    };
+    let targets = if first {
+        expand_provider(config.as_ref(), &state.provider)?
+            .into_iter()
+            .map(|provider| ProgressTarget {
+                provider,
+                message: None,
+            })
+            .collect()
+    } else {
+        std::mem::take(&mut state.targets)
+    };
+
+    let registry = ProviderRegistry::builtin();
+    let cx = SendContext {
+        config: config.as_ref(),
+        overrides: &SendOverrides::default(),
+        origin: &RemoteContext::from_local(detect_context()),
+        interaction: &Interaction::default(),
+        registry: &registry,
+    };
+    let total = targets.len();
+    let mut failed = 0;
+    let mut last_err = None;
+    for target in targets {
+        match progress_target(&cx, &task, &target, &notification) {
+            Ok(updated) => state.targets.push(updated),
+            Err(err) => {
+                debug_log(&format!("progress via {} failed: {err}", target.provider));
+                // Keep the target so the next update tries it again.
+                state.targets.push(target);
+                failed += 1;
+                last_err = Some(err);
+            }
+        }
+    }
+    if let Some(err) = last_err
+        && failed == total
+    {
+        return Err(err);
+    }
+
+    match notify_on_finish {
+        None => store.save(&args.key, &state)?,
+        Some(notify) => {
+            store.remove(&args.key)?;
+            if notify {
+                handle_send(
+                    config_path,
+                    SendArgs {
+                        title: Some(state.title),
+                        message: notification.message,
+                        source: state.source,
+                        tag: state.tag,
+                        provider: Some(state.provider),
+                        ..Default::default()
+                    },
+                )?;
+            }
+        }
     }
+    Ok(())
 }
 
-fn resolve_icon(
-    cli_icon: Option<PathBuf>,
-    source_config: Option<&SourceConfig>,
-    source: Option<&str>,
-) -> Option<PathBuf> {
-    if cli_icon.is_some() {
-        return cli_icon;
-    }
-    source_config.and_then(|cfg| cfg.icon.clone())
-        .or_else(|| default_source_icon(source))
+/// Shows `notification` as the latest state of a progress target, handing
+/// the provider what it returned for the target last time.
+fn progress_target(
+    cx: &SendContext,
+    task: &ProgressTask,
+    target: &ProgressTarget,
+    notification: &Notification,
+) -> Result<ProgressTarget, NotifallError> {
+    let provider = cx
+        .registry
+        .get(&target.provider)
+        .ok_or_else(|| NotifallError::ProviderUnsupported(target.provider.clone()))?;
+    let message = provider.progress(cx, task, notification.clone(), target.message.as_ref())?;
+    Ok(ProgressTarget {
+        provider: target.provider.clone(),
+        message,
+    })
 }
 
-fn resolve_macos_config(
-    config: Option<&Config>,
-    source_config: Option<&SourceConfig>,
-    source: Option<&str>,
-) -> Option<MacosConfig> {
-    let mut macos = config.and_then(|c| c.macos.clone());
-    if let Some(source_cfg) = source_config {
-        if source_cfg.app_bundle_id.is_some() {
-            let entry = macos.get_or_insert_with(MacosConfig::default);
-            entry.app_bundle_id = source_cfg.app_bundle_id.clone();
-        }
-    }
-    if macos.as_ref().and_then(|m| m.app_bundle_id.as_ref()).is_none() {
-        if let Some(bundle_id) = default_source_bundle_id(source) {
-            let entry = macos.get_or_insert_with(MacosConfig::default);
-            entry.app_bundle_id = Some(bundle_id);
-        }
+fn handle_config_set(
+    config_path: Option<&PathBuf>,
+    args: ConfigSetArgs,
+) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
+    let edit = if args.append {
+        ListEdit::Append
+    } else if args.remove {
+        ListEdit::Remove
+    } else {
+        ListEdit::Replace
+    };
+    config_edit::set_value(&mut doc, &args.key, &args.value, edit)?;
+    let new_contents = doc.to_string();
+    check_config_change(&path, &new_contents, &args.key)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
-    macos
+    fs::write(&path, new_contents)?;
+    println!("set {} in {}", args.key, path.display());
+    Ok(())
 }
 
-fn resolve_telegram_config(
-    config: Option<&Config>,
-    args: &SendArgs,
-) -> Result<TelegramConfig, NotifallError> {
-    let mut telegram = config
-        .and_then(|c| c.telegram.clone())
-        .unwrap_or_default();
-
-    if let Some(token) = args.telegram_token.as_ref() {
-        telegram.bot_token = Some(token.clone());
-    }
-    if let Some(chat_id) = args.telegram_chat_id.as_ref() {
-        telegram.chat_id = Some(chat_id.clone());
-    }
-    if let Some(parse_mode) = args.telegram_parse_mode.as_ref() {
-        telegram.parse_mode = Some(parse_mode.clone());
-    }
-    if args.telegram_silent {
-        telegram.silent = Some(true);
+fn handle_config_get(
+    config_path: Option<&PathBuf>,
+    args: ConfigKeyArgs,
+) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let doc = toml_edit::DocumentMut::from_str(&existing)?;
+    let item = config_edit::get_key(&doc, &args.key).filter(|item| !item.is_none());
+    let Some(item) = item else {
+        return Err(config_edit::missing_key(&args.key));
+    };
+    match item.as_value() {
+        Some(value) => match value.as_str() {
+            Some(text) => println!("{text}"),
+            None => {
+                let mut value = value.clone();
+                value.decor_mut().clear();
+                println!("{value}");
+            }
+        },
+        None => print!("{item}"),
     }
-
-    Ok(telegram)
-}
-
-fn default_source_icon(source: Option<&str>) -> Option<PathBuf> {
-    let _ = source?;
-    None
+    Ok(())
 }
 
-fn default_source_bundle_id(source: Option<&str>) -> Option<String> {
-    let source = source?;
-    if source == "claude" {
-        return ensure_source_bundle(
-            "claude",
-            "Ding Claude",
-            "com.ding.claude",
-            include_bytes!(
-                "../assets/brands/anthropic/claude/icons/claude-symbol-clay.icns"
-            ),
-        );
-    }
-    if source == "codex" {
-        return ensure_source_bundle(
-            "codex",
-            "Ding Codex",
-            "com.ding.codex",
-            include_bytes!("../assets/brands/codex/icons/openai-blossom-light.icns"),
-        );
+fn handle_config_unset(
+    config_path: Option<&PathBuf>,
+    args: ConfigKeyArgs,
+) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let mut doc = toml_edit::DocumentMut::from_str(&existing)?;
+    if config_edit::remove_key(&mut doc, &args.key).is_none() {
+        return Err(config_edit::missing_key(&args.key));
     }
-    None
+    let new_contents = doc.to_string();
+    check_config_change(&path, &new_contents, &args.key)?;
+    fs::write(&path, new_contents)?;
+    println!("unset {} in {}", args.key, path.display());
+    Ok(())
 }
 
-fn ensure_source_bundle(
-    source: &str,
-    display_name: &str,
-    bundle_id: &str,
-    icon_bytes: &[u8],
-) -> Option<String> {
-    let base_dir = std::env::var("XDG_CACHE_HOME")
-        .map(PathBuf::from)
-        .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".cache")))
-        .unwrap_or_else(|_| std::env::temp_dir());
-    let app_dir = base_dir.join("ding/apps").join(format!("{}.app", source));
-    let contents = app_dir.join("Contents");
-    let macos = contents.join("MacOS");
-    let resources = contents.join("Resources");
-
-    if fs::create_dir_all(&macos).is_err() || fs::create_dir_all(&resources).is_err() {
-        return None;
-    }
-
-    let icon_name = format!("{}.icns", source);
-    let icon_path = resources.join(&icon_name);
-    let icon_changed = match write_if_changed(&icon_path, icon_bytes) {
-        Ok(changed) => changed,
-        Err(_) => return None,
-    };
+/// Edits a copy of the config in `$VISUAL` or `$EDITOR` and saves it only
+/// once it validates, offering to re-open the editor until it does.
+fn handle_config_edit(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    let original = fs::read_to_string(&path).ok();
+    let draft =
+        config_edit::create_draft(&path, original.as_deref().unwrap_or(Config::template()))?;
 
-    let plist_path = contents.join("Info.plist");
-    if !plist_path.exists() || icon_changed {
-        let icon_version = icon_bytes
-            .iter()
-            .fold(0u32, |acc, byte| acc.wrapping_add(*byte as u32));
-        let plist = format!(
-            r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-<dict>
-  <key>CFBundleName</key>
-  <string>{}</string>
-  <key>CFBundleIdentifier</key>
-  <string>{}</string>
-  <key>CFBundleVersion</key>
-  <string>{}</string>
-  <key>CFBundleShortVersionString</key>
-  <string>{}</string>
-  <key>CFBundleExecutable</key>
-  <string>ding-helper</string>
-  <key>CFBundleIconFile</key>
-  <string>{}</string>
-  <key>LSUIElement</key>
-  <true/>
-</dict>
-</plist>
-"#,
-            display_name, bundle_id, icon_version, icon_version, icon_name
-        );
-        if fs::write(&plist_path, plist).is_err() {
-            return None;
-        }
-    }
+    let edited = edit_until_valid(&draft, &path);
+    let _ = fs::remove_file(&draft);
 
-    let exec_path = macos.join("ding-helper");
-    if !exec_path.exists() {
-        let script = b"#!/bin/sh\nexit 0\n";
-        if fs::write(&exec_path, script).is_err() {
-            return None;
+    match edited? {
+        Some(edited) if original.as_deref() == Some(edited.as_str()) => {
+            println!("no changes to {}", path.display());
         }
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Ok(mut perms) = fs::metadata(&exec_path).map(|m| m.permissions()) {
-                perms.set_mode(0o755);
-                let _ = fs::set_permissions(&exec_path, perms);
+        Some(edited) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
             }
+            fs::write(&path, edited)?;
+            println!("saved {}", path.display());
+        }
+        None => {
+            eprintln!("discarded changes; {} is unchanged", path.display());
+            std::process::exit(1);
         }
     }
-
-    let lsregister = "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
-    let _ = Command::new(lsregister).arg("-f").arg(&app_dir).status();
-
-    Some(bundle_id.to_string())
-}
-
-fn allow_image_icons() -> bool {
-    std::env::var("DING_ALLOW_IMAGE_ICONS").map(|v| v == "1").unwrap_or(false)
+    Ok(())
 }
 
-fn write_if_changed(path: &PathBuf, contents: &[u8]) -> Result<bool, std::io::Error> {
-    if let Ok(existing) = fs::read(path) {
-        if existing == contents {
-            return Ok(false);
+/// Opens `draft` in the editor until it validates, or the user gives up and
+/// `None` is returned. Problems are reported against the config at `path`.
+fn edit_until_valid(draft: &Path, path: &Path) -> Result<Option<String>, NotifallError> {
+    let registry = ProviderRegistry::builtin();
+    loop {
+        run_editor(draft)?;
+        let edited = fs::read_to_string(draft)?;
+        let diagnostics = schema::validate(&edited, &registry);
+        if diagnostics.is_empty() {
+            return Ok(Some(edited));
+        }
+        for diagnostic in &diagnostics {
+            eprintln!("{}:{diagnostic}", path.display());
+        }
+        if !confirm("Edit again?") {
+            return Ok(None);
         }
     }
-    fs::write(path, contents)?;
-    Ok(true)
 }
 
-fn default_config_path() -> PathBuf {
-    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
-        return PathBuf::from(dir).join("ding/config.toml");
-    }
-    if let Ok(home) = std::env::var("HOME") {
-        return PathBuf::from(home).join(".config/ding/config.toml");
+fn run_editor(path: &Path) -> Result<(), NotifallError> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // Through sh so editors with arguments, like "code --wait", work.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(NotifallError::Io(std::io::Error::other(format!(
+            "{editor} exited with {status}"
+        ))));
     }
-    PathBuf::from("ding.toml")
+    Ok(())
 }
 
-fn resolve_provider(
-    cli_provider: Option<&str>,
-    config: Option<&Config>,
-) -> Result<String, NotifallError> {
-    if let Some(provider) = cli_provider {
-        return Ok(provider.to_lowercase());
+/// Asks a yes/no question on stderr; yes unless answered "n". No input at all
+/// counts as no.
+fn confirm(question: &str) -> bool {
+    eprint!("{question} [Y/n] ");
+    let _ = std::io::Write::flush(&mut std::io::stderr());
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => !answer.trim().to_lowercase().starts_with('n'),
     }
-    if let Some(enabled) = config
-        .and_then(|c| c.forward.as_ref())
-        .and_then(|f| f.enabled)
-    {
-        if enabled {
-            return Ok("forward".to_string());
-        }
+}
+
+fn handle_config_validate(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    if !path.exists() {
+        println!("(no config file at {})", path.display());
+        return Ok(());
     }
-    if let Some(default_provider) = config.and_then(|c| c.default_provider.as_ref()) {
-        return Ok(default_provider.to_lowercase());
+    let contents = fs::read_to_string(&path)?;
+    let diagnostics = schema::validate(&contents, &ProviderRegistry::builtin());
+    if diagnostics.is_empty() {
+        println!("{}: ok", path.display());
+        return Ok(());
     }
-    if cfg!(target_os = "macos") {
-        return Ok("macos".to_string());
+    for diagnostic in &diagnostics {
+        println!("{}:{diagnostic}", path.display());
     }
-    Err(NotifallError::NoProviderAvailable)
+    std::process::exit(1);
 }
 
-fn map_urgency(arg: UrgencyArg) -> Urgency {
-    match arg {
-        UrgencyArg::Low => Urgency::Low,
-        UrgencyArg::Normal => Urgency::Normal,
-        UrgencyArg::High => Urgency::High,
+/// Validates `contents` before it replaces the config at `path`. Problems with
+/// `key` refuse the change; problems elsewhere were already there, so they
+/// are only reported.
+fn check_config_change(path: &Path, contents: &str, key: &str) -> Result<(), NotifallError> {
+    let other = config_edit::check_change(contents, key, &ProviderRegistry::builtin())?;
+    for diagnostic in &other {
+        eprintln!("warning: {}:{diagnostic}", path.display());
     }
+    Ok(())
 }
 
-fn handle_click(
-    outcome: Option<DeliveryOutcome>,
-    on_click: Option<&str>,
-    notification: &Notification,
-    context: Option<&Context>,
-) -> Result<(), NotifallError> {
-    let cmd = match (outcome, on_click) {
-        (Some(DeliveryOutcome::Clicked), Some(cmd)) => cmd,
-        (Some(DeliveryOutcome::ActionButton(_)), Some(cmd)) => cmd,
-        _ => return Ok(()),
-    };
+fn handle_config_path(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    println!("{}", path.display());
+    Ok(())
+}
 
-    let mut child = Command::new("sh");
-    child.arg("-c").arg(cmd);
-    if let Some(source) = notification.source.as_deref() {
-        child.env("DING_SOURCE", source);
-    }
-    child.env("DING_TITLE", &notification.title);
-    child.env("DING_MESSAGE", &notification.message);
-    if let Some(tag) = notification.tag.as_deref() {
-        child.env("DING_TAG", tag);
-    }
-    if let Some(context) = context {
-        if let Some(tmux) = context.tmux.as_ref() {
-            child.env("DING_TMUX_SESSION", &tmux.session);
-            child.env("DING_TMUX_WINDOW", &tmux.window);
-            child.env("DING_TMUX_PANE", &tmux.pane);
-            if let Some(client) = tmux.client.as_deref() {
-                child.env("DING_TMUX_CLIENT", client);
-            }
-        }
-        if let Some(terminal) = context.terminal.as_ref().and_then(|t| t.app.as_deref()) {
-            child.env("DING_TERMINAL_APP", terminal);
-        }
-        if let Ok(json) = serde_json::to_string(context) {
-            child.env("DING_CONTEXT_JSON", json);
-        }
+fn handle_config_list(config_path: Option<&PathBuf>) -> Result<(), NotifallError> {
+    let path = config_path.cloned().unwrap_or_else(default_config_path);
+    if !path.exists() {
+        println!("(no config file at {})", path.display());
+        return Ok(());
     }
-
-    child.spawn()?;
+    let contents = fs::read_to_string(&path)?;
+    print!("{contents}");
     Ok(())
 }
 
-fn spawn_background_wait(payload: WaitPayload) -> Result<PathBuf, NotifallError> {
-    let payload_path = write_payload(payload)?;
-    let exe = std::env::current_exe()?;
-    let mut cmd = Command::new(exe);
-    cmd.arg("wait-macos").arg("--payload").arg(&payload_path);
-    cmd.stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
-    #[cfg(unix)]
-    {
-        use std::os::unix::process::CommandExt;
-        unsafe {
-            cmd.pre_exec(|| {
-                libc::setsid();
-                Ok(())
-            });
-        }
+fn handle_wait_macos(args: crate::cli::WaitMacosArgs) -> Result<(), NotifallError> {
+    let contents = fs::read_to_string(&args.payload)?;
+    let payload: WaitPayload = serde_json::from_str(&contents)?;
+    let provider = MacosProvider::new(payload.macos)?;
+    let report = provider.send(
+        &payload.notification,
+        SendOptions {
+            wait_for_click: true,
+        },
+    )?;
+    // The asking process reads the answer and removes the payload.
+    if payload.ask {
+        println!("{}", serde_json::to_string(&report.outcome)?);
+        return Ok(());
     }
-    cmd.spawn()?;
-    Ok(payload_path)
+    handle_click(
+        report.outcome,
+        payload.on_click.as_deref(),
+        &payload.notification,
+        payload.context.as_ref(),
+    )?;
+    let _ = fs::remove_file(&args.payload);
+    Ok(())
 }
 
-fn write_payload(payload: WaitPayload) -> Result<PathBuf, NotifallError> {
-    let ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-    let file_name = format!("ding-payload-{}-{}.json", std::process::id(), ts);
-    let path = std::env::temp_dir().join(file_name);
-    let data = serde_json::to_vec(&payload)?;
-    fs::write(&path, data)?;
-    Ok(path)
+fn load_config(path: Option<&PathBuf>) -> Result<Option<Config>, NotifallError> {
+    Config::load(path.map(PathBuf::as_path))
 }
 
-fn activate_terminal(terminal: Option<&str>) {
-    if !cfg!(target_os = "macos") {
-        return;
+fn map_urgency(arg: UrgencyArg) -> Urgency {
+    match arg {
+        UrgencyArg::Low => Urgency::Low,
+        UrgencyArg::Normal => Urgency::Normal,
+        UrgencyArg::High => Urgency::High,
     }
+}
 
-    let app = match terminal {
-        Some(name) if name.eq_ignore_ascii_case("ghostty") => Some("Ghostty"),
-        Some(name) if name.eq_ignore_ascii_case("iterm") => Some("iTerm"),
-        Some(name) if name.eq_ignore_ascii_case("iterm.app") => Some("iTerm"),
-        Some(name) if name.eq_ignore_ascii_case("terminal") => Some("Terminal"),
-        Some(name) if name.eq_ignore_ascii_case("apple_terminal") => Some("Terminal"),
-        Some(name) if name.eq_ignore_ascii_case("apple_terminal.app") => Some("Terminal"),
-        Some(name) if name.eq_ignore_ascii_case("Apple_Terminal") => Some("Terminal"),
-        Some(name) if name.eq_ignore_ascii_case("Apple_Terminal.app") => Some("Terminal"),
-        _ => None,
-    };
-
-    if let Some(app) = app {
-        let _ = Command::new("osascript")
-            .args(["-e", &format!("tell application \"{}\" to activate", app)])
-            .status();
+fn urgency_arg(urgency: Urgency) -> UrgencyArg {
+    match urgency {
+        Urgency::Low => UrgencyArg::Low,
+        Urgency::Normal => UrgencyArg::Normal,
+        Urgency::High => UrgencyArg::High,
     }
 }
 
@@ -1644,23 +2048,10 @@ fn print_send_output(
         payload: Option<String>,
     }
 
-    let outcome_value = match outcome {
-        None => serde_json::Value::Null,
-        Some(DeliveryOutcome::Delivered) => serde_json::Value::String("delivered".to_string()),
-        Some(DeliveryOutcome::Clicked) => serde_json::Value::String("clicked".to_string()),
-        Some(DeliveryOutcome::ActionButton(label)) => serde_json::json!({
-            "type": "action",
-            "label": label,
-        }),
-        Some(DeliveryOutcome::Closed(label)) => serde_json::json!({
-            "type": "closed",
-            "label": label,
-        }),
-        Some(DeliveryOutcome::Replied(text)) => serde_json::json!({
-            "type": "reply",
-            "text": text,
-        }),
-    };
+    let outcome_value = outcome
+        .as_ref()
+        .map(DeliveryOutcome::to_json)
+        .unwrap_or(serde_json::Value::Null);
 
     let output = SendOutput {
         provider,
@@ -1672,100 +2063,132 @@ fn print_send_output(
     Ok(())
 }
 
-fn install_claude(apply: bool) -> Result<(), NotifallError> {
-    let home = home_dir()?;
-    let claude_dir = home.join(".claude");
-    let settings_path = claude_dir.join("settings.json");
-
-    let settings = fs::read_to_string(&settings_path).unwrap_or_else(|_| "{}".to_string());
-    let mut json: serde_json::Value = serde_json::from_str(&settings)?;
+/// The `--json` counterpart of a failed send, so scripts can tell a bad
+/// config from a flaky network without parsing the message.
+fn print_send_error(provider: &str, err: &NotifallError) -> Result<(), NotifallError> {
+    let output = serde_json::json!({
+        "provider": provider,
+        "ok": false,
+        "error": err.to_string(),
+        "kind": err.kind(),
+        "retryable": err.retryable(),
+    });
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
 
+fn install_status(target: Option<InstallTarget>, scope: InstallScope) -> Result<(), NotifallError> {
     let exe = std::env::current_exe()?;
-    let command = format!("{} hook claude", exe.display());
-    let hook_entry = serde_json::json!([{
-        "matcher": "",
-        "hooks": [{ "type": "command", "command": command }]
-    }]);
-
-    json["hooks"]["Notification"] = hook_entry.clone();
-    json["hooks"]["Stop"] = hook_entry.clone();
-
-    let new_contents = serde_json::to_string_pretty(&json)?;
-    if !apply {
-        print_diff(
-            &settings_path,
-            &settings,
-            &new_contents,
-            "ding install claude --apply",
-        )?;
-        return Ok(());
-    }
+    let agents = match target {
+        Some(target) => vec![install_agent(target)],
+        None => install::Agent::ALL.to_vec(),
+    };
 
-    if settings_path.exists() {
-        backup_file(&settings_path)?;
+    for agent in agents {
+        let name = agent.name();
+        let path = match install::config_path(agent, install_scope(scope)) {
+            Ok(path) => path,
+            Err(NotifallError::Install(reason)) => {
+                println!("{name}: {reason}");
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let statuses = install::statuses(agent, &path, &exe)?;
+        print_install_status(name, &path, statuses);
     }
-    fs::write(&settings_path, new_contents)?;
-
-    println!("Installed Claude hooks in {}", settings_path.display());
     Ok(())
 }
 
-fn install_codex(apply: bool) -> Result<(), NotifallError> {
-    let home = home_dir()?;
-    let codex_dir = home.join(".codex");
-    let config_path = codex_dir.join("config.toml");
-
-    let config = fs::read_to_string(&config_path).unwrap_or_default();
-    let mut doc = toml_edit::DocumentMut::from_str(&config)?;
-    let exe = std::env::current_exe()?;
-    let mut notify = toml_edit::Array::default();
-    notify.push(exe.display().to_string());
-    notify.push("hook");
-    notify.push("codex");
-    doc["notify"] = toml_edit::value(notify);
+fn print_install_status(name: &str, path: &Path, statuses: Option<Vec<install::HookStatus>>) {
+    let Some(statuses) = statuses else {
+        println!("{name}: {} (not found)", path.display());
+        return;
+    };
+    if statuses.is_empty() {
+        println!("{name}: {} (not installed)", path.display());
+        return;
+    }
+    println!("{name}: {}", path.display());
+    for status in statuses {
+        println!(
+            "  {:<22} {:<8} {}",
+            status.event,
+            status.state.as_str(),
+            status.binary.display()
+        );
+    }
+}
 
-    let new_contents = doc.to_string();
+/// Shows the diff (dry-run) or applies the change with a backup and reports
+/// what changed.
+fn apply_integration_plan(plan: install::Plan, apply: bool) -> Result<(), NotifallError> {
+    let change = match plan {
+        install::Plan::Unchanged(reason) => {
+            println!("{reason}");
+            return Ok(());
+        }
+        install::Plan::Change(change) => change,
+    };
     if !apply {
-        print_diff(
-            &config_path,
-            &config,
-            &new_contents,
-            "ding install codex --apply",
-        )?;
-        return Ok(());
+        let Some(new) = change.new.as_deref() else {
+            println!(
+                "Would remove {}. Re-run `{}` with --apply to remove it.",
+                change.path.display(),
+                rerun_command()
+            );
+            return Ok(());
+        };
+        return print_diff(&change.path, &change.old, new, &rerun_command());
     }
-
-    if config_path.exists() {
-        backup_file(&config_path)?;
+    change.apply()?;
+    println!("{} {}", change.done, change.path.display());
+    for line in &change.changes {
+        println!("  {line}");
     }
-    fs::write(&config_path, new_contents)?;
-
-    println!("Installed Codex notify in {}", config_path.display());
     Ok(())
 }
 
-fn home_dir() -> Result<PathBuf, NotifallError> {
-    std::env::var("HOME")
-        .map(PathBuf::from)
-        .map_err(|_| NotifallError::MissingHome)
+/// The current invocation, for the "re-run with --apply" hint.
+fn rerun_command() -> String {
+    std::iter::once("ding".to_string())
+        .chain(std::env::args().skip(1))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-fn backup_file(path: &PathBuf) -> Result<(), NotifallError> {
-    let ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let backup = path.with_extension(format!("bak-{}", ts));
-    fs::copy(path, &backup)?;
-    Ok(())
+fn claude_event_name(event: ClaudeEventArg) -> &'static str {
+    match event {
+        ClaudeEventArg::Notification => "Notification",
+        ClaudeEventArg::Stop => "Stop",
+        ClaudeEventArg::SubagentStop => "SubagentStop",
+        ClaudeEventArg::PreToolUse => "PreToolUse",
+        ClaudeEventArg::PostToolUse => "PostToolUse",
+        ClaudeEventArg::SessionStart => "SessionStart",
+        ClaudeEventArg::SessionEnd => "SessionEnd",
+        ClaudeEventArg::UserPromptSubmit => "UserPromptSubmit",
+        ClaudeEventArg::PreCompact => "PreCompact",
+    }
 }
 
-fn print_diff(
-    path: &PathBuf,
-    old: &str,
-    new: &str,
-    apply_command: &str,
-) -> Result<(), NotifallError> {
+fn install_agent(target: InstallTarget) -> install::Agent {
+    match target {
+        InstallTarget::Claude => install::Agent::Claude,
+        InstallTarget::Codex => install::Agent::Codex,
+        InstallTarget::Gemini => install::Agent::Gemini,
+        InstallTarget::Aider => install::Agent::Aider,
+        InstallTarget::Opencode => install::Agent::Opencode,
+    }
+}
+
+fn install_scope(scope: InstallScope) -> install::Scope {
+    match scope {
+        InstallScope::User => install::Scope::User,
+        InstallScope::Project => install::Scope::Project,
+    }
+}
+
+fn print_diff(path: &Path, old: &str, new: &str, apply_command: &str) -> Result<(), NotifallError> {
     let temp_dir = std::env::temp_dir();
     let old_path = temp_dir.join(format!(
         "ding-old-{}-{}",
@@ -1781,10 +2204,7 @@ fn print_diff(
     fs::write(&old_path, old)?;
     fs::write(&new_path, new)?;
 
-    let output = diff_output(
-        old_path.to_str().unwrap(),
-        new_path.to_str().unwrap(),
-    );
+    let output = diff_output(old_path.to_str().unwrap(), new_path.to_str().unwrap());
 
     let _ = fs::remove_file(&old_path);
     let _ = fs::remove_file(&new_path);
@@ -1836,15 +2256,15 @@ fn print_diff(
 }
 
 fn spawn_pager() -> Option<std::process::Child> {
-    if let Ok(pager) = std::env::var("PAGER") {
-        if !pager.trim().is_empty() {
-            return Command::new("sh")
-                .arg("-c")
-                .arg(&pager)
-                .stdin(Stdio::piped())
-                .spawn()
-                .ok();
-        }
+    if let Ok(pager) = std::env::var("PAGER")
+        && !pager.trim().is_empty()
+    {
+        return Command::new("sh")
+            .arg("-c")
+            .arg(&pager)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok();
     }
 
     for candidate in ["less", "more"] {
@@ -1946,10 +2366,10 @@ fn colorize_inline_code(text: &str) -> String {
 }
 
 fn pager_available() -> bool {
-    if let Ok(pager) = std::env::var("PAGER") {
-        if !pager.trim().is_empty() {
-            return true;
-        }
+    if let Ok(pager) = std::env::var("PAGER")
+        && !pager.trim().is_empty()
+    {
+        return true;
     }
     command_exists("less") || command_exists("more")
 }
@@ -1966,7 +2386,7 @@ fn command_exists(name: &str) -> bool {
 fn stdout_is_tty() -> bool {
     #[cfg(unix)]
     unsafe {
-        return libc::isatty(libc::STDOUT_FILENO) == 1;
+        libc::isatty(libc::STDOUT_FILENO) == 1
     }
     #[cfg(not(unix))]
     {
@@ -1974,168 +2394,287 @@ fn stdout_is_tty() -> bool {
     }
 }
 
-fn read_hook_payload(json: Option<&str>) -> Result<serde_json::Value, NotifallError> {
-    if let Some(raw) = json {
-        return Ok(serde_json::from_str(raw)?);
-    }
-    let mut stdin = std::io::stdin();
-    let mut buf = Vec::new();
-    use std::io::Read;
-    stdin.read_to_end(&mut buf)?;
-    if buf.is_empty() {
-        return Ok(serde_json::Value::Null);
-    }
-    Ok(serde_json::from_slice(&buf)?)
-}
-
-fn handle_claude_hook(payload: serde_json::Value) -> Result<(), NotifallError> {
-    let hook = payload
-        .get("hook_event_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or("Unknown");
-    let mut message = payload
-        .get("message")
-        .and_then(|v| v.as_str())
-        .or_else(|| payload.get("prompt").and_then(|v| v.as_str()))
-        .unwrap_or("")
-        .to_string();
-
-    let title = if hook == "Notification" {
-        let ntype = payload
-            .get("notification_type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("notification");
-        format!("Claude Code: {}", ntype)
-    } else if hook == "Stop" || hook == "SubagentStop" {
-        if message.is_empty() {
-            message = "Task completed".to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ding::provider::registry::Capabilities;
+
+    fn envelope(actions: Vec<Action>) -> RemoteEnvelope {
+        RemoteEnvelope {
+            notification: Notification {
+                actions,
+                ..Notification::new("Deploy", "done")
+            },
+            context: None,
+            timeout_ms: None,
+            progress: None,
         }
-        "Claude Code: finished".to_string()
-    } else {
-        format!("Claude Code: {}", hook)
-    };
+    }
 
-    if message.is_empty() {
-        if let Some(tool) = payload.get("tool_name").and_then(|v| v.as_str()) {
-            message = tool.to_string();
-        } else {
-            message = " ".to_string();
+    fn action(label: &str, url: Option<&str>, command: Option<&str>) -> Action {
+        Action {
+            label: label.to_string(),
+            url: url.map(str::to_string),
+            command: command.map(str::to_string),
         }
     }
 
-    let (title, message) = truncate_message(title, message);
-    let on_click = format!("{} focus", std::env::current_exe()?.display());
-    let args = SendArgs {
-        title: Some(title),
-        message,
-        icon: None,
-        no_icon: false,
-        link: None,
-        sound: None,
-        telegram_token: None,
-        telegram_chat_id: None,
-        telegram_parse_mode: None,
-        telegram_silent: false,
-        silent: false,
-        urgency: None,
-        tag: None,
-        source: Some("claude".to_string()),
-        on_click: Some(on_click),
-        wait_for_click: false,
-        background: true,
-        json: false,
-        provider: None,
-        remote_host: None,
-        remote_port: None,
-        remote_token: None,
-        remote_timeout_ms: None,
-        remote_retries: None,
-        no_fallback: false,
-    };
+    #[test]
+    fn listener_drops_remote_commands() {
+        let envelope = envelope(vec![
+            action("Wipe", None, Some("rm -rf ~")),
+            action("Open", Some("https://example.com"), None),
+        ]);
+        let notification = received_notification(None, &envelope, false, false);
+        assert_eq!(notification.actions.len(), 2);
+        assert_eq!(notification.actions[0].label, "Wipe");
+        assert!(notification.actions[0].command.is_none());
+        assert_eq!(
+            notification.actions[1].url.as_deref(),
+            Some("https://example.com")
+        );
+        assert!(!has_click_actions(&Notification {
+            actions: vec![notification.actions[0].clone()],
+            ..Default::default()
+        }));
+    }
 
-    handle_send(None, args)
-}
+    /// Answers every question with its first action and counts progress
+    /// updates in the message it returns.
+    struct Scripted {
+        caps: Capabilities,
+    }
 
-fn handle_codex_hook(payload: serde_json::Value) -> Result<(), NotifallError> {
-    let ntype = payload.get("type").and_then(|v| v.as_str()).unwrap_or("");
-    if ntype != "agent-turn-complete" {
-        return Ok(());
+    impl ProviderPlugin for Scripted {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            self.caps
+        }
+
+        fn deliver(
+            &self,
+            _cx: &SendContext,
+            _notification: Notification,
+            _targets: &mut Vec<TargetResult>,
+        ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+            Ok(None)
+        }
+
+        fn ask(
+            &self,
+            _cx: &SendContext,
+            notification: Notification,
+            _timeout: Duration,
+        ) -> Result<Option<String>, NotifallError> {
+            Ok(notification.actions.first().map(|a| a.label.clone()))
+        }
+
+        fn progress(
+            &self,
+            _cx: &SendContext,
+            _task: &ProgressTask,
+            _notification: Notification,
+            previous: Option<&serde_json::Value>,
+        ) -> Result<Option<serde_json::Value>, NotifallError> {
+            let count = previous.and_then(|v| v.as_u64()).unwrap_or(0);
+            Ok(Some(serde_json::json!(count + 1)))
+        }
     }
 
-    let assistant_message = payload
-        .get("last-assistant-message")
-        .and_then(|v| v.as_str());
-    let title = if let Some(msg) = assistant_message {
-        format!("Codex: {}", msg)
-    } else {
-        "Codex: Turn Complete".to_string()
-    };
+    fn with_plugin<T>(caps: Capabilities, run: impl FnOnce(&SendContext) -> T) -> T {
+        let mut registry = ProviderRegistry::empty();
+        registry.register(Scripted { caps });
+        let cx = SendContext {
+            config: None,
+            overrides: &SendOverrides::default(),
+            origin: &RemoteContext::default(),
+            interaction: &Interaction::default(),
+            registry: &registry,
+        };
+        run(&cx)
+    }
 
-    let input_messages = payload
-        .get("input_messages")
-        .and_then(|v| v.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let mut message = input_messages
-        .iter()
-        .filter_map(|v| v.as_str())
-        .collect::<Vec<_>>()
-        .join(" ")
-        .trim()
-        .to_string();
-    if message.is_empty() {
-        message = " ".to_string();
+    #[test]
+    fn ask_dispatches_to_plugins_that_can_ask() {
+        let question = Notification {
+            actions: vec![action("Approve", None, None), action("Deny", None, None)],
+            ..Notification::new("Run tests?", "")
+        };
+        let can_ask = Capabilities {
+            ask: true,
+            ..Default::default()
+        };
+        let answer = with_plugin(can_ask, |cx| {
+            delivery::ask_target(cx, "scripted", question.clone(), Duration::from_secs(1))
+        });
+        assert_eq!(answer.unwrap().as_deref(), Some("Approve"));
+
+        let err = with_plugin(Capabilities::default(), |cx| {
+            delivery::ask_target(cx, "scripted", question.clone(), Duration::from_secs(1))
+        });
+        assert_eq!(err.unwrap_err().kind(), "unsupported");
     }
 
-    let (title, message) = truncate_message(title, message);
-    let on_click = format!("{} focus", std::env::current_exe()?.display());
-    let args = SendArgs {
-        title: Some(title),
-        message,
-        icon: None,
-        no_icon: false,
-        link: None,
-        sound: None,
-        telegram_token: None,
-        telegram_chat_id: None,
-        telegram_parse_mode: None,
-        telegram_silent: false,
-        silent: false,
-        urgency: None,
-        tag: None,
-        source: Some("codex".to_string()),
-        on_click: Some(on_click),
-        wait_for_click: false,
-        background: true,
-        json: false,
-        provider: None,
-        remote_host: None,
-        remote_port: None,
-        remote_token: None,
-        remote_timeout_ms: None,
-        remote_retries: None,
-        no_fallback: false,
-    };
+    fn task(step: ProgressStep) -> ProgressTask {
+        ProgressTask {
+            key: "deploy".to_string(),
+            step,
+        }
+    }
 
-    handle_send(None, args)
-}
+    #[test]
+    fn progress_hands_providers_what_they_returned_last_time() {
+        let target = ProgressTarget {
+            provider: "scripted".to_string(),
+            message: Some(serde_json::json!(1)),
+        };
+        let update = Notification::new("Deploy", "50%");
+        let edited = with_plugin(Capabilities::default(), |cx| {
+            progress_target(cx, &task(ProgressStep::Update), &target, &update)
+        })
+        .unwrap();
+        assert_eq!(edited.message, Some(serde_json::json!(2)));
+    }
 
-fn truncate_message(title: String, message: String) -> (String, String) {
-    let max_title = 120;
-    let max_message = 300;
-    (
-        truncate_to(title, max_title),
-        truncate_to(message, max_message),
-    )
-}
+    /// Uses the default `progress`; delivering always fails.
+    struct Plain;
+
+    impl ProviderPlugin for Plain {
+        fn name(&self) -> &'static str {
+            "plain"
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+
+        fn deliver(
+            &self,
+            _cx: &SendContext,
+            _notification: Notification,
+            _targets: &mut Vec<TargetResult>,
+        ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+            Err(NotifallError::ProviderUnsupported("plain".to_string()))
+        }
+    }
+
+    #[test]
+    fn progress_updates_skip_providers_that_cannot_edit_or_replace() {
+        let mut registry = ProviderRegistry::empty();
+        registry.register(Plain);
+        let cx = SendContext {
+            config: None,
+            overrides: &SendOverrides::default(),
+            origin: &RemoteContext::default(),
+            interaction: &Interaction::default(),
+            registry: &registry,
+        };
+        let target = ProgressTarget {
+            provider: "plain".to_string(),
+            message: None,
+        };
+        let update = Notification::new("Deploy", "50%");
+        let skipped = progress_target(&cx, &task(ProgressStep::Update), &target, &update);
+        assert!(skipped.unwrap().message.is_none());
+        for step in [ProgressStep::Start, ProgressStep::Finish] {
+            let delivered = progress_target(&cx, &task(step), &target, &update);
+            assert_eq!(delivered.unwrap_err().kind(), "unsupported");
+        }
+    }
+
+    #[test]
+    fn listener_tasks_are_kept_apart_by_host() {
+        let task = task(ProgressStep::Update);
+        assert_eq!(task.group(), "ding-progress-deploy");
+        assert_eq!(
+            task.from_host(Some("ci")).group(),
+            "ding-progress-ci/deploy"
+        );
+        assert_eq!(task.from_host(None), task);
+    }
+
+    #[test]
+    fn provider_rows_say_what_is_missing_and_where_settings_come_from() {
+        let registry = ProviderRegistry::builtin();
+        let telegram = registry.get("telegram").unwrap();
+        let path = Path::new("/home/me/.config/ding/config.toml");
+        let empty = toml::Table::new();
+        assert_eq!(
+            provider_row(telegram, None, path, &empty),
+            [
+                "telegram",
+                "yes",
+                "no (telegram.bot_token)",
+                "wait-for-click,actions,ask,edit,attachments",
+                "-"
+            ]
+        );
 
-fn truncate_to(value: String, max_len: usize) -> String {
-    if value.len() <= max_len {
-        return value;
+        let contents = "[telegram]\nbot_token = \"1:a\"\nchat_id = \"42\"\n";
+        let config: Config = toml::from_str(contents).unwrap();
+        let sections: toml::Table = contents.parse().unwrap();
+        let row = provider_row(telegram, Some(&config), path, &sections);
+        assert_eq!(row[2], "yes");
+        assert_eq!(row[4], "/home/me/.config/ding/config.toml [telegram]");
+
+        let mut registry = ProviderRegistry::empty();
+        registry.register(Scripted {
+            caps: Capabilities::default(),
+        });
+        let scripted = registry.get("scripted").unwrap();
+        assert_eq!(
+            provider_row(scripted, None, path, &empty),
+            ["scripted", "yes", "yes", "-", "defaults"]
+        );
+    }
+
+    #[test]
+    fn test_rows_show_the_error_kind_of_a_failure() {
+        let latency = Duration::from_millis(42);
+        assert_eq!(
+            test_row("macos", &Ok(()), latency),
+            ["macos", "pass", "42ms", "-", "-"]
+        );
+        let auth = NotifallError::Provider(ProviderError::Auth("bad token".to_string()));
+        let row = test_row("telegram", &Err(auth), latency);
+        assert_eq!(&row[1..4], ["fail", "42ms", "auth"]);
+        let timeout = NotifallError::Provider(ProviderError::Timeout("10s".to_string()));
+        assert_eq!(test_row("remote", &Err(timeout), latency)[3], "timeout");
+        let unsupported = NotifallError::ProviderUnsupported("slack".to_string());
+        assert_eq!(
+            test_row("slack", &Err(unsupported), latency)[3],
+            "unsupported"
+        );
+    }
+
+    #[test]
+    fn listener_keeps_remote_commands_when_allowed() {
+        let envelope = envelope(vec![action("Merge", None, Some("gh pr merge 1"))]);
+        let notification = received_notification(None, &envelope, false, true);
+        assert_eq!(
+            notification.actions[0].command.as_deref(),
+            Some("gh pr merge 1")
+        );
+    }
+
+    #[test]
+    fn watch_pid_rejects_non_positive_pids() {
+        for pid in ["0", "-1"] {
+            assert!(Cli::try_parse_from(["ding", "watch", "pid", "--", pid]).is_err());
+        }
+        assert!(Cli::try_parse_from(["ding", "watch", "pid", "42"]).is_ok());
+    }
+
+    #[test]
+    fn once_is_only_offered_where_a_watch_repeats() {
+        // A pid exits only once, so `watch pid` always stops after one.
+        assert!(Cli::try_parse_from(["ding", "watch", "pid", "42", "--once"]).is_err());
+        assert!(Cli::try_parse_from(["ding", "watch", "proc", "rsync", "--once"]).is_ok());
+        assert!(
+            Cli::try_parse_from(["ding", "watch", "file", "log", "--match", "x", "--once"]).is_ok()
+        );
     }
-    let suffix = "...";
-    let take_len = max_len.saturating_sub(suffix.len());
-    let mut truncated = value.chars().take(take_len).collect::<String>();
-    truncated.push_str(suffix);
-    truncated
 }
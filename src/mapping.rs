@@ -0,0 +1,545 @@
+use crate::config::{HookEventConfig, HookMappingConfig};
+use crate::notification::Urgency;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A notification produced from a hook payload, before it is sent.
+#[derive(Debug, Clone, Default)]
+pub struct MappedEvent {
+    pub title: String,
+    pub message: String,
+    pub urgency: Option<Urgency>,
+    pub tag: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// What `ding hook claude` sends, as a mapping. The payload is first given
+/// the `/ding` fields from [`crate::hook::claude_payload`]: the transcript
+/// summary, the tool call, and a subtype that also marks failed tool calls.
+const CLAUDE_MAPPING: &str = r#"
+event = "/hook_event_name"
+
+[events."*"]
+title = "Claude Code: {/hook_event_name|Unknown}"
+message = "{/message|/tool_name}"
+urgency = "normal"
+tag = "claude"
+[events."*".metadata]
+session_id = "{/session_id}"
+cwd = "{/cwd}"
+transcript_path = "{/transcript_path}"
+
+[events.Notification]
+title = "Claude Code: {/notification_type|notification}"
+message = "{/message}"
+tag = "notification"
+[events."Notification.permission_prompt"]
+urgency = "high"
+tag = "permission"
+[events."Notification.elicitation_dialog"]
+urgency = "high"
+tag = "input"
+[events."Notification.idle_prompt"]
+tag = "idle"
+[events."Notification.auth_success"]
+urgency = "low"
+tag = "auth"
+
+[events.Stop]
+title = "Claude Code: finished"
+message = "{/ding/summary|Task completed}"
+tag = "stop"
+
+[events.SubagentStop]
+title = "Claude Code: subagent finished"
+message = "Subagent task completed"
+urgency = "low"
+tag = "subagent"
+
+[events.PreToolUse]
+title = "Claude Code: running {/tool_name}"
+message = "{/ding/tool|/tool_name}"
+urgency = "low"
+tag = "tool"
+
+[events.PostToolUse]
+title = "Claude Code: {/tool_name} finished"
+message = "{/ding/tool|/tool_name}"
+urgency = "low"
+tag = "tool"
+[events."PostToolUse.failed"]
+title = "Claude Code: {/tool_name} failed"
+urgency = "high"
+tag = "error"
+
+[events.SessionStart]
+title = "Claude Code: session started"
+message = "{/source}"
+urgency = "low"
+tag = "session"
+
+[events.SessionEnd]
+title = "Claude Code: session ended"
+message = "{/reason}"
+urgency = "low"
+tag = "session"
+
+[events.UserPromptSubmit]
+title = "Claude Code: prompt submitted"
+message = "{/prompt}"
+urgency = "low"
+tag = "prompt"
+
+[events.PreCompact]
+title = "Claude Code: compacting"
+message = "Compacting context ({/trigger})"
+urgency = "low"
+tag = "compact"
+"#;
+
+/// What `ding hook codex` sends, as a mapping: only finished turns.
+const CODEX_MAPPING: &str = r#"
+event = "/type"
+
+[events.agent-turn-complete]
+title = "Codex: {/last-assistant-message|Turn Complete}"
+message = "{/input-messages|/input_messages}"
+urgency = "normal"
+tag = "complete"
+"#;
+
+/// The built-in mapping behind `ding hook <name>`, for the integrations that
+/// are mappings. A `[hook_mappings.<name>]` section is applied on top of it.
+pub fn builtin_mapping(name: &str) -> Option<HookMappingConfig> {
+    let raw = match name {
+        "claude" => CLAUDE_MAPPING,
+        "codex" => CODEX_MAPPING,
+        _ => return None,
+    };
+    toml::from_str(raw).ok()
+}
+
+/// The notification the built-in mapping `name` makes of `payload`, to be
+/// passed as `base` to [`map_event`] with the configured mapping.
+pub fn builtin_event(name: &str, payload: &Value) -> Option<MappedEvent> {
+    map_event(name, &builtin_mapping(name)?, payload, None)
+}
+
+/// Event type pointers of the built-in hook handlers, so a mapping that only
+/// overrides a built-in integration does not need its own `event` key.
+pub fn builtin_event_pointer(name: &str) -> Option<&'static str> {
+    match name {
+        "claude" | "gemini" => Some("/hook_event_name"),
+        "codex" | "opencode" => Some("/type"),
+        _ => None,
+    }
+}
+
+/// Subtype pointers of the built-in hook handlers, tried in turn. Claude's
+/// `/ding/subtype` also marks failed tool calls; see [`crate::hook::claude_payload`].
+pub fn builtin_subtype_pointers(name: &str) -> &'static [&'static str] {
+    match name {
+        "claude" => &["/ding/subtype", "/notification_type"],
+        "gemini" => &["/notification_type"],
+        _ => &[],
+    }
+}
+
+/// Maps `payload` through the mapping named `name`.
+///
+/// Rules are looked up as `<event>.<subtype>`, then `<event>`, then `*`; for
+/// each field the most specific rule that sets it wins, falling back to
+/// `base` (the built-in handler's notification, if any). Returns `None` when
+/// the event is skipped, or when nothing matches and there is no `base`.
+pub fn map_event(
+    name: &str,
+    mapping: &HookMappingConfig,
+    payload: &Value,
+    base: Option<MappedEvent>,
+) -> Option<MappedEvent> {
+    let event = mapping
+        .event
+        .as_deref()
+        .or_else(|| builtin_event_pointer(name))
+        .and_then(|pointer| pointer_text(payload, pointer))
+        .unwrap_or_default();
+    let subtype = match mapping.subtype.as_deref() {
+        Some(pointer) => pointer_text(payload, pointer),
+        None => builtin_subtype_pointers(name)
+            .iter()
+            .find_map(|pointer| pointer_text(payload, pointer)),
+    }
+    .filter(|subtype| !subtype.is_empty());
+
+    let empty = BTreeMap::new();
+    let events = mapping.events.as_ref().unwrap_or(&empty);
+    let mut keys = Vec::new();
+    if let Some(subtype) = subtype.as_deref() {
+        keys.push(format!("{event}.{subtype}"));
+    }
+    keys.push(event.clone());
+    keys.push("*".to_string());
+    let rules = keys
+        .iter()
+        .filter_map(|key| events.get(key))
+        .collect::<Vec<&HookEventConfig>>();
+
+    if rules.is_empty() && base.is_none() {
+        return None;
+    }
+    if rules.iter().find_map(|rule| rule.skip) == Some(true) {
+        return None;
+    }
+
+    let mut vars = BTreeMap::new();
+    vars.insert("event", event.clone());
+    vars.insert("subtype", subtype.unwrap_or_default());
+    if let Some(base) = base.as_ref() {
+        vars.insert("title", base.title.clone());
+        vars.insert("message", base.message.clone());
+    }
+    let field = |get: fn(&HookEventConfig) -> Option<&String>| {
+        rules
+            .iter()
+            .find_map(|rule| get(rule))
+            .map(|template| render(template, payload, &vars))
+    };
+
+    let base = base.unwrap_or_else(|| MappedEvent {
+        title: format!("{name}: {event}"),
+        ..Default::default()
+    });
+    let mut metadata = base.metadata;
+    for rule in rules.iter().rev() {
+        for (key, template) in rule.metadata.iter().flatten() {
+            let value = render(template, payload, &vars);
+            if !value.is_empty() {
+                metadata.insert(key.clone(), value);
+            }
+        }
+    }
+    Some(MappedEvent {
+        title: field(|r| r.title.as_ref()).unwrap_or(base.title),
+        message: field(|r| r.message.as_ref()).unwrap_or(base.message),
+        urgency: rules.iter().find_map(|rule| rule.urgency).or(base.urgency),
+        tag: field(|r| r.tag.as_ref()).or(base.tag),
+        metadata,
+    })
+}
+
+/// Expands `{/json/pointer}` and `{/pointer|fallback}` from the payload and
+/// `{event}`, `{subtype}`, `{title}`, `{message}` from `vars`. A fallback
+/// starting with `/` is another pointer, tried in turn, as in
+/// `{/a|/b|none}`. `{{` and `}}` produce literal braces.
+pub fn render(template: &str, payload: &Value, vars: &BTreeMap<&str, String>) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let Some(end) = tail.find('}').filter(|_| tail.starts_with('{')) else {
+            out.push_str(&tail[..1]);
+            rest = &tail[1..];
+            continue;
+        };
+        let expr = &tail[1..end];
+        let (key, mut fallback) = match expr.split_once('|') {
+            Some((key, fallback)) => (key.trim(), Some(fallback)),
+            None => (expr.trim(), None),
+        };
+        let mut value = if key.starts_with('/') {
+            pointer_text(payload, key)
+        } else {
+            vars.get(key).cloned()
+        };
+        while value.as_deref().is_none_or(str::is_empty)
+            && let Some(pointer) = fallback.filter(|f| f.starts_with('/'))
+        {
+            let (next, rest) = match pointer.split_once('|') {
+                Some((next, rest)) => (next, Some(rest)),
+                None => (pointer, None),
+            };
+            value = pointer_text(payload, next.trim());
+            fallback = rest;
+        }
+        match value.filter(|v| !v.is_empty()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(fallback.unwrap_or("")),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The value at `pointer` as display text: strings as-is, string arrays
+/// joined with spaces, other values as compact JSON.
+fn pointer_text(payload: &Value, pointer: &str) -> Option<String> {
+    match payload.pointer(pointer)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) if items.iter().all(|v| v.is_string()) => Some(
+            items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(toml: &str) -> HookMappingConfig {
+        toml::from_str(toml).expect("valid mapping")
+    }
+
+    const CLAUDE_PERMISSION: &str = r#"{
+        "session_id": "3f1c9a2e",
+        "transcript_path": "/home/dev/.claude/projects/app/3f1c9a2e.jsonl",
+        "cwd": "/home/dev/app",
+        "hook_event_name": "Notification",
+        "notification_type": "permission_prompt",
+        "message": "Claude needs your permission to use Bash"
+    }"#;
+
+    const CODEX_TURN_COMPLETE: &str = r#"{
+        "type": "agent-turn-complete",
+        "turn-id": "12",
+        "input-messages": ["fix the failing test"],
+        "last-assistant-message": "Fixed the off-by-one in parser.rs"
+    }"#;
+
+    const CUSTOM_AGENT: &str = r#"{
+        "event": "run.finished",
+        "status": "failed",
+        "run": { "id": 42, "name": "nightly", "errors": ["timeout", "oom"] }
+    }"#;
+
+    fn payload(raw: &str) -> Value {
+        serde_json::from_str(raw).unwrap()
+    }
+
+    #[test]
+    fn maps_event_with_subtype_rule() {
+        let mapping = mapping(
+            r#"
+            [events."Notification.permission_prompt"]
+            title = "Approve? ({/cwd})"
+            urgency = "high"
+            tag = "permission"
+            [events.Notification]
+            message = "{/message}"
+            urgency = "normal"
+            "#,
+        );
+        let event = map_event("claude", &mapping, &payload(CLAUDE_PERMISSION), None).unwrap();
+        assert_eq!(event.title, "Approve? (/home/dev/app)");
+        assert_eq!(event.message, "Claude needs your permission to use Bash");
+        assert!(matches!(event.urgency, Some(Urgency::High)));
+        assert_eq!(event.tag.as_deref(), Some("permission"));
+    }
+
+    #[test]
+    fn overrides_builtin_fields_and_keeps_the_rest() {
+        let mapping = mapping(
+            r#"
+            [events.Notification]
+            title = "[work] {title}"
+            [events.Notification.metadata]
+            project = "{/cwd}"
+            "#,
+        );
+        let base = MappedEvent {
+            title: "Claude Code: permission_prompt".to_string(),
+            message: "Claude needs your permission to use Bash".to_string(),
+            urgency: Some(Urgency::High),
+            tag: Some("permission".to_string()),
+            metadata: BTreeMap::from([("session_id".to_string(), "3f1c9a2e".to_string())]),
+        };
+        let event = map_event("claude", &mapping, &payload(CLAUDE_PERMISSION), Some(base)).unwrap();
+        assert_eq!(event.title, "[work] Claude Code: permission_prompt");
+        assert_eq!(event.message, "Claude needs your permission to use Bash");
+        assert_eq!(event.tag.as_deref(), Some("permission"));
+        assert_eq!(event.metadata["session_id"], "3f1c9a2e");
+        assert_eq!(event.metadata["project"], "/home/dev/app");
+    }
+
+    #[test]
+    fn skip_suppresses_builtin_events() {
+        let mapping = mapping(
+            r#"
+            [events.agent-turn-complete]
+            skip = true
+            "#,
+        );
+        let base = MappedEvent {
+            title: "Codex: Turn Complete".to_string(),
+            ..Default::default()
+        };
+        assert!(map_event("codex", &mapping, &payload(CODEX_TURN_COMPLETE), Some(base)).is_none());
+    }
+
+    #[test]
+    fn wildcard_skip_acts_as_allowlist() {
+        let mapping = mapping(
+            r#"
+            event = "/event"
+            [events."*"]
+            skip = true
+            [events."run.finished"]
+            skip = false
+            title = "{/run/name} {/status}"
+            message = "{/run/errors}"
+            "#,
+        );
+        let event = map_event("ci", &mapping, &payload(CUSTOM_AGENT), None).unwrap();
+        assert_eq!(event.title, "nightly failed");
+        assert_eq!(event.message, "timeout oom");
+
+        let other = payload(r#"{ "event": "run.started" }"#);
+        assert!(map_event("ci", &mapping, &other, None).is_none());
+    }
+
+    #[test]
+    fn unmatched_generic_events_are_dropped() {
+        let mapping = mapping(
+            r#"
+            event = "/event"
+            [events."run.started"]
+            title = "started"
+            "#,
+        );
+        assert!(map_event("ci", &mapping, &payload(CUSTOM_AGENT), None).is_none());
+    }
+
+    #[test]
+    fn subtype_pointer_and_default_title() {
+        let mapping = mapping(
+            r#"
+            event = "/event"
+            subtype = "/status"
+            [events."run.finished.failed"]
+            urgency = "high"
+            [events."run.finished"]
+            message = "run {/run/id}"
+            "#,
+        );
+        let event = map_event("ci", &mapping, &payload(CUSTOM_AGENT), None).unwrap();
+        assert_eq!(event.title, "ci: run.finished");
+        assert_eq!(event.message, "run 42");
+        assert!(matches!(event.urgency, Some(Urgency::High)));
+    }
+
+    #[test]
+    fn render_handles_fallbacks_and_escapes() {
+        let vars = BTreeMap::from([("event", "Stop".to_string())]);
+        let payload = payload(CODEX_TURN_COMPLETE);
+        assert_eq!(
+            render("{event}: {/last-assistant-message}", &payload, &vars),
+            "Stop: Fixed the off-by-one in parser.rs"
+        );
+        assert_eq!(render("{/missing|n/a}", &payload, &vars), "n/a");
+        assert_eq!(
+            render("{{literal}} {/turn-id}", &payload, &vars),
+            "{literal} 12"
+        );
+        assert_eq!(
+            render("unclosed {brace", &payload, &vars),
+            "unclosed {brace"
+        );
+    }
+
+    #[test]
+    fn builtin_mappings_cover_claude_and_codex() {
+        let mut permission = payload(CLAUDE_PERMISSION);
+        permission["ding"] = serde_json::json!({ "subtype": "permission_prompt" });
+        let event = builtin_event("claude", &permission).unwrap();
+        assert_eq!(event.title, "Claude Code: permission_prompt");
+        assert_eq!(event.message, "Claude needs your permission to use Bash");
+        assert!(matches!(event.urgency, Some(Urgency::High)));
+        assert_eq!(event.tag.as_deref(), Some("permission"));
+        assert_eq!(event.metadata["session_id"], "3f1c9a2e");
+        assert!(!event.metadata.contains_key("tool_use_id"));
+
+        let failed = payload(
+            r#"{
+                "hook_event_name": "PostToolUse",
+                "tool_name": "Bash",
+                "tool_input": { "command": "cargo test" },
+                "ding": { "tool": "cargo test", "subtype": "failed" }
+            }"#,
+        );
+        let event = builtin_event("claude", &failed).unwrap();
+        assert_eq!(event.title, "Claude Code: Bash failed");
+        assert_eq!(event.message, "cargo test");
+        assert_eq!(event.tag.as_deref(), Some("error"));
+
+        let unknown = payload(r#"{ "hook_event_name": "TeammateIdle", "tool_name": "Task" }"#);
+        let event = builtin_event("claude", &unknown).unwrap();
+        assert_eq!(event.title, "Claude Code: TeammateIdle");
+        assert_eq!(event.message, "Task");
+
+        let event = builtin_event("codex", &payload(CODEX_TURN_COMPLETE)).unwrap();
+        assert_eq!(event.title, "Codex: Fixed the off-by-one in parser.rs");
+        assert_eq!(event.message, "fix the failing test");
+        assert_eq!(event.tag.as_deref(), Some("complete"));
+        let other = payload(r#"{ "type": "approval-requested" }"#);
+        assert!(builtin_event("codex", &other).is_none());
+    }
+
+    #[test]
+    fn configured_mappings_apply_on_top_of_builtins() {
+        let mapping = mapping(
+            r#"
+            [events."Notification.idle_prompt"]
+            urgency = "low"
+            message = "[idle] {message}"
+            [events.approval-requested]
+            title = "Codex wants approval"
+            "#,
+        );
+        let idle = payload(
+            r#"{
+                "hook_event_name": "Notification",
+                "notification_type": "idle_prompt",
+                "message": "Claude is waiting for your input",
+                "ding": { "subtype": "idle_prompt" }
+            }"#,
+        );
+        let base = builtin_event("claude", &idle);
+        let event = map_event("claude", &mapping, &idle, base).unwrap();
+        assert_eq!(event.title, "Claude Code: idle_prompt");
+        assert_eq!(event.message, "[idle] Claude is waiting for your input");
+        assert!(matches!(event.urgency, Some(Urgency::Low)));
+        assert_eq!(event.tag.as_deref(), Some("idle"));
+
+        let approval = payload(r#"{ "type": "approval-requested" }"#);
+        let base = builtin_event("codex", &approval);
+        let event = map_event("codex", &mapping, &approval, base).unwrap();
+        assert_eq!(event.title, "Codex wants approval");
+    }
+
+    #[test]
+    fn pointer_fallbacks_are_tried_in_turn() {
+        let vars = BTreeMap::new();
+        let payload = payload(CODEX_TURN_COMPLETE);
+        assert_eq!(
+            render("{/missing|/input-messages|none}", &payload, &vars),
+            "fix the failing test"
+        );
+        assert_eq!(
+            render("{/missing|/also-missing|none}", &payload, &vars),
+            "none"
+        );
+        assert_eq!(render("{/missing|/also-missing}", &payload, &vars), "");
+    }
+}
@@ -1,7 +1,20 @@
 use crate::config::MacosConfig;
 use crate::context::Context;
 use crate::notification::Notification;
+use crate::provider::DeliveryOutcome;
+use crate::provider::telegram::SentMessage;
+use crate::state::{default_state_dir, prune_dir};
 use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long pending Telegram messages wait for an answer before being pruned.
+const PENDING_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often `ding ask` looks for an answer recorded by the bot.
+const ANSWER_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WaitPayload {
@@ -9,4 +22,204 @@ pub struct WaitPayload {
     pub macos: Option<MacosConfig>,
     pub on_click: Option<String>,
     pub context: Option<Context>,
+    /// A question from `ding ask`: the bot, or `ding wait-macos`, hands the
+    /// chosen button to the asking process instead of running anything.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ask: bool,
+    /// A foreground `send --wait-for-click`: the bot records the press or
+    /// reply for the sending process, which handles the click itself.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wait: bool,
+}
+
+/// Telegram messages left for `ding telegram bot` to handle, answers it
+/// recorded for `ding ask` and waiting sends, and the lock the bot holds while it runs.
+#[derive(Debug, Clone)]
+pub struct PendingStore {
+    dir: PathBuf,
+}
+
+impl PendingStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The store every ding process shares, in the state directory.
+    pub fn default_location() -> Self {
+        Self::new(default_state_dir())
+    }
+
+    fn pending_dir(&self) -> PathBuf {
+        self.dir.join("telegram-pending")
+    }
+
+    fn answer_path(&self, sent: SentMessage) -> PathBuf {
+        self.dir.join("telegram-answers").join(message_file(sent))
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.dir.join("telegram-bot.lock")
+    }
+
+    pub fn save(&self, sent: SentMessage, payload: &WaitPayload) -> Result<(), std::io::Error> {
+        fs::create_dir_all(self.pending_dir())?;
+        fs::write(
+            self.pending_dir().join(message_file(sent)),
+            serde_json::to_vec(payload)?,
+        )
+    }
+
+    /// Removes and returns the pending notification behind a Telegram message.
+    pub fn take(&self, sent: SentMessage) -> Option<WaitPayload> {
+        let path = self.pending_dir().join(message_file(sent));
+        let contents = fs::read_to_string(&path).ok()?;
+        let _ = fs::remove_file(&path);
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn count(&self) -> usize {
+        fs::read_dir(self.pending_dir())
+            .map(|entries| entries.count())
+            .unwrap_or(0)
+    }
+
+    /// Drops pending messages and answers nobody picked up within a week.
+    pub fn prune(&self) {
+        prune_dir(&self.pending_dir(), PENDING_MAX_AGE);
+        prune_dir(&self.dir.join("telegram-answers"), PENDING_MAX_AGE);
+    }
+
+    /// Marks the bot as running for as long as the returned file is open.
+    /// Returns `None` if another bot already holds the lock.
+    pub fn lock_bot(&self) -> Result<Option<File>, std::io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let file = File::create(self.lock_path())?;
+        Ok(try_lock(&file).then_some(file))
+    }
+
+    /// Whether `ding telegram bot` is running and will see button presses.
+    /// Anything else polling getUpdates meanwhile would fight it for them.
+    pub fn bot_running(&self) -> bool {
+        match File::open(self.lock_path()) {
+            // The lock is released when this handle is closed.
+            Ok(file) => !try_lock(&file),
+            Err(_) => false,
+        }
+    }
+
+    /// Records how a question or waiting send was answered, for
+    /// [`Self::wait_for_answer`].
+    pub fn answer(
+        &self,
+        sent: SentMessage,
+        outcome: &DeliveryOutcome,
+    ) -> Result<(), std::io::Error> {
+        let path = self.answer_path(sent);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(outcome)?)
+    }
+
+    /// Waits until the bot records an answer for `sent` or `deadline`, if
+    /// any, passes. On timeout the message is withdrawn, so a late press is
+    /// reported as expired.
+    pub fn wait_for_answer(
+        &self,
+        sent: SentMessage,
+        deadline: Option<Instant>,
+    ) -> Option<DeliveryOutcome> {
+        loop {
+            if let Some(outcome) = self.read_answer(sent) {
+                return Some(outcome);
+            }
+            let remaining = deadline.map_or(ANSWER_POLL_INTERVAL, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            if remaining.is_zero() {
+                if self.take(sent).is_some() {
+                    return None;
+                }
+                // The bot took the message just now; its answer follows.
+                std::thread::sleep(ANSWER_POLL_INTERVAL);
+                return self.read_answer(sent);
+            }
+            std::thread::sleep(remaining.min(ANSWER_POLL_INTERVAL));
+        }
+    }
+
+    fn read_answer(&self, sent: SentMessage) -> Option<DeliveryOutcome> {
+        let path = self.answer_path(sent);
+        let contents = fs::read(&path).ok()?;
+        let _ = fs::remove_file(&path);
+        serde_json::from_slice(&contents).ok()
+    }
+}
+
+fn message_file(sent: SentMessage) -> String {
+    format!("{}_{}.json", sent.chat_id, sent.message_id)
+}
+
+/// Takes an exclusive lock on `file` without waiting.
+fn try_lock(file: &File) -> bool {
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> PendingStore {
+        let dir = std::env::temp_dir().join(format!("ding-pending-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        PendingStore::new(dir)
+    }
+
+    const SENT: SentMessage = SentMessage {
+        chat_id: 42,
+        message_id: 7,
+    };
+
+    #[test]
+    fn bot_running_follows_the_lock() {
+        let store = store("lock");
+        assert!(!store.bot_running());
+
+        let lock = store.lock_bot().unwrap().expect("lock is free");
+        assert!(store.bot_running());
+        assert!(store.lock_bot().unwrap().is_none());
+
+        drop(lock);
+        assert!(!store.bot_running());
+    }
+
+    #[test]
+    fn wait_for_answer_returns_the_recorded_outcome() {
+        let store = store("answer");
+        let approve = DeliveryOutcome::ActionButton("Approve".to_string());
+        store.answer(SENT, &approve).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert_eq!(store.wait_for_answer(SENT, Some(deadline)), Some(approve));
+        assert!(store.wait_for_answer(SENT, Some(Instant::now())).is_none());
+
+        let reply = DeliveryOutcome::Replied("ship it".to_string());
+        store.answer(SENT, &reply).unwrap();
+        assert_eq!(store.wait_for_answer(SENT, None), Some(reply));
+    }
+
+    #[test]
+    fn wait_for_answer_withdraws_the_question_on_timeout() {
+        let store = store("timeout");
+        let payload = WaitPayload {
+            notification: Notification::new("Approve?", "rm -rf target"),
+            macos: None,
+            on_click: None,
+            context: None,
+            ask: true,
+            wait: false,
+        };
+        store.save(SENT, &payload).unwrap();
+        assert!(store.wait_for_answer(SENT, Some(Instant::now())).is_none());
+        assert!(store.take(SENT).is_none());
+    }
 }
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+/// Messages kept up to date by `ding progress`, one file per key in the state
+/// directory so separate invocations can find what the first one sent.
+#[derive(Debug, Clone)]
+pub struct ProgressStore {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressState {
+    pub title: String,
+    pub provider: String,
+    pub source: Option<String>,
+    pub tag: Option<String>,
+    pub started: u64,
+    pub targets: Vec<ProgressTarget>,
+}
+
+/// Where one provider showed the progress message. `message` is what a
+/// provider that can edit needs to find it again, e.g. Telegram's chat and
+/// message id; it is empty for the others.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressTarget {
+    pub provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<serde_json::Value>,
+}
+
+/// Which `ding progress` call a notification is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStep {
+    Start,
+    Update,
+    Finish,
+}
+
+/// The task a progress notification belongs to, as providers and the
+/// listener see it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressTask {
+    pub key: String,
+    pub step: ProgressStep,
+}
+
+impl ProgressTask {
+    /// The identifier a provider that replaces notifications, rather than
+    /// editing them, shows every call of the task under.
+    pub fn group(&self) -> String {
+        format!("ding-progress-{}", self.key)
+    }
+
+    /// The task as the listener keeps it, apart from tasks with the same key
+    /// on other machines.
+    pub fn from_host(&self, host: Option<&str>) -> Self {
+        match host {
+            Some(host) => Self {
+                key: format!("{host}/{}", self.key),
+                step: self.step,
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+impl ProgressStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Serializes calls for `key` from separate processes, so two updates
+    /// don't both send a first message or drop the one the other saved.
+    /// Released when the returned file is closed.
+    pub fn lock(&self, key: &str) -> Result<fs::File, std::io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path(key).with_extension("lock"))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    pub fn load(&self, key: &str) -> Option<ProgressState> {
+        let contents = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, key: &str, state: &ProgressState) -> Result<(), std::io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key), serde_json::to_vec_pretty(state)?)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), std::io::Error> {
+        match fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+/// The message text with a bar for `percent`, e.g. `▓▓▓▓░░░░░░ 40%`.
+pub fn progress_text(message: &str, percent: Option<u8>) -> String {
+    let Some(percent) = percent else {
+        return message.to_string();
+    };
+    let filled = usize::from(percent.min(100)) / 10;
+    let bar = "▓".repeat(filled) + &"░".repeat(10 - filled);
+    if message.trim().is_empty() {
+        format!("{bar} {percent}%")
+    } else {
+        format!("{message}\n{bar} {percent}%")
+    }
+}
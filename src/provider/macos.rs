@@ -1,12 +1,16 @@
+#[cfg(target_os = "macos")]
+use crate::attachment;
 use crate::config::MacosConfig;
 use crate::notification::Notification;
-use crate::provider::{DeliveryOutcome, DeliveryReport, Provider, ProviderError, SendOptions};
+#[cfg(target_os = "macos")]
+use crate::provider::DeliveryOutcome;
+use crate::provider::{DeliveryReport, Provider, ProviderError, SendOptions};
 
 #[cfg(target_os = "macos")]
 use mac_notification_sys::error::{ApplicationError, Error as MacError};
 #[cfg(target_os = "macos")]
 use mac_notification_sys::{
-    set_application, Notification as MacNotification, NotificationResponse, Sound,
+    MainButton, Notification as MacNotification, NotificationResponse, Sound, set_application,
 };
 
 #[cfg(target_os = "macos")]
@@ -45,8 +49,34 @@ impl Provider for MacosProvider {
         notification: &Notification,
         options: SendOptions,
     ) -> Result<DeliveryReport, ProviderError> {
+        let labels = notification
+            .actions
+            .iter()
+            .map(|action| action.label.as_str())
+            .collect::<Vec<_>>();
+        // Notification Center can't show files, so just mention them.
+        let message = match attachment::hint(&notification.attachments) {
+            Some(hint) => format!("{}\n{hint}", notification.message),
+            None => notification.message.clone(),
+        };
         let mut mac = MacNotification::new();
-        mac.title(&notification.title).message(&notification.message);
+        mac.title(&notification.title).message(&message);
+
+        // Notification Center offers one action button plus the close button;
+        // more than two actions go into the action button's dropdown.
+        match labels.as_slice() {
+            [] => {}
+            [only] => {
+                mac.main_button(MainButton::SingleAction(only));
+            }
+            [first, second] => {
+                mac.main_button(MainButton::SingleAction(first))
+                    .close_button(second);
+            }
+            [first, ..] => {
+                mac.main_button(MainButton::DropdownActions(first, &labels));
+            }
+        }
 
         if let Some(tag) = notification.tag.as_deref() {
             mac.subtitle(tag);
@@ -72,10 +102,7 @@ impl Provider for MacosProvider {
             mac.default_sound();
         }
 
-        let icon_path = notification
-            .icon
-            .as_ref()
-            .or(self.config.icon.as_ref());
+        let icon_path = notification.icon.as_ref().or(self.config.icon.as_ref());
         if icon_path.is_some() {
             // Disabled: macOS 15.5 + ImageIO crashes when loading image paths in this backend.
             // We'll rely on bundle icons instead.
@@ -89,7 +116,7 @@ impl Provider for MacosProvider {
         let response = mac
             .send()
             .map_err(|err| ProviderError::Message(err.to_string()))?;
-        let outcome = map_response(response, options.wait_for_click);
+        let outcome = map_response(response, options.wait_for_click, notification);
 
         Ok(DeliveryReport {
             provider: self.name(),
@@ -99,10 +126,57 @@ impl Provider for MacosProvider {
     }
 }
 
+#[cfg(target_os = "macos")]
+impl MacosProvider {
+    /// Shows `notification` in place of any earlier one shown under `group`.
+    /// The API ding sends through can't remove a delivered notification, so
+    /// this goes through `terminal-notifier`; `Unsupported` if it isn't
+    /// installed.
+    pub fn replace(&self, group: &str, notification: &Notification) -> Result<(), ProviderError> {
+        let mut command = std::process::Command::new("terminal-notifier");
+        command
+            .args(["-group", group, "-title", &notification.title])
+            .args(["-message", &notification.message]);
+        if let Some(tag) = notification.tag.as_deref() {
+            command.args(["-subtitle", tag]);
+        }
+        if let Some(bundle) = self.config.app_bundle_id.as_deref() {
+            command.args(["-sender", bundle]);
+        }
+        let sound = notification
+            .sound
+            .as_deref()
+            .or(self.config.sound.as_deref())
+            .unwrap_or("default");
+        if !["none", "off", "silent"]
+            .iter()
+            .any(|off| sound.eq_ignore_ascii_case(off))
+        {
+            command.args(["-sound", sound]);
+        }
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ProviderError::Unsupported);
+            }
+            Err(err) => return Err(ProviderError::Message(err.to_string())),
+        };
+        if !output.status.success() {
+            return Err(ProviderError::Message(format!(
+                "terminal-notifier exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(target_os = "macos")]
 fn map_response(
     response: NotificationResponse,
     waited: bool,
+    notification: &Notification,
 ) -> Option<DeliveryOutcome> {
     if !waited {
         return None;
@@ -111,6 +185,11 @@ fn map_response(
         NotificationResponse::None => DeliveryOutcome::Delivered,
         NotificationResponse::Click => DeliveryOutcome::Clicked,
         NotificationResponse::ActionButton(label) => DeliveryOutcome::ActionButton(label),
+        NotificationResponse::CloseButton(label)
+            if notification.actions.iter().any(|a| a.label == label) =>
+        {
+            DeliveryOutcome::ActionButton(label)
+        }
         NotificationResponse::CloseButton(label) => DeliveryOutcome::Closed(label),
         NotificationResponse::Reply(text) => DeliveryOutcome::Replied(text),
     };
@@ -126,6 +205,10 @@ impl MacosProvider {
     pub fn new(_config: Option<MacosConfig>) -> Result<Self, ProviderError> {
         Err(ProviderError::Unsupported)
     }
+
+    pub fn replace(&self, _group: &str, _notification: &Notification) -> Result<(), ProviderError> {
+        Err(ProviderError::Unsupported)
+    }
 }
 
 #[cfg(not(target_os = "macos"))]
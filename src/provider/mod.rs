@@ -1,15 +1,19 @@
 use crate::notification::Notification;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub mod macos;
+pub mod registry;
 pub mod telegram;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct SendOptions {
     pub wait_for_click: bool,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DeliveryOutcome {
     Delivered,
     Clicked,
@@ -18,6 +22,28 @@ pub enum DeliveryOutcome {
     Replied(String),
 }
 
+impl DeliveryOutcome {
+    /// The outcome as shown in `--json` output and history.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::Delivered => serde_json::Value::String("delivered".to_string()),
+            Self::Clicked => serde_json::Value::String("clicked".to_string()),
+            Self::ActionButton(label) => serde_json::json!({
+                "type": "action",
+                "label": label,
+            }),
+            Self::Closed(label) => serde_json::json!({
+                "type": "closed",
+                "label": label,
+            }),
+            Self::Replied(text) => serde_json::json!({
+                "type": "reply",
+                "text": text,
+            }),
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct DeliveryReport {
@@ -26,15 +52,126 @@ pub struct DeliveryReport {
     pub outcome: Option<DeliveryOutcome>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, thiserror::Error)]
 pub enum ProviderError {
     #[error("provider not available on this platform")]
     Unsupported,
-    #[error("provider error: {0}")]
+    /// A required setting is missing; `field` is its config key.
+    #[error("{field} is not configured")]
+    NotConfigured { field: String },
+    /// The credentials (bot token, listener token) were refused.
+    #[error("authentication failed: {0}")]
+    Auth(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("rate limited, retry after {}s", retry_after.as_secs())]
+    RateLimited { retry_after: Duration },
+    /// A permanent failure such as an unknown chat or a blocked bot; sending
+    /// the same request again won't help.
+    #[error("rejected with status {status}: {body}")]
+    Rejected { status: u16, body: String },
+    /// A network failure or server error that may succeed on retry.
+    #[error("transport error: {0}")]
+    Transport(String),
+    /// Anything else, e.g. a notification the provider can't render.
+    #[error("{0}")]
     Message(String),
 }
 
+impl ProviderError {
+    pub fn not_configured(field: &str) -> Self {
+        Self::NotConfigured {
+            field: field.to_string(),
+        }
+    }
+
+    /// Maps an HTTP error status. `retry_after` comes from the response,
+    /// where the service provides one.
+    pub fn from_status(status: u16, body: String, retry_after: Option<Duration>) -> Self {
+        match status {
+            401 => Self::Auth(body),
+            408 | 504 => Self::Timeout(body),
+            429 => Self::RateLimited {
+                retry_after: retry_after.unwrap_or(Duration::from_secs(1)),
+            },
+            500.. => Self::Transport(format!("status {status}: {body}")),
+            _ => Self::Rejected { status, body },
+        }
+    }
+
+    /// Machine-readable name of the variant, as shown in `--json` output and
+    /// history.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Unsupported => "unsupported",
+            Self::NotConfigured { .. } => "not_configured",
+            Self::Auth(_) => "auth",
+            Self::Timeout(_) => "timeout",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Rejected { .. } => "rejected",
+            Self::Transport(_) => "transport",
+            Self::Message(_) => "other",
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Timeout(_) | Self::RateLimited { .. } | Self::Transport(_)
+        )
+    }
+}
+
+impl From<ureq::Error> for ProviderError {
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Status(status, response) => {
+                let retry_after = response
+                    .header("Retry-After")
+                    .and_then(|value| value.trim().parse().ok())
+                    .map(Duration::from_secs);
+                let body = response.into_string().unwrap_or_default();
+                Self::from_status(status, body.trim().to_string(), retry_after)
+            }
+            ureq::Error::Transport(transport) => {
+                if is_timeout(&transport) {
+                    Self::Timeout(transport.to_string())
+                } else {
+                    Self::Transport(transport.to_string())
+                }
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for ProviderError {
+    /// For failures reading a response body.
+    fn from(err: std::io::Error) -> Self {
+        if is_timeout(&err) {
+            Self::Timeout(err.to_string())
+        } else {
+            Self::Transport(err.to_string())
+        }
+    }
+}
+
+fn is_timeout(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(io) = err.downcast_ref::<std::io::Error>()
+            && matches!(
+                io.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            )
+        {
+            return true;
+        }
+        current = err.source();
+    }
+    false
+}
+
 pub trait Provider {
     fn name(&self) -> &'static str;
     fn send(
@@ -0,0 +1,409 @@
+use crate::config::Config;
+use crate::delivery::{self, SendContext};
+use crate::error::NotifallError;
+use crate::history::TargetResult;
+use crate::notification::Notification;
+use crate::progress::{ProgressStep, ProgressTask};
+use crate::provider::{DeliveryOutcome, ProviderError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The provider that shows notifications on this machine: what the listener
+/// delivers to and what remote sends fall back to.
+pub const LOCAL_PROVIDER: &str = "macos";
+
+/// What a provider can do with a notification beyond showing its text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Shows action buttons.
+    pub actions: bool,
+    /// Can block until the notification is clicked or answered.
+    pub wait_for_click: bool,
+    /// Delivers attached files, not just their names.
+    pub attachments: bool,
+    /// Plays the notification's sound.
+    pub sound: bool,
+    /// Shows the notification's icon.
+    pub icons: bool,
+    /// Can update a notification it already sent, as `ding progress` does.
+    pub edit: bool,
+    /// Can show a question and return the button chosen, as `ding ask` does.
+    pub ask: bool,
+}
+
+impl Capabilities {
+    /// The supported features by name, as `providers list` shows them.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.wait_for_click, "wait-for-click"),
+            (self.actions, "actions"),
+            (self.sound, "sound"),
+            (self.icons, "icons"),
+            (self.ask, "ask"),
+            (self.edit, "edit"),
+            (self.attachments, "attachments"),
+        ]
+        .into_iter()
+        .filter_map(|(supported, name)| supported.then_some(name))
+        .collect()
+    }
+}
+
+/// A delivery channel `ding send` can pick by name. Implement this and
+/// register it with `ProviderRegistry::register` (or `Ding::register`) to add
+/// a provider; forwarding and `providers list` pick it up from there.
+pub trait ProviderPlugin: Send + Sync {
+    /// The name used for `--provider`, `default_provider`, and
+    /// `forward.targets`.
+    fn name(&self) -> &'static str;
+
+    /// The config table the provider reads, if any.
+    fn config_section(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Whether the provider works on this platform at all.
+    fn available(&self) -> bool {
+        true
+    }
+
+    /// Whether `config` has what the provider needs; the error names the
+    /// missing setting.
+    fn check_config(&self, _config: Option<&Config>) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Delivers `notification`, adding a result to `targets` for each
+    /// provider it tried when there is more than one.
+    fn deliver(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        targets: &mut Vec<TargetResult>,
+    ) -> Result<Option<DeliveryOutcome>, NotifallError>;
+
+    /// Shows `notification` with its actions as buttons and waits up to
+    /// `timeout` for one to be chosen. Returns the label, or `None` if nobody
+    /// answered. Only called when `capabilities().ask` is set.
+    fn ask(
+        &self,
+        _cx: &SendContext,
+        _notification: Notification,
+        _timeout: Duration,
+    ) -> Result<Option<String>, NotifallError> {
+        Err(NotifallError::ProviderUnsupported(self.name().to_string()))
+    }
+
+    /// Shows the latest state of a `ding progress` task. `previous` is what
+    /// the last call returned; a provider that can edit (`capabilities().edit`)
+    /// returns what it needs to find the message next time. By default the
+    /// start and finish are delivered as new notifications and updates are
+    /// skipped, since they would stack up.
+    fn progress(
+        &self,
+        cx: &SendContext,
+        task: &ProgressTask,
+        notification: Notification,
+        _previous: Option<&serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, NotifallError> {
+        if task.step == ProgressStep::Update {
+            return Ok(None);
+        }
+        self.deliver(cx, notification, &mut Vec::new())
+            .map(|_| None)
+    }
+}
+
+/// The providers ding knows by name, in the order `providers list` shows them.
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn ProviderPlugin>>,
+}
+
+impl ProviderRegistry {
+    /// An empty registry, for callers that want only their own providers.
+    pub fn empty() -> Self {
+        Self {
+            providers: Vec::new(),
+        }
+    }
+
+    /// The providers that ship with ding.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register(ForwardPlugin);
+        registry.register(RemotePlugin);
+        registry.register(TelegramPlugin);
+        registry.register(MacosPlugin);
+        registry
+    }
+
+    /// Adds `provider`, replacing any provider with the same name.
+    pub fn register(&mut self, provider: impl ProviderPlugin + 'static) {
+        let provider: Arc<dyn ProviderPlugin> = Arc::new(provider);
+        match self
+            .providers
+            .iter()
+            .position(|p| p.name() == provider.name())
+        {
+            Some(index) => self.providers[index] = provider,
+            None => self.providers.push(provider),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ProviderPlugin> {
+        self.providers
+            .iter()
+            .find(|p| p.name() == name)
+            .map(|p| p.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ProviderPlugin> {
+        self.providers.iter().map(|p| p.as_ref())
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl std::fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|p| p.name()))
+            .finish()
+    }
+}
+
+struct MacosPlugin;
+
+impl ProviderPlugin for MacosPlugin {
+    fn name(&self) -> &'static str {
+        "macos"
+    }
+
+    fn config_section(&self) -> Option<&'static str> {
+        Some("macos")
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            actions: true,
+            wait_for_click: true,
+            attachments: false,
+            sound: true,
+            icons: true,
+            edit: false,
+            ask: true,
+        }
+    }
+
+    fn available(&self) -> bool {
+        cfg!(target_os = "macos")
+    }
+
+    fn deliver(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        targets: &mut Vec<TargetResult>,
+    ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+        delivery::deliver_macos(cx, notification, targets)
+    }
+
+    fn ask(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        timeout: Duration,
+    ) -> Result<Option<String>, NotifallError> {
+        delivery::ask_macos(cx.config, notification, timeout)
+    }
+
+    fn progress(
+        &self,
+        cx: &SendContext,
+        task: &ProgressTask,
+        notification: Notification,
+        _previous: Option<&serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, NotifallError> {
+        delivery::progress_macos(cx, task, notification).map(|_| None)
+    }
+}
+
+struct TelegramPlugin;
+
+impl ProviderPlugin for TelegramPlugin {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn config_section(&self) -> Option<&'static str> {
+        Some("telegram")
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            actions: true,
+            wait_for_click: true,
+            attachments: true,
+            sound: false,
+            icons: false,
+            edit: true,
+            ask: true,
+        }
+    }
+
+    fn check_config(&self, config: Option<&Config>) -> Result<(), ProviderError> {
+        let telegram = config.and_then(|c| c.telegram.as_ref());
+        if telegram.and_then(|t| t.bot_token.as_ref()).is_none() {
+            return Err(ProviderError::not_configured("telegram.bot_token"));
+        }
+        if telegram.and_then(|t| t.chat_id.as_ref()).is_none() {
+            return Err(ProviderError::not_configured("telegram.chat_id"));
+        }
+        Ok(())
+    }
+
+    fn deliver(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        targets: &mut Vec<TargetResult>,
+    ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+        let report = delivery::send_telegram(cx, notification)?;
+        targets.push(TargetResult::ok(self.name(), report.id));
+        Ok(report.outcome)
+    }
+
+    fn ask(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        timeout: Duration,
+    ) -> Result<Option<String>, NotifallError> {
+        delivery::ask_telegram(cx, notification, timeout)
+    }
+
+    fn progress(
+        &self,
+        cx: &SendContext,
+        _task: &ProgressTask,
+        notification: Notification,
+        previous: Option<&serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, NotifallError> {
+        delivery::progress_telegram(cx, notification, previous)
+    }
+}
+
+struct RemotePlugin;
+
+impl ProviderPlugin for RemotePlugin {
+    fn name(&self) -> &'static str {
+        "remote"
+    }
+
+    fn config_section(&self) -> Option<&'static str> {
+        Some("remote")
+    }
+
+    /// What the listener's machine shows, minus the icon, which is a path on
+    /// this one. Answers come back through `ding ask`.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            actions: true,
+            wait_for_click: false,
+            attachments: true,
+            sound: true,
+            icons: false,
+            edit: false,
+            ask: true,
+        }
+    }
+
+    fn check_config(&self, config: Option<&Config>) -> Result<(), ProviderError> {
+        let remote = config.and_then(|c| c.remote.as_ref());
+        if remote.is_some_and(|r| r.host.is_some() || r.url.is_some()) {
+            Ok(())
+        } else {
+            Err(ProviderError::not_configured("remote.host"))
+        }
+    }
+
+    fn deliver(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        targets: &mut Vec<TargetResult>,
+    ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+        delivery::send_remote(cx, notification, targets)
+    }
+
+    fn ask(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        timeout: Duration,
+    ) -> Result<Option<String>, NotifallError> {
+        delivery::ask_remote(cx, notification, timeout)
+    }
+
+    /// The listener shows the task with its local provider.
+    fn progress(
+        &self,
+        cx: &SendContext,
+        task: &ProgressTask,
+        notification: Notification,
+        _previous: Option<&serde_json::Value>,
+    ) -> Result<Option<serde_json::Value>, NotifallError> {
+        delivery::progress_remote(cx, task, notification).map(|_| None)
+    }
+}
+
+struct ForwardPlugin;
+
+impl ProviderPlugin for ForwardPlugin {
+    fn name(&self) -> &'static str {
+        "forward"
+    }
+
+    fn config_section(&self) -> Option<&'static str> {
+        Some("forward")
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            actions: true,
+            wait_for_click: false,
+            attachments: true,
+            sound: false,
+            icons: false,
+            edit: false,
+            ask: false,
+        }
+    }
+
+    fn check_config(&self, config: Option<&Config>) -> Result<(), ProviderError> {
+        let targets = config
+            .and_then(|c| c.forward.as_ref())
+            .and_then(|f| f.targets.as_ref())
+            .filter(|targets| !targets.is_empty());
+        match targets {
+            Some(_) => Ok(()),
+            None => Err(ProviderError::not_configured("forward.targets")),
+        }
+    }
+
+    fn deliver(
+        &self,
+        cx: &SendContext,
+        notification: Notification,
+        targets: &mut Vec<TargetResult>,
+    ) -> Result<Option<DeliveryOutcome>, NotifallError> {
+        delivery::forward(cx, notification, targets)
+    }
+}
@@ -1,176 +1,1318 @@
+use crate::attachment::{self, Attachment};
 use crate::config::TelegramConfig;
 use crate::notification::Notification;
 use crate::provider::{DeliveryOutcome, DeliveryReport, Provider, ProviderError, SendOptions};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_API_URL: &str = "https://api.telegram.org";
+/// Upper bound for a single getUpdates long poll.
+pub const MAX_POLL_SECS: u64 = 25;
+const CALLBACK_PREFIX: &str = "ding:";
+/// Telegram's limits for photo uploads, media captions, and message text.
+const MAX_PHOTO_BYTES: u64 = 10 * 1024 * 1024;
+const MAX_CAPTION_CHARS: usize = 1024;
+const MAX_MESSAGE_CHARS: usize = 4096;
+const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_TIMEOUT_MS: u64 = 15_000;
+const DEFAULT_RETRIES: u32 = 3;
+/// Telegram asks for no more than about one message per second per chat.
+const DEFAULT_MIN_INTERVAL_MS: u64 = 1_000;
+/// Longer rate-limit waits fail instead of blocking the caller.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Pause between polls while waiting on updates that belong to others.
+const SHARED_POLL_DELAY: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Default)]
 pub struct TelegramProvider {
     config: TelegramConfig,
+    queue_dir: Option<PathBuf>,
+}
+
+/// Where a message ding sent ended up, used to match presses and replies.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SentMessage {
+    pub chat_id: i64,
+    pub message_id: i64,
+}
+
+/// Where a message goes: a chat and, in forum supergroups, a topic.
+#[derive(Debug, Clone)]
+struct ChatTarget {
+    chat_id: String,
+    message_thread_id: Option<i64>,
+}
+
+/// A button press on, or a text reply to, a message, or a `/command`.
+/// `from` is the Telegram user ID of whoever sent it.
+#[derive(Debug, Clone)]
+pub enum TelegramInteraction {
+    Button {
+        callback_id: String,
+        message: SentMessage,
+        from: Option<i64>,
+        index: usize,
+    },
+    Reply {
+        message: SentMessage,
+        from: Option<i64>,
+        text: String,
+    },
+    Command {
+        message: SentMessage,
+        from: Option<i64>,
+        name: String,
+        args: String,
+    },
+}
+
+impl TelegramInteraction {
+    /// The message interacted with; for commands, the command message itself.
+    pub fn message(&self) -> SentMessage {
+        match self {
+            Self::Button { message, .. }
+            | Self::Reply { message, .. }
+            | Self::Command { message, .. } => *message,
+        }
+    }
+
+    pub fn from(&self) -> Option<i64> {
+        match self {
+            Self::Button { from, .. } | Self::Reply { from, .. } | Self::Command { from, .. } => {
+                *from
+            }
+        }
+    }
+
+    /// The outcome for `notification`, the notification behind the message.
+    pub fn outcome(&self, notification: &Notification) -> Option<DeliveryOutcome> {
+        match self {
+            Self::Button { index, .. } => notification
+                .actions
+                .get(*index)
+                .map(|action| DeliveryOutcome::ActionButton(action.label.clone())),
+            Self::Reply { text, .. } => Some(DeliveryOutcome::Replied(text.clone())),
+            Self::Command { .. } => None,
+        }
+    }
 }
 
 impl TelegramProvider {
     pub fn new(config: TelegramConfig) -> Result<Self, ProviderError> {
         if config.bot_token.as_deref().unwrap_or("").is_empty() {
-            return Err(ProviderError::Message(
-                "telegram bot_token is not configured".to_string(),
-            ));
+            return Err(ProviderError::not_configured("telegram.bot_token"));
         }
-        if config.chat_id.as_deref().unwrap_or("").is_empty() {
-            return Err(ProviderError::Message(
-                "telegram chat_id is not configured".to_string(),
-            ));
+        let has_named_chats = config.chats.as_ref().is_some_and(|chats| !chats.is_empty());
+        if config.chat_id.as_deref().unwrap_or("").is_empty() && !has_named_chats {
+            return Err(ProviderError::not_configured("telegram.chat_id"));
         }
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            queue_dir: None,
+        })
     }
-}
 
-impl Provider for TelegramProvider {
-    fn name(&self) -> &'static str {
-        "telegram"
+    /// A provider for setup steps that only talk to the Bot API, such as
+    /// finding the chat ID before one is configured. It can't send
+    /// notifications.
+    pub fn without_chat(config: TelegramConfig) -> Result<Self, ProviderError> {
+        if config.bot_token.as_deref().unwrap_or("").is_empty() {
+            return Err(ProviderError::not_configured("telegram.bot_token"));
+        }
+        Ok(Self {
+            config,
+            queue_dir: None,
+        })
     }
 
-    fn send(
-        &self,
-        notification: &Notification,
-        _options: SendOptions,
-    ) -> Result<DeliveryReport, ProviderError> {
-        let token = self
-            .config
-            .bot_token
+    /// Keeps per-chat send state in `dir`, so messages to one chat are sent
+    /// one at a time and spaced out even across ding processes.
+    pub fn with_queue_dir(mut self, dir: PathBuf) -> Self {
+        self.queue_dir = Some(dir);
+        self
+    }
+
+    /// The chat for `notification`: a `[telegram.route]` match by source,
+    /// then by urgency, otherwise the default chat.
+    fn chat_for(&self, notification: &Notification) -> Result<ChatTarget, ProviderError> {
+        let route = self.config.route.as_ref();
+        let by_source = notification
+            .source
             .as_deref()
-            .unwrap_or_default();
-        let chat_id = self
+            .and_then(|source| route?.source.as_ref()?.get(source));
+        let urgency = notification.urgency.unwrap_or_default();
+        let by_urgency = route
+            .and_then(|route| route.urgency.as_ref())
+            .and_then(|routes| routes.get(urgency.as_str()));
+        let Some(name) = by_source.or(by_urgency) else {
+            return match self.config.chat_id.as_deref() {
+                Some(chat_id) if !chat_id.is_empty() => Ok(ChatTarget {
+                    chat_id: chat_id.to_string(),
+                    message_thread_id: self.config.message_thread_id,
+                }),
+                _ => Err(ProviderError::not_configured("telegram.chat_id")),
+            };
+        };
+        let chat = self
+            .config
+            .chats
+            .as_ref()
+            .and_then(|chats| chats.get(name))
+            .ok_or_else(|| ProviderError::not_configured(&format!("telegram.chats.{name}")))?;
+        match chat.chat_id.as_deref() {
+            Some(chat_id) if !chat_id.is_empty() => Ok(ChatTarget {
+                chat_id: chat_id.to_string(),
+                message_thread_id: chat.message_thread_id,
+            }),
+            _ => Err(ProviderError::not_configured(&format!(
+                "telegram.chats.{name}.chat_id"
+            ))),
+        }
+    }
+
+    fn api_url(&self, method: &str) -> String {
+        let base = self
             .config
-            .chat_id
+            .api_url
             .as_deref()
-            .unwrap_or_default();
-        let parse_mode = self.config.parse_mode.as_deref();
-        let silent = self.config.silent.unwrap_or(false);
+            .unwrap_or(DEFAULT_API_URL)
+            .trim_end_matches('/');
+        let token = self.config.bot_token.as_deref().unwrap_or_default();
+        format!("{base}/bot{token}/{method}")
+    }
 
-        let text = build_text(notification, parse_mode)?;
-        if text.is_empty() {
-            return Err(ProviderError::Message(
-                "telegram text is empty".to_string(),
-            ));
+    /// Sends `notification`. URL actions always become inline URL buttons;
+    /// when `interactive`, the other actions become callback buttons whose
+    /// presses are reported by [`TelegramProvider::poll`]. Text over
+    /// Telegram's length limit goes out as several messages. Attachments
+    /// follow as photos or documents, with the text as the first one's
+    /// caption when it fits; the returned message is the one carrying the
+    /// buttons.
+    pub fn send_message(
+        &self,
+        notification: &Notification,
+        interactive: bool,
+    ) -> Result<SentMessage, ProviderError> {
+        let chat = self.chat_for(notification)?;
+        let texts = build_messages(
+            notification,
+            self.config.parse_mode.as_deref(),
+            MAX_MESSAGE_CHARS,
+        );
+        if texts.is_empty() {
+            return Err(ProviderError::Message("telegram text is empty".to_string()));
         }
+        let buttons = notification
+            .actions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, action)| match action.url.as_deref() {
+                Some(url) => Some(serde_json::json!({ "text": action.label, "url": url })),
+                None if interactive => Some(serde_json::json!({
+                    "text": action.label,
+                    "callback_data": format!("{CALLBACK_PREFIX}{i}"),
+                })),
+                None => None,
+            })
+            .collect::<Vec<_>>();
+        let reply_markup =
+            (!buttons.is_empty()).then(|| serde_json::json!({ "inline_keyboard": [buttons] }));
 
-        let url = format!("https://api.telegram.org/bot{token}/sendMessage");
+        let uploads = notification
+            .attachments
+            .iter()
+            .filter(|a| is_available(a))
+            .collect::<Vec<_>>();
+        let mut uploads = uploads.iter();
+        let sent = match (texts.as_slice(), uploads.as_slice()) {
+            ([caption], [first, ..]) if caption.chars().count() <= MAX_CAPTION_CHARS => {
+                let mut fields = self.message_payload(&chat);
+                fields["caption"] = serde_json::Value::String(caption.clone());
+                if let Some(markup) = reply_markup {
+                    fields["reply_markup"] = markup;
+                }
+                uploads.next();
+                self.queued(&chat.chat_id, || self.send_attachment(first, &fields))?
+            }
+            _ => {
+                let mut sent = None;
+                let last = texts.len() - 1;
+                for (i, text) in texts.into_iter().enumerate() {
+                    let mut payload = self.message_payload(&chat);
+                    payload["text"] = serde_json::Value::String(text);
+                    // Buttons go under the final part.
+                    if i == last
+                        && let Some(markup) = reply_markup.clone()
+                    {
+                        payload["reply_markup"] = markup;
+                    }
+                    let message =
+                        self.queued(&chat.chat_id, || self.call("sendMessage", &payload))?;
+                    sent = Some(sent_message(&message, "sendMessage")?);
+                }
+                sent.ok_or_else(|| ProviderError::Message("telegram text is empty".to_string()))?
+            }
+        };
+        for attachment in uploads {
+            let mut fields = self.message_payload(&chat);
+            fields["reply_to_message_id"] = sent.message_id.into();
+            self.queued(&chat.chat_id, || self.send_attachment(attachment, &fields))?;
+        }
+        Ok(sent)
+    }
+
+    /// Uploads one attachment with sendPhoto, or sendDocument for anything
+    /// Telegram won't take as a photo.
+    fn send_attachment(
+        &self,
+        attachment: &Attachment,
+        fields: &serde_json::Value,
+    ) -> Result<SentMessage, ProviderError> {
+        let bytes = attachment
+            .read()
+            .map_err(|err| ProviderError::Message(format!("{}: {err}", attachment.name)))?;
+        let (method, field) = if is_photo(attachment) {
+            ("sendPhoto", "photo")
+        } else {
+            ("sendDocument", "document")
+        };
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let boundary = format!("ding-boundary-{nanos:x}");
+        let mut body = Vec::with_capacity(bytes.len() + 1024);
+        for (name, value) in fields.as_object().into_iter().flatten() {
+            let value = match value {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            body.extend(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{field}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                attachment.name.replace(['"', '\r', '\n'], "_"),
+                attachment.mime
+            )
+            .as_bytes(),
+        );
+        body.extend(&bytes);
+        body.extend(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+        let content_type = format!("multipart/form-data; boundary={boundary}");
+        // Uploads get extra time on top of the usual read timeout.
+        let timeout = self.read_timeout() + Duration::from_secs(120);
+        let message = self.request(
+            method,
+            RequestBody::Multipart(&content_type, &body),
+            timeout,
+        )?;
+        sent_message(&message, method)
+    }
+
+    /// Long-polls getUpdates for up to `timeout_secs` and returns button
+    /// presses on and replies to any message. `offset` tracks confirmed
+    /// updates across calls; every update returned is confirmed on the next
+    /// call, so only one process, such as `ding telegram bot`, should poll
+    /// this way.
+    pub fn poll(
+        &self,
+        offset: &mut Option<i64>,
+        timeout_secs: u64,
+    ) -> Result<Vec<TelegramInteraction>, ProviderError> {
+        let mut interactions = Vec::new();
+        for update in self.get_updates(*offset, timeout_secs)? {
+            *offset = Some(update.update_id + 1);
+            interactions.extend(update.interaction());
+        }
+        Ok(interactions)
+    }
+
+    /// IDs of the chats that messaged the bot in the updates still waiting
+    /// for it. Nothing is confirmed, so `ding telegram bot` still gets them.
+    pub fn recent_chats(&self) -> Result<Vec<i64>, ProviderError> {
+        let mut chats = self
+            .get_updates(None, 0)?
+            .into_iter()
+            .filter_map(|update| update.message.map(|message| message.chat.id))
+            .collect::<Vec<_>>();
+        chats.sort();
+        chats.dedup();
+        Ok(chats)
+    }
+
+    /// Long-polls getUpdates for up to `timeout_secs`, confirming the updates
+    /// before `offset`.
+    fn get_updates(
+        &self,
+        offset: Option<i64>,
+        timeout_secs: u64,
+    ) -> Result<Vec<TelegramUpdate>, ProviderError> {
+        let mut request = serde_json::json!({
+            "timeout": timeout_secs,
+            "allowed_updates": ["callback_query", "message"],
+        });
+        if let Some(offset) = offset {
+            request["offset"] = serde_json::Value::from(offset);
+        }
+        let updates = self.call_with_timeout(
+            "getUpdates",
+            &request,
+            Duration::from_secs(timeout_secs + 10),
+        )?;
+        serde_json::from_value(updates).map_err(|err| ProviderError::Message(err.to_string()))
+    }
+
+    /// Whether an interaction comes from one of the configured chats or from
+    /// a user in `allowed_users`.
+    pub fn is_authorized(&self, interaction: &TelegramInteraction) -> bool {
+        let from_allowed_user = interaction.from().is_some_and(|from| {
+            self.config
+                .allowed_users
+                .as_ref()
+                .is_some_and(|users| users.contains(&from))
+        });
+        let chat_id = interaction.message().chat_id.to_string();
+        let named_chats = self
+            .config
+            .chats
+            .iter()
+            .flat_map(|chats| chats.values())
+            .filter_map(|chat| chat.chat_id.as_deref());
+        from_allowed_user
+            || self
+                .config
+                .chat_id
+                .as_deref()
+                .into_iter()
+                .chain(named_chats)
+                .any(|configured| configured == chat_id)
+    }
+
+    /// Replaces the text of a message sent earlier, leaving it in place. An
+    /// edit can't grow into several messages, so only the first part of
+    /// over-long text is kept.
+    pub fn edit_message(
+        &self,
+        message: SentMessage,
+        notification: &Notification,
+    ) -> Result<(), ProviderError> {
+        let parse_mode = self.config.parse_mode.as_deref();
+        let text = build_messages(notification, parse_mode, MAX_MESSAGE_CHARS)
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Message("telegram text is empty".to_string()))?;
         let mut payload = serde_json::json!({
-            "chat_id": chat_id,
+            "chat_id": message.chat_id,
+            "message_id": message.message_id,
             "text": text,
         });
+        if let Some(mode) = parse_mode
+            && !mode.trim().is_empty()
+        {
+            payload["parse_mode"] = serde_json::Value::String(mode.to_string());
+        }
+        let chat_id = message.chat_id.to_string();
+        match self.queued(&chat_id, || self.call("editMessageText", &payload)) {
+            // Telegram rejects edits that leave the message unchanged.
+            Err(ProviderError::Rejected { body, .. })
+                if body.contains("message is not modified") =>
+            {
+                Ok(())
+            }
+            other => other.map(|_| ()),
+        }
+    }
 
-        if let Some(mode) = parse_mode {
-            if !mode.trim().is_empty() {
-                payload["parse_mode"] = serde_json::Value::String(mode.to_string());
+    /// The bot's username. Succeeds only if Telegram accepts the token.
+    pub fn get_me(&self) -> Result<String, ProviderError> {
+        let me = self.call("getMe", &serde_json::json!({}))?;
+        Ok(me
+            .get("username")
+            .and_then(|username| username.as_str())
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Sends plain text to `chat_id`, e.g. the answer to a bot command.
+    pub fn reply(&self, chat_id: i64, text: &str) -> Result<(), ProviderError> {
+        let payload = serde_json::json!({ "chat_id": chat_id, "text": text });
+        self.queued(&chat_id.to_string(), || self.call("sendMessage", &payload))
+            .map(|_| ())
+    }
+
+    /// Registers the bot's command list (shown in Telegram's command menu).
+    pub fn set_commands(&self, commands: &[(&str, &str)]) -> Result<(), ProviderError> {
+        let commands = commands
+            .iter()
+            .map(|(command, description)| {
+                serde_json::json!({ "command": command, "description": description })
+            })
+            .collect::<Vec<_>>();
+        self.call(
+            "setMyCommands",
+            &serde_json::json!({ "commands": commands }),
+        )
+        .map(|_| ())
+    }
+
+    /// Confirms an interaction: answers a button press with `text` and
+    /// removes the buttons so the message can't be answered twice.
+    pub fn acknowledge(&self, interaction: &TelegramInteraction, text: &str) {
+        if let TelegramInteraction::Button { callback_id, .. } = interaction {
+            let _ = self.call(
+                "answerCallbackQuery",
+                &serde_json::json!({ "callback_query_id": callback_id, "text": text }),
+            );
+        }
+        self.clear_buttons(interaction.message());
+    }
+
+    /// Removes a message's buttons, e.g. once a question has timed out.
+    pub fn clear_buttons(&self, message: SentMessage) {
+        let _ = self.call(
+            "editMessageReplyMarkup",
+            &serde_json::json!({
+                "chat_id": message.chat_id,
+                "message_id": message.message_id,
+                "reply_markup": { "inline_keyboard": [] },
+            }),
+        );
+    }
+
+    /// Polls until `sent` gets a button press (or, if `accept_replies`, a
+    /// reply) or `deadline` passes. Interactions with other messages are left
+    /// alone: confirming an update confirms every earlier one too, so only
+    /// updates for `sent` are confirmed, and only while nobody else's come
+    /// before them. `ding telegram bot` or another waiter still gets theirs.
+    pub fn wait_for_outcome(
+        &self,
+        sent: SentMessage,
+        notification: &Notification,
+        deadline: Option<Instant>,
+        accept_replies: bool,
+    ) -> Result<Option<DeliveryOutcome>, ProviderError> {
+        let mut offset = None;
+        let mut seen = HashSet::new();
+        loop {
+            let poll_secs = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        self.clear_buttons(sent);
+                        return Ok(None);
+                    }
+                    remaining.as_secs().clamp(1, MAX_POLL_SECS)
+                }
+                None => MAX_POLL_SECS,
+            };
+            let updates = match self.get_updates(offset, poll_secs) {
+                Ok(updates) => updates,
+                // Another process polled at the same moment; take turns.
+                Err(ProviderError::Rejected { status: 409, .. }) => {
+                    std::thread::sleep(SHARED_POLL_DELAY);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let mut fresh = false;
+            let mut confirming = true;
+            for update in &updates {
+                let update_id = update.update_id;
+                let interaction = update.interaction().filter(|interaction| {
+                    let message = interaction.message();
+                    message.chat_id == sent.chat_id
+                        && message.message_id == sent.message_id
+                        && self.is_authorized(interaction)
+                        && (accept_replies
+                            || !matches!(interaction, TelegramInteraction::Reply { .. }))
+                });
+                confirming &= interaction.is_some();
+                if confirming {
+                    offset = Some(update_id + 1);
+                }
+                if !seen.insert(update_id) {
+                    continue;
+                }
+                fresh = true;
+                if let Some(interaction) = interaction
+                    && let Some(outcome) = interaction.outcome(notification)
+                {
+                    let label = match &outcome {
+                        DeliveryOutcome::ActionButton(label) => label.as_str(),
+                        _ => "",
+                    };
+                    self.acknowledge(&interaction, label);
+                    return Ok(Some(outcome));
+                }
+            }
+            // Telegram answers at once while unconfirmed updates are waiting,
+            // so don't spin on ones already looked at.
+            if !updates.is_empty() && !fresh {
+                std::thread::sleep(SHARED_POLL_DELAY);
             }
         }
-        if silent {
+    }
+
+    /// Sends `notification` with its actions as inline keyboard buttons and
+    /// waits up to `timeout` for one to be pressed. Returns the chosen label,
+    /// or `None` if nobody answered in time.
+    pub fn ask(
+        &self,
+        notification: &Notification,
+        timeout: Duration,
+    ) -> Result<Option<String>, ProviderError> {
+        let deadline = Instant::now() + timeout;
+        let sent = self.send_message(notification, true)?;
+        match self.wait_for_outcome(sent, notification, Some(deadline), false)? {
+            Some(DeliveryOutcome::ActionButton(label)) => Ok(Some(label)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Fields shared by every message sent to `chat`; the caller adds the
+    /// text, caption, or reply.
+    fn message_payload(&self, chat: &ChatTarget) -> serde_json::Value {
+        let mut payload = serde_json::json!({ "chat_id": chat.chat_id });
+        if let Some(thread) = chat.message_thread_id {
+            payload["message_thread_id"] = thread.into();
+        }
+        if let Some(mode) = self.config.parse_mode.as_deref()
+            && !mode.trim().is_empty()
+        {
+            payload["parse_mode"] = serde_json::Value::String(mode.to_string());
+        }
+        if self.config.silent.unwrap_or(false) {
             payload["disable_notification"] = serde_json::Value::Bool(true);
         }
+        payload
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        payload: &serde_json::Value,
+    ) -> Result<serde_json::Value, ProviderError> {
+        self.call_with_timeout(method, payload, self.read_timeout())
+    }
+
+    /// Calls a Bot API method and returns its `result`.
+    fn call_with_timeout(
+        &self,
+        method: &str,
+        payload: &serde_json::Value,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, ProviderError> {
+        self.request(method, RequestBody::Json(payload), timeout)
+    }
+
+    fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS))
+    }
+
+    /// Sends one Bot API request, retrying failures [`should_retry`] allows:
+    /// rate limits after the wait Telegram asks for, the rest with
+    /// exponential backoff.
+    fn request(
+        &self,
+        method: &str,
+        body: RequestBody,
+        read_timeout: Duration,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let connect_timeout = self
+            .config
+            .connect_timeout_ms
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS);
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_millis(connect_timeout))
+            .timeout_read(read_timeout)
+            .timeout_write(read_timeout)
+            .build();
+        let retries = self.config.retries.unwrap_or(DEFAULT_RETRIES);
+        let mut attempt = 0;
+        loop {
+            let request = agent.post(&self.api_url(method));
+            let response = match body {
+                RequestBody::Json(payload) => request
+                    .set("Content-Type", "application/json")
+                    .send_json(payload.clone()),
+                RequestBody::Multipart(content_type, bytes) => {
+                    request.set("Content-Type", content_type).send_bytes(bytes)
+                }
+            };
+            let unsent = matches!(
+                &response,
+                Err(ureq::Error::Transport(transport))
+                    if matches!(transport.kind(), ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed)
+            );
+            let err = match parse_response(response) {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            let delay = match &err {
+                ProviderError::RateLimited { retry_after } => *retry_after,
+                _ => Duration::from_millis(500 << attempt.min(6)),
+            };
+            if !should_retry(method, &err, unsent) || attempt >= retries || delay > MAX_RETRY_AFTER
+            {
+                return Err(err);
+            }
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
 
-        let response = ureq::post(&url)
-            .set("Content-Type", "application/json")
-            .send_json(payload);
-
-        match response {
-            Ok(res) => {
-                let parsed: TelegramResponse = res
-                    .into_json()
-                    .map_err(|err| ProviderError::Message(err.to_string()))?;
-                if !parsed.ok {
-                    let desc = parsed
-                        .description
-                        .unwrap_or_else(|| "telegram error".to_string());
-                    return Err(ProviderError::Message(desc));
+    /// Runs `send` while holding the lock for `chat_id`, at least
+    /// `min_interval_ms` after the previous message to that chat. The lock
+    /// file records when that was.
+    fn queued<T>(
+        &self,
+        chat_id: &str,
+        send: impl FnOnce() -> Result<T, ProviderError>,
+    ) -> Result<T, ProviderError> {
+        let Some(dir) = self.queue_dir.as_ref() else {
+            return send();
+        };
+        let name = chat_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
                 }
-                let id = parsed
-                    .result
-                    .as_ref()
-                    .map(|r| r.message_id.to_string());
-                Ok(DeliveryReport {
-                    provider: self.name(),
-                    id,
-                    outcome: Some(DeliveryOutcome::Delivered),
-                })
+            })
+            .collect::<String>();
+        let opened = fs::create_dir_all(dir).and_then(|_| {
+            fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(dir.join(format!("{name}.lock")))
+        });
+        let Ok(mut file) = opened else {
+            return send();
+        };
+        // Released when `file` is closed.
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(&file);
+        if unsafe { libc::flock(fd, libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let mut last = String::new();
+        let _ = file.read_to_string(&mut last);
+        let interval = self
+            .config
+            .min_interval_ms
+            .unwrap_or(DEFAULT_MIN_INTERVAL_MS) as u128;
+        if let Ok(last) = last.trim().parse::<u128>() {
+            let wait = (last + interval).saturating_sub(now_millis());
+            if wait > 0 {
+                std::thread::sleep(Duration::from_millis(wait as u64));
             }
-            Err(ureq::Error::Status(code, res)) => {
-                let desc = res
-                    .into_json::<TelegramResponse>()
-                    .ok()
-                    .and_then(|r| r.description)
-                    .unwrap_or_else(|| format!("telegram error status {code}"));
-                Err(ProviderError::Message(desc))
+        }
+
+        let result = send();
+        let _ = file.set_len(0);
+        let _ = file.rewind();
+        let _ = write!(file, "{}", now_millis());
+        result
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RequestBody<'a> {
+    Json(&'a serde_json::Value),
+    /// Content type (with boundary) and the encoded form.
+    Multipart(&'a str, &'a [u8]),
+}
+
+/// Bot API methods that do the same thing however often they arrive.
+const IDEMPOTENT_METHODS: &[&str] = &[
+    "getMe",
+    "getUpdates",
+    "editMessageText",
+    "editMessageReplyMarkup",
+    "answerCallbackQuery",
+    "setMyCommands",
+];
+
+/// Whether a failed `method` call may be sent again. Rate limits always can,
+/// since Telegram refused the request. Timeouts and transport failures can
+/// only when resending is harmless: the method is idempotent, or the request
+/// never left (`unsent`: DNS or connect failures). Otherwise a message that
+/// did arrive would be posted twice.
+fn should_retry(method: &str, err: &ProviderError, unsent: bool) -> bool {
+    match err {
+        ProviderError::RateLimited { .. } => true,
+        err if err.retryable() => unsent || IDEMPOTENT_METHODS.contains(&method),
+        _ => false,
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Unwraps a Bot API response into its `result`.
+fn parse_response(
+    response: Result<ureq::Response, ureq::Error>,
+) -> Result<serde_json::Value, ProviderError> {
+    match response {
+        Ok(res) => {
+            let parsed: TelegramResponse = res.into_json()?;
+            if !parsed.ok {
+                let body = parsed
+                    .description
+                    .unwrap_or_else(|| "telegram error".to_string());
+                return Err(ProviderError::Rejected { status: 200, body });
             }
-            Err(err) => Err(ProviderError::Message(err.to_string())),
+            Ok(parsed.result.unwrap_or_default())
+        }
+        Err(ureq::Error::Status(status, res)) => {
+            let parsed = res.into_json::<TelegramResponse>().ok();
+            let retry_after = parsed
+                .as_ref()
+                .and_then(|r| r.parameters.as_ref())
+                .and_then(|p| p.retry_after);
+            let description = parsed
+                .and_then(|r| r.description)
+                .unwrap_or_else(|| format!("telegram error status {status}"));
+            Err(ProviderError::from_status(
+                status,
+                description,
+                retry_after.map(Duration::from_secs),
+            ))
         }
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl Provider for TelegramProvider {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn send(
+        &self,
+        notification: &Notification,
+        options: SendOptions,
+    ) -> Result<DeliveryReport, ProviderError> {
+        let sent = self.send_message(notification, options.wait_for_click)?;
+        let outcome = if options.wait_for_click {
+            self.wait_for_outcome(sent, notification, None, true)?
+        } else {
+            Some(DeliveryOutcome::Delivered)
+        };
+        Ok(DeliveryReport {
+            provider: self.name(),
+            id: Some(sent.message_id.to_string()),
+            outcome,
+        })
     }
 }
 
 #[derive(Debug, Deserialize)]
 struct TelegramResponse {
     ok: bool,
-    result: Option<TelegramMessage>,
+    result: Option<serde_json::Value>,
     description: Option<String>,
+    parameters: Option<TelegramResponseParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramResponseParameters {
+    retry_after: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramUpdate {
+    update_id: i64,
+    callback_query: Option<TelegramCallbackQuery>,
+    message: Option<TelegramMessage>,
+}
+
+impl TelegramUpdate {
+    /// What the update means to ding, if anything.
+    fn interaction(&self) -> Option<TelegramInteraction> {
+        if let Some(query) = &self.callback_query {
+            let index = query
+                .data
+                .as_deref()
+                .and_then(|data| data.strip_prefix(CALLBACK_PREFIX))
+                .and_then(|i| i.parse::<usize>().ok())?;
+            return Some(TelegramInteraction::Button {
+                callback_id: query.id.clone(),
+                message: query.message.as_ref()?.sent(),
+                from: query.from.as_ref().map(|user| user.id),
+                index,
+            });
+        }
+        let message = self.message.as_ref()?;
+        let from = message.from.as_ref().map(|user| user.id);
+        let text = message.text.clone()?;
+        if let Some(original) = &message.reply_to_message {
+            return Some(TelegramInteraction::Reply {
+                message: original.sent(),
+                from,
+                text,
+            });
+        }
+        let command = text.strip_prefix('/')?;
+        let (name, args) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        // In groups commands may be addressed as /cmd@botname.
+        let name = name.split('@').next().unwrap_or(name);
+        Some(TelegramInteraction::Command {
+            message: message.sent(),
+            from,
+            name: name.to_lowercase(),
+            args: args.trim().to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramCallbackQuery {
+    id: String,
+    from: Option<TelegramUser>,
+    data: Option<String>,
+    message: Option<TelegramMessage>,
 }
 
 #[derive(Debug, Deserialize)]
 struct TelegramMessage {
     message_id: i64,
+    chat: TelegramChat,
+    from: Option<TelegramUser>,
+    text: Option<String>,
+    reply_to_message: Option<Box<TelegramMessage>>,
 }
 
-fn build_text(
-    notification: &Notification,
-    parse_mode: Option<&str>,
-) -> Result<String, ProviderError> {
-    let mut title = notification.title.clone();
-    let mut message = notification.message.clone();
-    let mut link = notification.link.clone().unwrap_or_default();
+#[derive(Debug, Deserialize)]
+struct TelegramChat {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramUser {
+    id: i64,
+}
 
-    let use_markdown = matches!(
-        parse_mode,
-        Some(mode) if mode.eq_ignore_ascii_case("markdownv2")
-    );
+fn sent_message(message: &serde_json::Value, method: &str) -> Result<SentMessage, ProviderError> {
+    let message_id = message.get("message_id").and_then(|id| id.as_i64());
+    let chat_id = message
+        .get("chat")
+        .and_then(|chat| chat.get("id"))
+        .and_then(|id| id.as_i64());
+    match (chat_id, message_id) {
+        (Some(chat_id), Some(message_id)) => Ok(SentMessage {
+            chat_id,
+            message_id,
+        }),
+        _ => Err(ProviderError::Message(format!(
+            "telegram {method} returned no message"
+        ))),
+    }
+}
+
+/// Whether the attachment's contents are here to upload; ones that stayed on
+/// another machine are only named in the text.
+fn is_available(attachment: &Attachment) -> bool {
+    attachment.data.is_some() || attachment.path.as_deref().is_some_and(|p| p.is_file())
+}
 
-    if use_markdown {
-        title = escape_markdown_v2(&title);
-        message = escape_markdown_v2(&message);
-        if !link.is_empty() {
-            link = escape_markdown_v2(&link);
+fn is_photo(attachment: &Attachment) -> bool {
+    matches!(
+        attachment.mime.as_str(),
+        "image/jpeg" | "image/png" | "image/webp"
+    ) && attachment.size <= MAX_PHOTO_BYTES
+}
+
+impl TelegramMessage {
+    fn sent(&self) -> SentMessage {
+        SentMessage {
+            chat_id: self.chat.id,
+            message_id: self.message_id,
         }
     }
+}
 
-    let mut parts = Vec::new();
-    if !title.trim().is_empty() {
-        parts.push(title);
+#[derive(Debug, Clone, Copy)]
+enum Markup {
+    Plain,
+    MarkdownV2,
+    Html,
+}
+
+impl Markup {
+    fn from_parse_mode(parse_mode: Option<&str>) -> Self {
+        match parse_mode.map(str::trim) {
+            Some(mode) if mode.eq_ignore_ascii_case("markdownv2") => Self::MarkdownV2,
+            Some(mode) if mode.eq_ignore_ascii_case("html") => Self::Html,
+            _ => Self::Plain,
+        }
     }
-    if !message.trim().is_empty() {
-        parts.push(message);
+
+    fn escape_char(self, ch: char, out: &mut String) {
+        match (self, ch) {
+            (
+                Self::MarkdownV2,
+                '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|'
+                | '{' | '}' | '.' | '!' | '\\',
+            ) => {
+                out.push('\\');
+                out.push(ch);
+            }
+            (Self::Html, '&') => out.push_str("&amp;"),
+            (Self::Html, '<') => out.push_str("&lt;"),
+            (Self::Html, '>') => out.push_str("&gt;"),
+            _ => out.push(ch),
+        }
     }
-    if !link.trim().is_empty() {
-        parts.push(link);
+
+    fn escape(self, input: &str) -> String {
+        let mut out = String::with_capacity(input.len());
+        for ch in input.chars() {
+            self.escape_char(ch, &mut out);
+        }
+        out
     }
 
-    let mut text = parts.join("\n");
-    if text.len() > 4096 {
-        let suffix = "...";
-        let max = 4096usize.saturating_sub(suffix.len());
-        text = format!("{}{}", text.chars().take(max).collect::<String>(), suffix);
+    fn bold(self, input: &str) -> String {
+        match self {
+            Self::Plain => input.to_string(),
+            Self::MarkdownV2 => format!("*{}*", self.escape(input)),
+            Self::Html => format!("<b>{}</b>", self.escape(input)),
+        }
     }
-    Ok(text)
 }
 
-fn escape_markdown_v2(input: &str) -> String {
-    let mut out = String::with_capacity(input.len());
-    for ch in input.chars() {
-        match ch {
-            '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|' | '{'
-            | '}' | '.' | '!' => {
-                out.push('\\');
-                out.push(ch);
+/// Renders `notification` for `parse_mode`: the title in bold, then the
+/// message, the link, and any attachments that couldn't be uploaded. Text
+/// longer than `limit` is split into several messages, at line breaks where
+/// possible.
+fn build_messages(
+    notification: &Notification,
+    parse_mode: Option<&str>,
+    limit: usize,
+) -> Vec<String> {
+    let markup = Markup::from_parse_mode(parse_mode);
+    let missing = notification
+        .attachments
+        .iter()
+        .filter(|a| !is_available(a))
+        .cloned()
+        .collect::<Vec<_>>();
+    let body = [
+        Some(notification.message.as_str()),
+        notification.link.as_deref(),
+        attachment::hint(&missing).as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|part| !part.trim().is_empty())
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    let mut messages = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    let title = notification.title.trim();
+    if !title.is_empty() {
+        current = markup.bold(&fit_title(title, markup, limit));
+        current_len = current.chars().count();
+    }
+    for line in body.split('\n') {
+        for piece in escape_in_pieces(line, markup, limit) {
+            let piece_len = piece.chars().count();
+            if current_len > 0 && current_len + 1 + piece_len > limit {
+                messages.push(std::mem::take(&mut current));
+                current_len = 0;
             }
-            _ => out.push(ch),
+            if current_len > 0 {
+                current.push('\n');
+                current_len += 1;
+            }
+            current.push_str(&piece);
+            current_len += piece_len;
+        }
+    }
+    messages.push(current);
+    messages
+        .into_iter()
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .collect()
+}
+
+/// `title`, cut short with "…" if it wouldn't fit in `limit` once escaped
+/// and made bold.
+fn fit_title(title: &str, markup: Markup, limit: usize) -> String {
+    if markup.bold(title).chars().count() <= limit {
+        return title.to_string();
+    }
+    let mut budget = limit.saturating_sub(markup.bold("…").chars().count());
+    let mut fitted = String::new();
+    let mut escaped = String::new();
+    for ch in title.chars() {
+        escaped.clear();
+        markup.escape_char(ch, &mut escaped);
+        let len = escaped.chars().count();
+        if len > budget {
+            break;
+        }
+        budget -= len;
+        fitted.push(ch);
+    }
+    fitted.push('…');
+    fitted
+}
+
+/// Escapes `line`, breaking it into pieces that each fit in `limit` without
+/// cutting an escape sequence in half.
+fn escape_in_pieces(line: &str, markup: Markup, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    let mut piece_len = 0;
+    let mut escaped = String::new();
+    for ch in line.chars() {
+        escaped.clear();
+        markup.escape_char(ch, &mut escaped);
+        let len = escaped.chars().count();
+        if piece_len + len > limit {
+            pieces.push(std::mem::take(&mut piece));
+            piece_len = 0;
         }
+        piece.push_str(&escaped);
+        piece_len += len;
+    }
+    pieces.push(piece);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_titles_are_cut_short_not_dropped() {
+        let notification = Notification::new("a.b".repeat(20), "done");
+        let messages = build_messages(&notification, Some("MarkdownV2"), 20);
+        assert_eq!(messages, ["*a\\.ba\\.ba\\.ba\\.ba…*", "done"]);
+
+        let messages = build_messages(&notification, None, 20);
+        assert_eq!(
+            messages,
+            [format!("{}…", &"a.b".repeat(7)[..19]), "done".to_string()]
+        );
+
+        let short = Notification::new("Build", "done");
+        assert_eq!(
+            build_messages(&short, Some("HTML"), 20),
+            ["<b>Build</b>\ndone"]
+        );
+    }
+
+    #[test]
+    fn sends_are_retried_only_when_they_never_left() {
+        let timeout = ProviderError::Timeout("read timed out".to_string());
+        let server = ProviderError::from_status(502, "bad gateway".to_string(), None);
+        for err in [&timeout, &server] {
+            assert!(!should_retry("sendMessage", err, false));
+            assert!(!should_retry("sendDocument", err, false));
+            assert!(should_retry("sendMessage", err, true));
+            assert!(should_retry("editMessageText", err, false));
+            assert!(should_retry("getUpdates", err, false));
+        }
+
+        let limited = ProviderError::RateLimited {
+            retry_after: Duration::from_secs(1),
+        };
+        assert!(should_retry("sendMessage", &limited, false));
+
+        let rejected = ProviderError::Rejected {
+            status: 400,
+            body: "chat not found".to_string(),
+        };
+        assert!(!should_retry("getUpdates", &rejected, true));
+    }
+
+    /// A local Bot API stand-in that answers getUpdates with `updates` not
+    /// yet confirmed, and every other method with a sent message. Returns a
+    /// provider pointed at it and the getUpdates offsets it was asked for.
+    fn stand_in(
+        updates: Vec<serde_json::Value>,
+    ) -> (TelegramProvider, std::sync::mpsc::Receiver<Option<i64>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        let (offsets, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let body: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+                let result = if request.url().ends_with("/getUpdates") {
+                    let offset = body.get("offset").and_then(|o| o.as_i64());
+                    let _ = offsets.send(offset);
+                    let pending = updates
+                        .iter()
+                        .filter(|u| u["update_id"].as_i64() >= offset)
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    serde_json::Value::from(pending)
+                } else {
+                    serde_json::json!({ "message_id": 7, "chat": { "id": 42 } })
+                };
+                let response = serde_json::json!({ "ok": true, "result": result });
+                let _ = request.respond(tiny_http::Response::from_string(response.to_string()));
+            }
+        });
+        let provider = TelegramProvider::new(TelegramConfig {
+            bot_token: Some("T".to_string()),
+            api_url: Some(format!("http://127.0.0.1:{port}")),
+            chat_id: Some("42".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        (provider, received)
+    }
+
+    fn question() -> Notification {
+        Notification {
+            actions: vec![
+                crate::notification::Action {
+                    label: "Approve".to_string(),
+                    url: None,
+                    command: None,
+                },
+                crate::notification::Action {
+                    label: "Deny".to_string(),
+                    url: None,
+                    command: None,
+                },
+            ],
+            ..Notification::new("Deploy?", "to production")
+        }
+    }
+
+    fn press(update_id: i64, message_id: i64, data: &str) -> serde_json::Value {
+        serde_json::json!({
+            "update_id": update_id,
+            "callback_query": {
+                "id": format!("q{update_id}"),
+                "from": { "id": 1 },
+                "data": data,
+                "message": { "message_id": message_id, "chat": { "id": 42 } },
+            },
+        })
+    }
+
+    #[test]
+    fn button_presses_are_read_without_confirming_other_updates() {
+        // Update 1 is a press on someone else's message, e.g. one the bot or
+        // another waiter is waiting for; it must stay unconfirmed.
+        let (provider, offsets) = stand_in(vec![press(1, 99, "ding:0"), press(2, 7, "ding:1")]);
+        let notification = question();
+        let sent = provider.send_message(&notification, true).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let outcome = provider
+            .wait_for_outcome(sent, &notification, Some(deadline), false)
+            .unwrap();
+        assert_eq!(
+            outcome,
+            Some(DeliveryOutcome::ActionButton("Deny".to_string()))
+        );
+        assert!(offsets.try_iter().all(|offset| offset.is_none()));
+    }
+
+    #[test]
+    fn replies_are_read_when_accepted() {
+        let reply = serde_json::json!({
+            "update_id": 5,
+            "message": {
+                "message_id": 8,
+                "chat": { "id": 42 },
+                "from": { "id": 1 },
+                "text": "ship it",
+                "reply_to_message": { "message_id": 7, "chat": { "id": 42 } },
+            },
+        });
+        let (provider, _) = stand_in(vec![reply]);
+        let notification = question();
+        let report = provider
+            .send(
+                &notification,
+                SendOptions {
+                    wait_for_click: true,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            report.outcome,
+            Some(DeliveryOutcome::Replied("ship it".to_string()))
+        );
+    }
+
+    #[test]
+    fn commands_are_parsed_and_only_heard_from_configured_chats() {
+        let update: TelegramUpdate = serde_json::from_value(serde_json::json!({
+            "update_id": 9,
+            "message": {
+                "message_id": 31,
+                "chat": { "id": -1001, "type": "supergroup" },
+                "from": { "id": 5, "is_bot": false, "first_name": "Dev" },
+                "text": "/DND@ding_bot  2h ",
+                "entities": [{ "type": "bot_command", "offset": 0, "length": 13 }],
+            },
+        }))
+        .unwrap();
+        let command = update.interaction().unwrap();
+        let TelegramInteraction::Command { name, args, .. } = &command else {
+            panic!("not a command: {command:?}");
+        };
+        assert_eq!((name.as_str(), args.as_str()), ("dnd", "2h"));
+
+        let config = TelegramConfig {
+            bot_token: Some("T".to_string()),
+            chat_id: Some("42".to_string()),
+            ..Default::default()
+        };
+        let provider = TelegramProvider::new(config.clone()).unwrap();
+        assert!(!provider.is_authorized(&command));
+        let provider = TelegramProvider::new(TelegramConfig {
+            chat_id: Some("-1001".to_string()),
+            ..config.clone()
+        })
+        .unwrap();
+        assert!(provider.is_authorized(&command));
+        let provider = TelegramProvider::new(TelegramConfig {
+            allowed_users: Some(vec![5]),
+            ..config
+        })
+        .unwrap();
+        assert!(provider.is_authorized(&command));
+
+        let plain: TelegramUpdate = serde_json::from_value(serde_json::json!({
+            "update_id": 10,
+            "message": { "message_id": 32, "chat": { "id": 42 }, "text": "hello" },
+        }))
+        .unwrap();
+        assert!(plain.interaction().is_none());
     }
-    out
 }
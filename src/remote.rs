@@ -1,13 +1,35 @@
+use crate::config::RemoteConfig;
 use crate::context::{Context, TerminalContext, TmuxContext};
+use crate::debug::{debug_enabled, debug_log};
 use crate::notification::Notification;
+use crate::progress::ProgressTask;
+use crate::provider::ProviderError;
 use libc::gethostname;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::time::Duration;
+
+/// Largest request body the listener reads, enough for the embedded
+/// attachment budget after base64 encoding.
+pub const MAX_ENVELOPE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The longest Retry-After from the listener that a send will wait out.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+/// Wait before the first retry of a send that never reached the listener;
+/// doubled for each one after.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteEnvelope {
     pub notification: Notification,
     pub context: Option<RemoteContext>,
+    /// For `/ask`: how long the listener waits for an answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// For `ding progress`: the task whose earlier notification this one
+    /// replaces on the listener's machine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<ProgressTask>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -40,6 +62,17 @@ impl RemoteContext {
             terminal,
         }
     }
+
+    /// The tmux/terminal part of this context, as used for click handling.
+    pub fn to_context(&self) -> Option<Context> {
+        if self.tmux.is_none() && self.terminal.is_none() {
+            return None;
+        }
+        Some(Context {
+            tmux: self.tmux.clone(),
+            terminal: self.terminal.clone(),
+        })
+    }
 }
 
 fn local_hostname() -> Option<String> {
@@ -52,3 +85,466 @@ fn local_hostname() -> Option<String> {
     let slice = &buf[..len];
     Some(String::from_utf8_lossy(slice).to_string())
 }
+
+/// Per-send listener settings that win over `[remote]`, e.g. from
+/// command-line flags.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteOverrides {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub token: Option<String>,
+    pub timeout_ms: Option<u64>,
+    pub retries: Option<u32>,
+}
+
+/// Talks to a `ding listen` on another machine.
+#[derive(Debug, Clone)]
+pub struct RemoteClient {
+    url: String,
+    token: Option<String>,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl RemoteClient {
+    /// A client for the listener's `/notify` endpoint at `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            token: None,
+            timeout: Duration::from_millis(2000),
+            retries: 2,
+        }
+    }
+
+    /// A client for the listener in `[remote]`, with `overrides` applied.
+    pub fn from_config(
+        config: Option<&RemoteConfig>,
+        overrides: &RemoteOverrides,
+    ) -> Result<Self, ProviderError> {
+        let config = config.cloned().unwrap_or_default();
+        let (url, _host, _port) = resolve_remote_target(
+            overrides.host.as_deref(),
+            overrides.port,
+            config.host.as_deref(),
+            config.port,
+            config.url.as_deref(),
+        )
+        .ok_or_else(|| ProviderError::not_configured("remote.host"))?;
+        let timeout_ms = overrides.timeout_ms.or(config.timeout_ms).unwrap_or(2000);
+        Ok(Self {
+            url,
+            token: overrides.token.clone().or(config.token),
+            timeout: Duration::from_millis(timeout_ms),
+            retries: overrides.retries.or(config.retries).unwrap_or(2),
+        })
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Delivers `envelope`, retrying failures [`should_retry`] allows: rate
+    /// limits after the wait the listener asks for, the rest with exponential
+    /// backoff.
+    pub fn send(&self, envelope: &RemoteEnvelope) -> Result<(), ProviderError> {
+        debug_log(&format!(
+            "remote_url={} timeout_ms={} retries={}",
+            self.url,
+            self.timeout.as_millis(),
+            self.retries
+        ));
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.timeout)
+            .timeout_read(self.timeout)
+            .build();
+        let body = to_json(envelope)?;
+        let mut attempt = 0;
+
+        loop {
+            let response = self
+                .authorize(agent.post(&self.url))
+                .send_json(body.clone());
+            let unsent = matches!(
+                &response,
+                Err(ureq::Error::Transport(transport))
+                    if matches!(transport.kind(), ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed)
+            );
+            let err = match response {
+                Ok(response) if (200..300).contains(&response.status()) => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.into_string().unwrap_or_default();
+                    ProviderError::from_status(status, body.trim().to_string(), None)
+                }
+                Err(err) => err.into(),
+            };
+            let delay = match &err {
+                ProviderError::RateLimited { retry_after } => *retry_after,
+                _ => RETRY_BACKOFF * 2u32.pow(attempt.min(6)),
+            };
+            attempt += 1;
+            if !should_retry(&err, unsent) || attempt > self.retries || delay > MAX_RETRY_AFTER {
+                return Err(err);
+            }
+            debug_log(&format!("remote attempt {attempt} failed: {err}"));
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Posts an ask envelope to the listener's `/ask` endpoint, which holds
+    /// the request open until the question is answered or `timeout` passes.
+    pub fn ask(
+        &self,
+        envelope: &RemoteEnvelope,
+        timeout: Duration,
+    ) -> Result<Option<String>, ProviderError> {
+        let url = to_ask_url(&self.url);
+        debug_log(&format!("remote_ask_url={url}"));
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.timeout)
+            .timeout_read(timeout + self.timeout)
+            .build();
+        let response: serde_json::Value = self
+            .authorize(agent.post(&url))
+            .send_json(to_json(envelope)?)?
+            .into_json()?;
+        Ok(response
+            .get("action")
+            .and_then(|a| a.as_str())
+            .map(|a| a.to_string()))
+    }
+
+    /// Checks that the listener is up and accepts our token.
+    pub fn ping(&self) -> Result<(), ProviderError> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.timeout)
+            .timeout_read(self.timeout)
+            .build();
+        self.authorize(agent.get(&to_ping_url(&self.url))).call()?;
+        Ok(())
+    }
+
+    fn authorize(&self, request: ureq::Request) -> ureq::Request {
+        let request = request.set("Content-Type", "application/json");
+        match self.token.as_deref() {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request,
+        }
+    }
+}
+
+fn to_json(envelope: &RemoteEnvelope) -> Result<serde_json::Value, ProviderError> {
+    serde_json::to_value(envelope).map_err(|err| ProviderError::Message(err.to_string()))
+}
+
+/// A request the listener accepted: authorized, well-formed, and ready to be
+/// shown.
+#[derive(Debug)]
+pub enum ListenerRequest {
+    Notify(RemoteEnvelope),
+    /// A question; answer it with `Reply::answer`.
+    Ask(RemoteEnvelope),
+}
+
+/// The open HTTP request behind a `ListenerRequest`. It can be moved to
+/// another thread, so slow answers don't hold up other notifications.
+pub struct Reply {
+    request: tiny_http::Request,
+}
+
+impl Reply {
+    pub fn ok(self) {
+        let _ = self
+            .request
+            .respond(json_response(200, r#"{"status":"ok"}"#));
+    }
+
+    pub fn answer(self, action: Option<&str>) {
+        let body = serde_json::json!({ "action": action }).to_string();
+        let _ = self.request.respond(json_response(200, &body));
+    }
+
+    /// Turns the request away with 429, asking the client to come back
+    /// after `retry_after`.
+    pub fn busy(self, message: &str, retry_after: Duration) {
+        let body = serde_json::json!({ "error": message }).to_string();
+        let mut response = json_response(429, &body);
+        let seconds = retry_after.as_secs().max(1).to_string();
+        if let Ok(header) = tiny_http::Header::from_bytes("Retry-After", seconds) {
+            response.add_header(header);
+        }
+        let _ = self.request.respond(response);
+    }
+
+    pub fn error(self, message: &str) {
+        let body = serde_json::json!({ "error": message }).to_string();
+        let _ = self.request.respond(json_response(500, &body));
+    }
+}
+
+/// The HTTP side of `ding listen`: checks the path, method, client address,
+/// and token, then hands each envelope to the caller.
+pub struct Listener {
+    server: tiny_http::Server,
+    token: Option<String>,
+    require_token: bool,
+    allow_hosts: Vec<String>,
+}
+
+impl Listener {
+    /// Listens on `addr` (e.g. "0.0.0.0:4280") without any access checks.
+    pub fn bind(addr: &str) -> Result<Self, std::io::Error> {
+        let server = tiny_http::Server::http(addr).map_err(std::io::Error::other)?;
+        Ok(Self {
+            server,
+            token: None,
+            require_token: false,
+            allow_hosts: Vec::new(),
+        })
+    }
+
+    /// Requires requests to carry `token`, as a bearer token or in
+    /// `X-Ding-Token`. With `require` off, `token` is ignored.
+    pub fn with_token(mut self, token: Option<String>, require: bool) -> Self {
+        self.token = token;
+        self.require_token = require;
+        self
+    }
+
+    /// Only accepts requests from these IP addresses; empty allows all.
+    pub fn with_allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.allow_hosts = hosts;
+        self
+    }
+
+    /// Serves requests until the server shuts down, answering pings and bad
+    /// requests itself.
+    pub fn serve(&self, mut handle: impl FnMut(ListenerRequest, Reply)) {
+        for request in self.server.incoming_requests() {
+            if let Some((accepted, request)) = self.accept(request) {
+                handle(accepted, Reply { request });
+            }
+        }
+    }
+
+    fn accept(
+        &self,
+        mut request: tiny_http::Request,
+    ) -> Option<(ListenerRequest, tiny_http::Request)> {
+        let path = request.url().split('?').next().unwrap_or("").to_string();
+        if debug_enabled() {
+            let remote = request
+                .remote_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            debug_log(&format!("listener request path={path} remote={remote}"));
+        }
+        let reject = |request: tiny_http::Request, status, body| {
+            let _ = request.respond(json_response(status, body));
+            None
+        };
+        let is_ping = path == "/ping";
+        let is_ask = path == "/ask";
+        if path != "/notify" && !is_ask && !is_ping {
+            return reject(request, 404, r#"{"error":"not found"}"#);
+        }
+
+        if !is_ping && request.method() != &tiny_http::Method::Post {
+            return reject(request, 405, r#"{"error":"method not allowed"}"#);
+        }
+
+        if !self.allow_hosts.is_empty()
+            && let Some(remote) = request.remote_addr()
+        {
+            let host = remote.ip().to_string();
+            if !self.allow_hosts.iter().any(|allowed| allowed == &host) {
+                return reject(request, 403, r#"{"error":"forbidden"}"#);
+            }
+        }
+
+        // Pings go through the same checks so `ding remote ping` and
+        // `ding doctor` can tell a wrong token from a dead listener.
+        if self.require_token {
+            let incoming = extract_token(request.headers());
+            if self.token.as_deref() != incoming.as_deref() {
+                return reject(request, 401, r#"{"error":"unauthorized"}"#);
+            }
+        }
+
+        if is_ping {
+            return reject(request, 200, r#"{"status":"ok"}"#);
+        }
+
+        let mut body = String::new();
+        let mut reader = std::io::Read::take(request.as_reader(), MAX_ENVELOPE_BYTES);
+        if std::io::Read::read_to_string(&mut reader, &mut body).is_err() {
+            return reject(request, 400, r#"{"error":"invalid body"}"#);
+        }
+
+        let envelope: RemoteEnvelope = match serde_json::from_str(&body) {
+            Ok(payload) => payload,
+            Err(_) => return reject(request, 400, r#"{"error":"invalid json"}"#),
+        };
+        let accepted = if is_ask {
+            ListenerRequest::Ask(envelope)
+        } else {
+            ListenerRequest::Notify(envelope)
+        };
+        Some((accepted, request))
+    }
+}
+
+pub fn resolve_remote_target(
+    cli_host: Option<&str>,
+    cli_port: Option<u16>,
+    cfg_host: Option<&str>,
+    cfg_port: Option<u16>,
+    cfg_url: Option<&str>,
+) -> Option<(String, String, u16)> {
+    if let Some(host) = cli_host {
+        let port = cli_port.or(cfg_port).unwrap_or(4280);
+        let url = format!("http://{host}:{port}/notify");
+        return Some((url, host.to_string(), port));
+    }
+
+    if let Some(host) = cfg_host {
+        let port = cli_port.or(cfg_port).unwrap_or(4280);
+        let url = format!("http://{host}:{port}/notify");
+        return Some((url, host.to_string(), port));
+    }
+
+    if let Some(url) = cfg_url
+        && let Some((host, port)) = parse_remote_url(url)
+    {
+        let port = cli_port.unwrap_or(port);
+        let url = format!("http://{host}:{port}/notify");
+        return Some((url, host, port));
+    }
+
+    None
+}
+
+pub fn parse_remote_url(url: &str) -> Option<(String, u16)> {
+    let trimmed = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .unwrap_or(url);
+    let host_port = trimmed.split('/').next().unwrap_or(trimmed);
+    if let Some((host, port)) = host_port.rsplit_once(':')
+        && let Ok(port) = port.parse::<u16>()
+    {
+        return Some((host.to_string(), port));
+    }
+    None
+}
+
+fn to_ask_url(url: &str) -> String {
+    to_ping_url(url).trim_end_matches("/ping").to_string() + "/ask"
+}
+
+fn to_ping_url(url: &str) -> String {
+    if url.ends_with("/notify") {
+        return url.trim_end_matches("/notify").to_string() + "/ping";
+    }
+    if url.ends_with('/') {
+        return format!("{url}ping");
+    }
+    format!("{url}/ping")
+}
+
+/// Whether a failed send may be sent again. Rate limits always can, since
+/// the listener refused the envelope. Timeouts and other transient failures
+/// can only when the request never left (`unsent`: DNS or connect failures):
+/// a listener that got the envelope may have shown it already.
+fn should_retry(err: &ProviderError, unsent: bool) -> bool {
+    match err {
+        ProviderError::RateLimited { .. } => true,
+        err => err.retryable() && unsent,
+    }
+}
+
+fn extract_token(headers: &[tiny_http::Header]) -> Option<String> {
+    for header in headers {
+        let name = header.field.as_str().to_string();
+        if name.eq_ignore_ascii_case("authorization") {
+            let value = header.value.as_str();
+            if let Some(token) = value.strip_prefix("Bearer ") {
+                return Some(token.to_string());
+            }
+        }
+        if name.eq_ignore_ascii_case("x-ding-token") {
+            return Some(header.value.as_str().to_string());
+        }
+    }
+    None
+}
+
+fn json_response(status: u16, body: &str) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+    let mut response = tiny_http::Response::from_string(body.to_string());
+    let header = tiny_http::Header::from_bytes("Content-Type", "application/json").ok();
+    if let Some(header) = header {
+        response.add_header(header);
+    }
+    response.with_status_code(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_are_retried_only_when_they_never_reached_the_listener() {
+        let timeout = ProviderError::Timeout("read timed out".to_string());
+        let server = ProviderError::from_status(503, "busy".to_string(), None);
+        for err in [&timeout, &server] {
+            assert!(!should_retry(err, false));
+            assert!(should_retry(err, true));
+        }
+
+        let limited = ProviderError::RateLimited {
+            retry_after: Duration::from_secs(1),
+        };
+        assert!(should_retry(&limited, false));
+
+        let auth = ProviderError::from_status(401, "bad token".to_string(), None);
+        assert!(!should_retry(&auth, true));
+    }
+
+    #[test]
+    fn busy_replies_tell_the_client_when_to_ask_again() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        std::thread::spawn(move || {
+            let request = server.recv().unwrap();
+            Reply { request }.busy("too many questions", Duration::from_secs(30));
+        });
+        let client = RemoteClient::new(format!("http://127.0.0.1:{port}"));
+        let envelope = RemoteEnvelope {
+            notification: Notification::new("Deploy?", ""),
+            context: None,
+            timeout_ms: Some(1000),
+            progress: None,
+        };
+        let err = client.ask(&envelope, Duration::from_secs(1)).unwrap_err();
+        assert!(matches!(
+            err,
+            ProviderError::RateLimited { retry_after } if retry_after == Duration::from_secs(30)
+        ));
+    }
+}
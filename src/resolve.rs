@@ -0,0 +1,494 @@
+use crate::config::{Config, MacosConfig, SourceConfig, TelegramConfig};
+use crate::error::NotifallError;
+use crate::notification::Notification;
+use crate::provider::ProviderError;
+use crate::provider::telegram::TelegramProvider;
+use crate::state::{cache_dir, default_state_dir};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Per-send Telegram settings that win over `[telegram]`, e.g. from
+/// command-line flags.
+#[derive(Debug, Clone, Default)]
+pub struct TelegramOverrides {
+    pub token: Option<String>,
+    /// A chat name from `telegram.chats` or a raw chat id.
+    pub chat: Option<String>,
+    pub parse_mode: Option<String>,
+    pub silent: bool,
+}
+
+/// Picks the provider: an explicit name, then forwarding when it is on, then
+/// `default_provider`, then the platform default.
+pub fn resolve_provider(
+    cli_provider: Option<&str>,
+    config: Option<&Config>,
+) -> Result<String, NotifallError> {
+    if let Some(provider) = cli_provider {
+        return Ok(provider.to_lowercase());
+    }
+    if let Some(enabled) = config
+        .and_then(|c| c.forward.as_ref())
+        .and_then(|f| f.enabled)
+        && enabled
+    {
+        return Ok("forward".to_string());
+    }
+    if let Some(default_provider) = config.and_then(|c| c.default_provider.as_ref()) {
+        return Ok(default_provider.to_lowercase());
+    }
+    if cfg!(target_os = "macos") {
+        return Ok("macos".to_string());
+    }
+    Err(NotifallError::NoProviderAvailable)
+}
+
+/// The providers that `provider_name` delivers to: the forward targets for
+/// "forward", otherwise just the provider itself.
+pub fn expand_provider(
+    config: Option<&Config>,
+    provider_name: &str,
+) -> Result<Vec<String>, NotifallError> {
+    if provider_name != "forward" {
+        return Ok(vec![provider_name.to_string()]);
+    }
+    config
+        .and_then(|c| c.forward.as_ref())
+        .and_then(|f| f.targets.clone())
+        .filter(|targets| !targets.is_empty())
+        .ok_or_else(|| NotifallError::Provider(ProviderError::not_configured("forward.targets")))
+}
+
+/// The `[sources.<name>]` settings for a notification's source.
+pub fn resolve_source_config<'a>(
+    config: Option<&'a Config>,
+    source: Option<&str>,
+) -> Option<&'a SourceConfig> {
+    let source = source?;
+    if let Some(cfg) = config
+        .and_then(|c| c.sources.as_ref())
+        .and_then(|sources| sources.get(source))
+    {
+        return Some(cfg);
+    }
+    None
+}
+
+/// The title to show: an explicit one, the source's display name, or the
+/// capitalized source name.
+pub fn resolve_title(
+    cli_title: Option<String>,
+    source_config: Option<&SourceConfig>,
+    source: Option<&str>,
+) -> String {
+    if let Some(title) = cli_title {
+        return title;
+    }
+    if let Some(display) = source_config.and_then(|cfg| cfg.display_name.as_ref()) {
+        return display.clone();
+    }
+    if let Some(source) = source {
+        return title_from_source(source);
+    }
+    "Ding".to_string()
+}
+
+/// "claude" -> "Claude".
+pub fn title_from_source(source: &str) -> String {
+    let mut chars = source.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => "Ding".to_string(),
+    }
+}
+
+/// An explicit icon, else the source's configured one.
+pub fn resolve_icon(
+    cli_icon: Option<PathBuf>,
+    source_config: Option<&SourceConfig>,
+    source: Option<&str>,
+) -> Option<PathBuf> {
+    if cli_icon.is_some() {
+        return cli_icon;
+    }
+    source_config
+        .and_then(|cfg| cfg.icon.clone())
+        .or_else(|| default_source_icon(source))
+}
+
+/// The macOS settings for a source, with its app bundle (and so its icon)
+/// filled in.
+pub fn resolve_macos_config(
+    config: Option<&Config>,
+    source_config: Option<&SourceConfig>,
+    source: Option<&str>,
+) -> Option<MacosConfig> {
+    let mut macos = config.and_then(|c| c.macos.clone());
+    if let Some(source_cfg) = source_config
+        && source_cfg.app_bundle_id.is_some()
+    {
+        let entry = macos.get_or_insert_with(MacosConfig::default);
+        entry.app_bundle_id = source_cfg.app_bundle_id.clone();
+    }
+    if macos
+        .as_ref()
+        .and_then(|m| m.app_bundle_id.as_ref())
+        .is_none()
+        && let Some(bundle_id) = default_source_bundle_id(source)
+    {
+        let entry = macos.get_or_insert_with(MacosConfig::default);
+        entry.app_bundle_id = Some(bundle_id);
+    }
+    macos
+}
+
+/// A Telegram provider that shares the per-chat send queue with every other
+/// ding process.
+pub fn telegram_provider(
+    telegram_config: TelegramConfig,
+) -> Result<TelegramProvider, ProviderError> {
+    Ok(TelegramProvider::new(telegram_config)?
+        .with_queue_dir(default_state_dir().join("telegram-queue")))
+}
+
+/// The `[telegram]` settings with `overrides` applied. An explicit chat can
+/// name one of `telegram.chats` or be a raw chat id, and turns routing off.
+pub fn resolve_telegram_config(
+    config: Option<&Config>,
+    overrides: &TelegramOverrides,
+) -> TelegramConfig {
+    let mut telegram = config.and_then(|c| c.telegram.clone()).unwrap_or_default();
+
+    if let Some(token) = overrides.token.as_ref() {
+        telegram.bot_token = Some(token.clone());
+    }
+    if let Some(chat) = overrides.chat.as_ref() {
+        let named = telegram
+            .chats
+            .as_ref()
+            .and_then(|chats| chats.get(chat))
+            .cloned();
+        match named {
+            Some(named) => {
+                telegram.chat_id = named.chat_id;
+                telegram.message_thread_id = named.message_thread_id;
+            }
+            None => {
+                telegram.chat_id = Some(chat.clone());
+                telegram.message_thread_id = None;
+            }
+        }
+        // An explicit chat wins over routing.
+        telegram.route = None;
+    }
+    if let Some(parse_mode) = overrides.parse_mode.as_ref() {
+        telegram.parse_mode = Some(parse_mode.clone());
+    }
+    if overrides.silent {
+        telegram.silent = Some(true);
+    }
+
+    telegram
+}
+
+fn default_source_icon(source: Option<&str>) -> Option<PathBuf> {
+    let _ = source?;
+    None
+}
+
+/// Registers app bundles with Launch Services so Notification Center can find
+/// their icons.
+pub const LSREGISTER: &str = "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
+
+/// An app bundle ding generates so an agent's notifications carry its icon.
+struct SourceBundle {
+    source: &'static str,
+    display_name: &'static str,
+    bundle_id: &'static str,
+    icon: &'static [u8],
+}
+
+const SOURCE_BUNDLES: &[SourceBundle] = &[
+    SourceBundle {
+        source: "claude",
+        display_name: "Ding Claude",
+        bundle_id: "com.ding.claude",
+        icon: include_bytes!("../assets/brands/anthropic/claude/icons/claude-symbol-clay.icns"),
+    },
+    SourceBundle {
+        source: "codex",
+        display_name: "Ding Codex",
+        bundle_id: "com.ding.codex",
+        icon: include_bytes!("../assets/brands/codex/icons/openai-blossom-light.icns"),
+    },
+    SourceBundle {
+        source: "gemini",
+        display_name: "Ding Gemini",
+        bundle_id: "com.ding.gemini",
+        icon: include_bytes!("../assets/brands/gemini/icons/gemini-monogram.icns"),
+    },
+    SourceBundle {
+        source: "aider",
+        display_name: "Ding Aider",
+        bundle_id: "com.ding.aider",
+        icon: include_bytes!("../assets/brands/aider/icons/aider-monogram.icns"),
+    },
+    SourceBundle {
+        source: "opencode",
+        display_name: "Ding opencode",
+        bundle_id: "com.ding.opencode",
+        icon: include_bytes!("../assets/brands/opencode/icons/opencode-monogram.icns"),
+    },
+];
+
+/// The generated agent app bundles as (source, bundle id, path), whether or
+/// not they have been created yet.
+pub fn source_bundles() -> Vec<(&'static str, &'static str, PathBuf)> {
+    SOURCE_BUNDLES
+        .iter()
+        .map(|bundle| {
+            (
+                bundle.source,
+                bundle.bundle_id,
+                source_bundle_dir(bundle.source),
+            )
+        })
+        .collect()
+}
+
+fn source_bundle_dir(source: &str) -> PathBuf {
+    cache_dir().join("apps").join(format!("{source}.app"))
+}
+
+fn default_source_bundle_id(source: Option<&str>) -> Option<String> {
+    let source = source?;
+    let bundle = SOURCE_BUNDLES
+        .iter()
+        .find(|bundle| bundle.source == source)?;
+    ensure_source_bundle(
+        bundle.source,
+        bundle.display_name,
+        bundle.bundle_id,
+        bundle.icon,
+    )
+}
+
+fn ensure_source_bundle(
+    source: &str,
+    display_name: &str,
+    bundle_id: &str,
+    icon_bytes: &[u8],
+) -> Option<String> {
+    let app_dir = source_bundle_dir(source);
+    let contents = app_dir.join("Contents");
+    let macos = contents.join("MacOS");
+    let resources = contents.join("Resources");
+
+    if fs::create_dir_all(&macos).is_err() || fs::create_dir_all(&resources).is_err() {
+        return None;
+    }
+
+    let icon_name = format!("{}.icns", source);
+    let icon_path = resources.join(&icon_name);
+    let icon_changed = match write_if_changed(&icon_path, icon_bytes) {
+        Ok(changed) => changed,
+        Err(_) => return None,
+    };
+
+    let plist_path = contents.join("Info.plist");
+    if !plist_path.exists() || icon_changed {
+        let icon_version = icon_bytes
+            .iter()
+            .fold(0u32, |acc, byte| acc.wrapping_add(*byte as u32));
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>CFBundleName</key>
+  <string>{}</string>
+  <key>CFBundleIdentifier</key>
+  <string>{}</string>
+  <key>CFBundleVersion</key>
+  <string>{}</string>
+  <key>CFBundleShortVersionString</key>
+  <string>{}</string>
+  <key>CFBundleExecutable</key>
+  <string>ding-helper</string>
+  <key>CFBundleIconFile</key>
+  <string>{}</string>
+  <key>LSUIElement</key>
+  <true/>
+</dict>
+</plist>
+"#,
+            display_name, bundle_id, icon_version, icon_version, icon_name
+        );
+        if fs::write(&plist_path, plist).is_err() {
+            return None;
+        }
+    }
+
+    let exec_path = macos.join("ding-helper");
+    if !exec_path.exists() {
+        let script = b"#!/bin/sh\nexit 0\n";
+        if fs::write(&exec_path, script).is_err() {
+            return None;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(mut perms) = fs::metadata(&exec_path).map(|m| m.permissions()) {
+                perms.set_mode(0o755);
+                let _ = fs::set_permissions(&exec_path, perms);
+            }
+        }
+    }
+
+    let _ = Command::new(LSREGISTER).arg("-f").arg(&app_dir).status();
+
+    Some(bundle_id.to_string())
+}
+
+/// Image icons are off unless `DING_ALLOW_IMAGE_ICONS=1`; loading them can
+/// crash Notification Center on some macOS versions.
+pub fn allow_image_icons() -> bool {
+    std::env::var("DING_ALLOW_IMAGE_ICONS")
+        .map(|v| v == "1")
+        .unwrap_or(false)
+}
+
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<bool, std::io::Error> {
+    if let Ok(existing) = fs::read(path)
+        && existing == contents
+    {
+        return Ok(false);
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// Default `(title, message)` length limits per provider; `None` means no limit.
+fn default_truncation(provider: &str) -> (Option<usize>, Option<usize>) {
+    match provider {
+        "macos" => (Some(120), Some(300)),
+        "telegram" => (Some(256), Some(3500)),
+        _ => (None, None),
+    }
+}
+
+/// Truncates the title and message to the limits configured for `provider`
+/// (`truncate_title` / `truncate_message`, where 0 disables truncation).
+pub fn truncate_message(
+    config: Option<&Config>,
+    provider: &str,
+    mut notification: Notification,
+) -> Notification {
+    let configured = config.and_then(|c| match provider {
+        "macos" => c
+            .macos
+            .as_ref()
+            .map(|m| (m.truncate_title, m.truncate_message)),
+        "telegram" => c
+            .telegram
+            .as_ref()
+            .map(|t| (t.truncate_title, t.truncate_message)),
+        "remote" => c
+            .remote
+            .as_ref()
+            .map(|r| (r.truncate_title, r.truncate_message)),
+        _ => None,
+    });
+    let (default_title, default_message) = default_truncation(provider);
+    let (title_limit, message_limit) = configured.unwrap_or((None, None));
+    let limit = |configured: Option<usize>, default: Option<usize>| match configured {
+        Some(0) => None,
+        Some(max) => Some(max),
+        None => default,
+    };
+    if let Some(max) = limit(title_limit, default_title) {
+        notification.title = truncate_to(notification.title, max);
+    }
+    if let Some(max) = limit(message_limit, default_message) {
+        notification.message = truncate_to(notification.message, max);
+    }
+    notification
+}
+
+/// Cuts `value` to `max_len` characters, ending in "...".
+pub fn truncate_to(value: String, max_len: usize) -> String {
+    if value.chars().count() <= max_len {
+        return value;
+    }
+    let suffix = "...";
+    let take_len = max_len.saturating_sub(suffix.len());
+    let mut truncated = value.chars().take(take_len).collect::<String>();
+    truncated.push_str(suffix);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long() -> Notification {
+        Notification::new("t".repeat(300), "m".repeat(4000))
+    }
+
+    fn lengths(notification: &Notification) -> (usize, usize) {
+        (
+            notification.title.chars().count(),
+            notification.message.chars().count(),
+        )
+    }
+
+    #[test]
+    fn truncation_defaults_differ_per_provider() {
+        assert_eq!(
+            lengths(&truncate_message(None, "macos", long())),
+            (120, 300)
+        );
+        assert_eq!(
+            lengths(&truncate_message(None, "telegram", long())),
+            (256, 3500)
+        );
+        assert_eq!(
+            lengths(&truncate_message(None, "remote", long())),
+            (300, 4000)
+        );
+        assert!(
+            truncate_message(None, "macos", long())
+                .message
+                .ends_with("...")
+        );
+    }
+
+    #[test]
+    fn truncation_is_configured_per_provider() {
+        let config: Config = toml::from_str(
+            "[macos]\ntruncate_message = 50\n\n[telegram]\ntruncate_title = 0\ntruncate_message = 100\n\n[remote]\ntruncate_title = 20\n",
+        )
+        .unwrap();
+        let config = Some(&config);
+        assert_eq!(
+            lengths(&truncate_message(config, "macos", long())),
+            (120, 50)
+        );
+        assert_eq!(
+            lengths(&truncate_message(config, "telegram", long())),
+            (300, 100)
+        );
+        assert_eq!(
+            lengths(&truncate_message(config, "remote", long())),
+            (20, 4000)
+        );
+    }
+
+    #[test]
+    fn truncation_counts_characters() {
+        assert_eq!(truncate_to("héllo wörld".to_string(), 8), "héllo...");
+        assert_eq!(truncate_to("short".to_string(), 5), "short");
+        assert_eq!(truncate_to("abcdef".to_string(), 2), "...");
+    }
+}
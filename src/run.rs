@@ -0,0 +1,354 @@
+use crate::attachment::Attachment;
+use crate::debug::debug_log;
+use crate::duration::format_duration;
+use crate::state::{ATTACHMENT_MAX_AGE, attachments_dir, prune_dir};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Message for a command that exited 0, unless `--on-success` is given.
+pub const DEFAULT_ON_SUCCESS: &str = "{command} succeeded in {duration}";
+/// Message for a command that failed, unless `--on-failure` is given.
+pub const DEFAULT_ON_FAILURE: &str = "{command} failed with exit {code} after {duration}\n{tail}";
+
+/// A command started by `ding run`. stdin stays the terminal's. When lines
+/// are kept, stdout and stderr are copied through ding, since tools report
+/// failures on either; otherwise they stay the terminal's too.
+///
+/// While the command runs, ding ignores Ctrl-C, which reaches the command
+/// through the terminal, and passes SIGTERM on to it. Either way ding
+/// outlives the command to report how it ended.
+pub struct Running {
+    child: Child,
+    tail: Arc<Mutex<OutputTail>>,
+    pumps: Vec<JoinHandle<()>>,
+    started: Instant,
+}
+
+impl Running {
+    /// Starts `command` (program and arguments), keeping the last
+    /// `keep_lines` lines of its output.
+    pub fn spawn(command: &[String], keep_lines: usize) -> std::io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+        let output = || {
+            if keep_lines > 0 {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            }
+        };
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stdout(output())
+            .stderr(output());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // Ignored signals stay ignored across exec; the command gets
+            // Ctrl-C as usual.
+            unsafe {
+                command.pre_exec(|| {
+                    libc::signal(libc::SIGINT, libc::SIG_DFL);
+                    Ok(())
+                });
+            }
+            signals::watch();
+        }
+        let started = Instant::now();
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                #[cfg(unix)]
+                signals::restore();
+                return Err(err);
+            }
+        };
+        #[cfg(unix)]
+        signals::forward_to(child.id());
+
+        let tail = Arc::new(Mutex::new(OutputTail::new(keep_lines)));
+        let mut pumps = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            let tail = tail.clone();
+            pumps.push(std::thread::spawn(move || {
+                pump_output(stdout, std::io::stdout(), &tail)
+            }));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let tail = tail.clone();
+            pumps.push(std::thread::spawn(move || {
+                pump_output(stderr, std::io::stderr(), &tail)
+            }));
+        }
+        Ok(Self {
+            child,
+            tail,
+            pumps,
+            started,
+        })
+    }
+
+    /// Waits for the command to exit and its output to be copied.
+    pub fn wait(mut self) -> std::io::Result<Finished> {
+        let status = self.child.wait();
+        #[cfg(unix)]
+        signals::restore();
+        let status = status?;
+        for pump in self.pumps {
+            let _ = pump.join();
+        }
+        let lines = self.tail.lock().map(|t| t.lines()).unwrap_or_default();
+        Ok(Finished {
+            code: exit_code(&status),
+            elapsed: self.started.elapsed(),
+            lines,
+        })
+    }
+}
+
+/// How a command run by [`Running`] ended.
+#[derive(Debug, Clone)]
+pub struct Finished {
+    /// Exit code as the shell reports it: 128 plus the signal number when the
+    /// command was killed.
+    pub code: i32,
+    pub elapsed: Duration,
+    /// The last lines of output that were kept.
+    pub lines: Vec<String>,
+}
+
+impl Finished {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+
+    /// The last `count` kept lines, joined.
+    pub fn tail(&self, count: usize) -> String {
+        self.lines[self.lines.len().saturating_sub(count)..].join("\n")
+    }
+
+    /// Expands `template` for this run; see [`expand_template`].
+    pub fn message(&self, template: &str, command: &str, tail_lines: usize) -> String {
+        expand_template(
+            template,
+            command,
+            self.code,
+            self.elapsed,
+            &self.tail(tail_lines),
+        )
+    }
+
+    /// Writes the last `count` kept lines to an `output.log` attachment.
+    /// Returns its path, or `None` when there is nothing to attach.
+    pub fn attach_output(&self, count: usize) -> Option<PathBuf> {
+        if count == 0 || self.lines.is_empty() {
+            return None;
+        }
+        let mut output = self.tail(count);
+        output.push('\n');
+        prune_dir(&attachments_dir(), ATTACHMENT_MAX_AGE);
+        match Attachment::from_bytes(&attachments_dir(), "output.log", output.as_bytes()) {
+            Ok(attachment) => attachment.path,
+            Err(err) => {
+                debug_log(&format!("run output not attached: {err}"));
+                None
+            }
+        }
+    }
+}
+
+/// Fills in `{command}`, `{code}`, `{duration}`, and `{tail}`, and turns a
+/// literal `\n` into a newline.
+pub fn expand_template(
+    template: &str,
+    command: &str,
+    code: i32,
+    elapsed: Duration,
+    tail: &str,
+) -> String {
+    template
+        .replace("\\n", "\n")
+        .replace("{command}", command)
+        .replace("{code}", &code.to_string())
+        .replace("{duration}", &format_duration(elapsed))
+        .replace("{tail}", tail)
+}
+
+/// Keeps the last `max` lines written by a child process.
+#[derive(Debug)]
+pub struct OutputTail {
+    max: usize,
+    lines: VecDeque<String>,
+}
+
+impl OutputTail {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            lines: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: &[u8]) {
+        if self.max == 0 {
+            return;
+        }
+        if self.lines.len() == self.max {
+            self.lines.pop_front();
+        }
+        self.lines
+            .push_back(String::from_utf8_lossy(line).trim_end().to_string());
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+}
+
+/// Copies `input` to `output` as it arrives, recording complete lines in `tail`.
+pub fn pump_output(mut input: impl Read, mut output: impl Write, tail: &Mutex<OutputTail>) {
+    let mut buf = [0u8; 8192];
+    let mut partial = Vec::new();
+    loop {
+        let read = match input.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let _ = output.write_all(&buf[..read]);
+        let _ = output.flush();
+        for &byte in &buf[..read] {
+            if byte != b'\n' {
+                partial.push(byte);
+                continue;
+            }
+            if let Ok(mut tail) = tail.lock() {
+                tail.push(&partial);
+            }
+            partial.clear();
+        }
+    }
+    if !partial.is_empty()
+        && let Ok(mut tail) = tail.lock()
+    {
+        tail.push(&partial);
+    }
+}
+
+/// How ding treats Ctrl-C and SIGTERM while `ding run` waits for the command.
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// The command SIGTERM is passed on to, once it is running.
+    static CHILD: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward(signal: libc::c_int) {
+        let pid = CHILD.load(Ordering::SeqCst);
+        if pid > 0 {
+            unsafe {
+                libc::kill(pid, signal);
+            }
+        }
+    }
+
+    /// Ignores Ctrl-C and catches SIGTERM until [`restore`].
+    pub fn watch() {
+        let handler = forward as extern "C" fn(libc::c_int);
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_IGN);
+            libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        }
+    }
+
+    pub fn forward_to(pid: u32) {
+        CHILD.store(pid as i32, Ordering::SeqCst);
+    }
+
+    pub fn restore() {
+        CHILD.store(0, Ordering::SeqCst);
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGTERM, libc::SIG_DFL);
+        }
+    }
+}
+
+/// The exit code a shell would report for `status`.
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_fill_in_the_run() {
+        let message = expand_template(
+            "{command} failed with exit {code} after {duration}\\n{tail}",
+            "cargo test",
+            101,
+            Duration::from_secs(75),
+            "test result: FAILED",
+        );
+        assert_eq!(
+            message,
+            "cargo test failed with exit 101 after 1m 15s\ntest result: FAILED"
+        );
+
+        let finished = Finished {
+            code: 0,
+            elapsed: Duration::from_secs(3),
+            lines: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        };
+        assert_eq!(finished.message("{tail}", "make", 2), "b\nc");
+        assert_eq!(
+            finished.message(DEFAULT_ON_SUCCESS, "make", 2),
+            "make succeeded in 3.0s"
+        );
+    }
+
+    #[test]
+    fn output_tail_keeps_the_last_lines_of_both_streams() {
+        let tail = Mutex::new(OutputTail::new(3));
+        let mut copied = Vec::new();
+        pump_output(&b"one\r\ntwo\nthree\n"[..], &mut copied, &tail);
+        pump_output(&b"four\nno newline"[..], &mut copied, &tail);
+        assert_eq!(copied, b"one\r\ntwo\nthree\nfour\nno newline");
+        assert_eq!(
+            tail.lock().unwrap().lines(),
+            ["three", "four", "no newline"]
+        );
+
+        let mut none = OutputTail::new(0);
+        none.push(b"dropped");
+        assert!(none.lines().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exit_codes_match_the_shell() {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(exit_code(&ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(&ExitStatus::from_raw(libc::SIGINT)), 130);
+        assert_eq!(exit_code(&ExitStatus::from_raw(libc::SIGTERM)), 143);
+    }
+}
@@ -0,0 +1,822 @@
+use crate::config::Config;
+use crate::provider::registry::ProviderRegistry;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+/// What a config key holds, for validation and `ding config set`.
+#[derive(Debug)]
+pub enum Kind {
+    String,
+    /// One of a fixed set of strings, compared case-insensitively.
+    Choice(&'static [&'static str]),
+    /// A registered provider name.
+    Provider,
+    /// A provider name `forward.targets` can send to.
+    Target,
+    /// A macOS sound name, "default", or "none".
+    Sound,
+    /// A name from `telegram.chats`.
+    Chat,
+    Bool,
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Path {
+        must_exist: bool,
+    },
+    List(&'static Kind),
+    Table(&'static [Field]),
+    /// A table with user-chosen keys, limited to `keys` when set.
+    Map {
+        keys: Option<&'static [&'static str]>,
+        value: &'static Kind,
+    },
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+const fn field(name: &'static str, kind: Kind) -> Field {
+    Field { name, kind }
+}
+
+const PORT: Kind = Kind::Integer { min: 1, max: 65535 };
+const COUNT: Kind = Kind::Integer {
+    min: 0,
+    max: i64::MAX,
+};
+const RETRIES: Kind = Kind::Integer {
+    min: 0,
+    max: u32::MAX as i64,
+};
+const ID: Kind = Kind::Integer {
+    min: i64::MIN,
+    max: i64::MAX,
+};
+const ICON: Kind = Kind::Path { must_exist: true };
+const URGENCIES: &[&str] = &["low", "normal", "high"];
+
+/// Every key `Config` reads. Keep in step with the structs in `config.rs`.
+pub static CONFIG: Kind = Kind::Table(&[
+    field("default_provider", Kind::Provider),
+    field(
+        "macos",
+        Kind::Table(&[
+            field("sound", Kind::Sound),
+            field("app_bundle_id", Kind::String),
+            field("icon", ICON),
+            field("truncate_title", COUNT),
+            field("truncate_message", COUNT),
+        ]),
+    ),
+    field(
+        "remote",
+        Kind::Table(&[
+            field("host", Kind::String),
+            field("port", PORT),
+            field("url", Kind::String),
+            field("token", Kind::String),
+            field("timeout_ms", COUNT),
+            field("retries", RETRIES),
+            field("fallback_to_local", Kind::Bool),
+            field("truncate_title", COUNT),
+            field("truncate_message", COUNT),
+        ]),
+    ),
+    field(
+        "listener",
+        Kind::Table(&[
+            field("bind", Kind::String),
+            field("port", PORT),
+            field("token", Kind::String),
+            field("require_token", Kind::Bool),
+            field("prefix_hostname", Kind::Bool),
+            field("allow_hosts", Kind::List(&Kind::String)),
+            field("on_click", Kind::String),
+            field("allow_remote_commands", Kind::Bool),
+        ]),
+    ),
+    field(
+        "forward",
+        Kind::Table(&[
+            field("enabled", Kind::Bool),
+            field("targets", Kind::List(&Kind::Target)),
+        ]),
+    ),
+    field(
+        "telegram",
+        Kind::Table(&[
+            field("bot_token", Kind::String),
+            field("api_url", Kind::String),
+            field("allowed_users", Kind::List(&ID)),
+            field("chat_id", Kind::String),
+            field("message_thread_id", ID),
+            field(
+                "parse_mode",
+                Kind::Choice(&["MarkdownV2", "Markdown", "HTML"]),
+            ),
+            field("silent", Kind::Bool),
+            field("truncate_title", COUNT),
+            field("truncate_message", COUNT),
+            field("connect_timeout_ms", COUNT),
+            field("timeout_ms", COUNT),
+            field("retries", RETRIES),
+            field("min_interval_ms", COUNT),
+            field(
+                "chats",
+                Kind::Map {
+                    keys: None,
+                    value: &Kind::Table(&[
+                        field("chat_id", Kind::String),
+                        field("message_thread_id", ID),
+                    ]),
+                },
+            ),
+            field(
+                "route",
+                Kind::Table(&[
+                    field(
+                        "source",
+                        Kind::Map {
+                            keys: None,
+                            value: &Kind::Chat,
+                        },
+                    ),
+                    field(
+                        "urgency",
+                        Kind::Map {
+                            keys: Some(URGENCIES),
+                            value: &Kind::Chat,
+                        },
+                    ),
+                ]),
+            ),
+        ]),
+    ),
+    field(
+        "sources",
+        Kind::Map {
+            keys: None,
+            value: &Kind::Table(&[
+                field("icon", ICON),
+                field("app_bundle_id", Kind::String),
+                field("display_name", Kind::String),
+            ]),
+        },
+    ),
+    field(
+        "history",
+        Kind::Table(&[
+            field("enabled", Kind::Bool),
+            field("path", Kind::Path { must_exist: false }),
+            field("max_entries", COUNT),
+            field("max_age_days", COUNT),
+        ]),
+    ),
+    field(
+        "watch",
+        Kind::Table(&[field(
+            "codex",
+            Kind::Table(&[
+                field("sessions_dir", Kind::Path { must_exist: false }),
+                field(
+                    "events",
+                    Kind::List(&Kind::Choice(&[
+                        "failed",
+                        "approval",
+                        "file-change",
+                        "plan",
+                        "complete",
+                    ])),
+                ),
+            ]),
+        )]),
+    ),
+    field(
+        "hook_mappings",
+        Kind::Map {
+            keys: None,
+            value: &Kind::Table(&[
+                field("event", Kind::String),
+                field("subtype", Kind::String),
+                field("source", Kind::String),
+                field(
+                    "events",
+                    Kind::Map {
+                        keys: None,
+                        value: &Kind::Table(&[
+                            field("title", Kind::String),
+                            field("message", Kind::String),
+                            field("urgency", Kind::Choice(URGENCIES)),
+                            field("tag", Kind::String),
+                            field("skip", Kind::Bool),
+                            field(
+                                "metadata",
+                                Kind::Map {
+                                    keys: None,
+                                    value: &Kind::String,
+                                },
+                            ),
+                        ]),
+                    },
+                ),
+            ]),
+        },
+    ),
+]);
+
+/// The kind of the value at dotted `key`, e.g. "telegram.chats.alerts.chat_id",
+/// or `None` if the config has no such key.
+pub fn lookup(key: &str) -> Option<&'static Kind> {
+    let mut kind = &CONFIG;
+    for part in key.split('.') {
+        kind = match kind {
+            Kind::Table(fields) => &fields.iter().find(|field| field.name == part)?.kind,
+            Kind::Map { keys, value } => {
+                if keys.is_some_and(|keys| !keys.contains(&part)) {
+                    return None;
+                }
+                value
+            }
+            _ => return None,
+        };
+    }
+    Some(kind)
+}
+
+/// Parses a command-line value as `kind`: `true`/`false` for flags, numbers
+/// for integers, comma-separated items for lists, and text for the rest, so
+/// `config set telegram.chat_id 123` stays a string. Range and name checks
+/// are left to [`validate`].
+pub fn coerce(kind: &Kind, raw: &str) -> Result<Value, String> {
+    let expected = || format!("must be {}, not {raw:?}", kind.describe());
+    match kind {
+        Kind::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" => Ok(Value::from(true)),
+            "false" | "no" | "off" => Ok(Value::from(false)),
+            _ => Err(expected()),
+        },
+        Kind::Integer { .. } => raw
+            .trim()
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| expected()),
+        Kind::List(inner) => {
+            let mut array = toml_edit::Array::new();
+            for item in raw
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+            {
+                array.push(coerce(inner, item)?);
+            }
+            Ok(Value::Array(array))
+        }
+        Kind::Table(_) | Kind::Map { .. } => {
+            Err("is a table; set one of its keys instead".to_string())
+        }
+        _ => Ok(Value::from(raw)),
+    }
+}
+
+/// The sounds macOS ships with.
+const SYSTEM_SOUNDS: &[&str] = &[
+    "Basso",
+    "Blow",
+    "Bottle",
+    "Frog",
+    "Funk",
+    "Glass",
+    "Hero",
+    "Morse",
+    "Ping",
+    "Pop",
+    "Purr",
+    "Sosumi",
+    "Submarine",
+    "Tink",
+];
+
+/// A problem in a config file, located by line and column (both from 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The dotted key it concerns, e.g. "remote.port" or "forward.targets[1]".
+    pub key: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Checks config file `contents` against [`CONFIG`]: unknown keys, wrong
+/// types, out-of-range numbers, unknown providers, chats, parse modes and
+/// sounds, and missing icon files.
+pub fn validate(contents: &str, registry: &ProviderRegistry) -> Vec<Diagnostic> {
+    let doc = match ImDocument::parse(contents) {
+        Ok(doc) => doc,
+        Err(err) => {
+            let span = err.span().unwrap_or(0..0);
+            let message = err.message().trim_end().replace('\n', "; ");
+            return vec![diagnostic(contents, "", span, &message)];
+        }
+    };
+    let mut validator = Validator {
+        contents,
+        registry,
+        chats: doc
+            .get("telegram")
+            .and_then(|t| t.get("chats"))
+            .and_then(Item::as_table_like)
+            .map(|chats| chats.iter().map(|(name, _)| name.to_string()).collect())
+            .unwrap_or_default(),
+        diagnostics: Vec::new(),
+    };
+    let Kind::Table(fields) = &CONFIG else {
+        unreachable!("the config schema is a table");
+    };
+    validator.table(doc.as_table(), fields, "");
+
+    // The schema should catch everything serde would reject; this is the
+    // safety net if the two drift apart.
+    if validator.diagnostics.is_empty()
+        && let Err(err) = toml::from_str::<Config>(contents)
+    {
+        let span = err.span().unwrap_or(0..0);
+        validator
+            .diagnostics
+            .push(diagnostic(contents, "", span, err.message()));
+    }
+    validator.diagnostics
+}
+
+struct Validator<'a> {
+    contents: &'a str,
+    registry: &'a ProviderRegistry,
+    chats: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, key: &str, span: Option<Range<usize>>, message: String) {
+        let span = span.unwrap_or(0..0);
+        self.diagnostics
+            .push(diagnostic(self.contents, key, span, &message));
+    }
+
+    fn table(&mut self, table: &dyn TableLike, fields: &[Field], path: &str) {
+        for (key, item) in table.iter() {
+            let key_span = table.key(key).and_then(|k| k.span());
+            let full = join(path, key);
+            match fields.iter().find(|field| field.name == key) {
+                Some(field) => self.item(item, &field.kind, &full, key_span),
+                None => {
+                    let place = if path.is_empty() {
+                        "at the top level".to_string()
+                    } else {
+                        format!("in [{path}]")
+                    };
+                    let names: Vec<&str> = fields.iter().map(|field| field.name).collect();
+                    let message = format!("unknown key `{key}` {place}{}", suggest(key, &names));
+                    self.report(&full, key_span, message);
+                }
+            }
+        }
+    }
+
+    fn item(&mut self, item: &Item, kind: &Kind, path: &str, key_span: Option<Range<usize>>) {
+        let span = item.span().or(key_span);
+        match kind {
+            Kind::Table(fields) => match item.as_table_like() {
+                Some(table) => self.table(table, fields, path),
+                None => self.report(path, span, format!("`{path}` must be a table")),
+            },
+            Kind::Map { keys, value } => {
+                let Some(table) = item.as_table_like() else {
+                    return self.report(path, span, format!("`{path}` must be a table"));
+                };
+                for (key, item) in table.iter() {
+                    let key_span = table.key(key).and_then(|k| k.span());
+                    let full = join(path, key);
+                    if let Some(keys) = keys
+                        && !keys.contains(&key)
+                    {
+                        let message = format!(
+                            "unknown key `{key}` in [{path}]; expected one of {}",
+                            keys.join(", ")
+                        );
+                        self.report(&full, key_span, message);
+                        continue;
+                    }
+                    self.item(item, value, &full, key_span);
+                }
+            }
+            Kind::List(inner) => match item.as_array() {
+                Some(array) => {
+                    for (index, value) in array.iter().enumerate() {
+                        self.value(value, inner, &format!("{path}[{index}]"));
+                    }
+                }
+                None => self.report(path, span, format!("`{path}` must be an array")),
+            },
+            _ => match item.as_value() {
+                Some(value) => self.value(value, kind, path),
+                None => self.report(path, span, format!("`{path}` must be {}", kind.describe())),
+            },
+        }
+    }
+
+    fn value(&mut self, value: &Value, kind: &Kind, path: &str) {
+        if let Err(message) = self.check(value, kind) {
+            self.report(path, value.span(), format!("`{path}` {message}"));
+        }
+    }
+
+    fn check(&self, value: &Value, kind: &Kind) -> Result<(), String> {
+        let expected = || format!("must be {}", kind.describe());
+        match kind {
+            Kind::Bool => value.as_bool().map(|_| ()).ok_or_else(expected),
+            Kind::Integer { min, max } => {
+                let n = value.as_integer().ok_or_else(expected)?;
+                if n < *min || n > *max {
+                    return Err(format!("must be between {min} and {max}, not {n}"));
+                }
+                Ok(())
+            }
+            Kind::String => value.as_str().map(|_| ()).ok_or_else(expected),
+            Kind::Path { must_exist } => {
+                let path = value.as_str().ok_or_else(expected)?;
+                if *must_exist && !Path::new(path).exists() {
+                    return Err(format!("points at {path}, which doesn't exist"));
+                }
+                Ok(())
+            }
+            Kind::Choice(options) => {
+                let text = value.as_str().ok_or_else(expected)?;
+                one_of(text, options)
+            }
+            Kind::Provider | Kind::Target => {
+                let name = value.as_str().ok_or_else(expected)?;
+                let names: Vec<&str> = self
+                    .registry
+                    .iter()
+                    .map(|provider| provider.name())
+                    .filter(|provider| matches!(kind, Kind::Provider) || *provider != "forward")
+                    .collect();
+                one_of(name, &names)
+            }
+            Kind::Chat => {
+                let name = value.as_str().ok_or_else(expected)?;
+                if self.chats.iter().any(|chat| chat == name) {
+                    return Ok(());
+                }
+                let names: Vec<&str> = self.chats.iter().map(String::as_str).collect();
+                Err(format!(
+                    "names chat {name:?}, which is not in [telegram.chats]{}",
+                    suggest(name, &names)
+                ))
+            }
+            Kind::Sound => {
+                let sound = value.as_str().ok_or_else(expected)?;
+                let mut names = vec!["default", "none", "off", "silent"];
+                names.extend(SYSTEM_SOUNDS);
+                let installed = installed_sounds();
+                names.extend(installed.iter().map(String::as_str));
+                one_of(sound, &names)
+            }
+            Kind::List(_) | Kind::Table(_) | Kind::Map { .. } => Err(expected()),
+        }
+    }
+}
+
+impl Kind {
+    /// How the kind reads in "must be ..." messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Bool => "true or false",
+            Self::Integer { .. } => "an integer",
+            Self::List(_) => "an array",
+            Self::Table(_) | Self::Map { .. } => "a table",
+            Self::Path { .. } => "a path",
+            _ => "a string",
+        }
+    }
+}
+
+fn one_of(text: &str, options: &[&str]) -> Result<(), String> {
+    if options
+        .iter()
+        .any(|option| option.eq_ignore_ascii_case(text))
+    {
+        return Ok(());
+    }
+    match suggest(text, options) {
+        suggestion if !suggestion.is_empty() => Err(format!("can't be {text:?}{suggestion}")),
+        _ => Err(format!(
+            "can't be {text:?}; expected one of {}",
+            options.join(", ")
+        )),
+    }
+}
+
+/// Custom sounds from the macOS sound folders, by file stem.
+fn installed_sounds() -> Vec<String> {
+    let mut dirs = vec![
+        PathBuf::from("/Library/Sounds"),
+        PathBuf::from("/System/Library/Sounds"),
+    ];
+    if let Ok(home) = std::env::var("HOME") {
+        dirs.push(PathBuf::from(home).join("Library/Sounds"));
+    }
+    dirs.iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect()
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// " (did you mean `x`?)" for the closest of `names` to a typo, or nothing.
+fn suggest(typo: &str, names: &[&str]) -> String {
+    let typo = typo.to_lowercase();
+    names
+        .iter()
+        .map(|name| (edit_distance(&typo, &name.to_lowercase()), name))
+        .filter(|(distance, name)| *distance <= (name.len() / 3).max(2) && *distance < name.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| format!(" (did you mean `{name}`?)"))
+        .unwrap_or_default()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn diagnostic(contents: &str, key: &str, span: Range<usize>, message: &str) -> Diagnostic {
+    let before = &contents[..span.start.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|text| text.chars().count() + 1)
+        .unwrap_or(1);
+    Diagnostic {
+        key: key.to_string(),
+        line,
+        column,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        CodexWatchConfig, ForwardConfig, HistoryConfig, HookEventConfig, HookMappingConfig,
+        ListenerConfig, MacosConfig, RemoteConfig, SourceConfig, TelegramChatConfig,
+        TelegramConfig, TelegramRouteConfig, WatchConfig,
+    };
+    use crate::notification::Urgency;
+    use std::collections::BTreeMap;
+
+    fn check(contents: &str) -> Vec<Diagnostic> {
+        validate(contents, &ProviderRegistry::builtin())
+    }
+
+    fn map<V>(key: &str, value: V) -> Option<BTreeMap<String, V>> {
+        Some(BTreeMap::from([(key.to_string(), value)]))
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_offending_value() {
+        let diagnostics = check("[remote]\nhost = \"mac\"\n  port = 0\n");
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.key, "remote.port");
+        assert_eq!((diagnostic.line, diagnostic.column), (3, 10));
+        assert_eq!(
+            diagnostic.to_string(),
+            "3:10: `remote.port` must be between 1 and 65535, not 0"
+        );
+    }
+
+    #[test]
+    fn parse_errors_carry_a_location() {
+        let diagnostics = check("[telegram]\nchat_id = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].key, "");
+        assert_eq!(diagnostics[0].line, 2);
+    }
+
+    #[test]
+    fn unknown_keys_suggest_the_closest_name() {
+        let diagnostics = check("[telegram]\nbot_tokn = \"x\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].key, "telegram.bot_tokn");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 1));
+        assert_eq!(
+            diagnostics[0].message,
+            "unknown key `bot_tokn` in [telegram] (did you mean `bot_token`?)"
+        );
+
+        let diagnostics = check("default_provider = \"telegarm\"\n");
+        assert!(
+            diagnostics[0]
+                .message
+                .ends_with("(did you mean `telegram`?)"),
+            "{}",
+            diagnostics[0].message
+        );
+
+        let diagnostics = check("wibble = 1\n");
+        assert_eq!(
+            diagnostics[0].message,
+            "unknown key `wibble` at the top level"
+        );
+    }
+
+    #[test]
+    fn coerce_follows_the_key_kind() {
+        let value = |key: &str, raw: &str| coerce(lookup(key).unwrap(), raw).map(|v| v.to_string());
+        assert_eq!(value("remote.fallback_to_local", "yes").unwrap(), "true");
+        assert_eq!(value("listener.require_token", "Off").unwrap(), "false");
+        assert_eq!(value("remote.port", " 4415 ").unwrap(), "4415");
+        assert_eq!(value("telegram.chat_id", "123").unwrap(), "\"123\"");
+        assert_eq!(
+            value("forward.targets", "telegram, remote,").unwrap(),
+            "[\"telegram\", \"remote\"]"
+        );
+        assert_eq!(
+            value("remote.port", "many").unwrap_err(),
+            "must be an integer, not \"many\""
+        );
+        assert_eq!(
+            value("history.enabled", "maybe").unwrap_err(),
+            "must be true or false, not \"maybe\""
+        );
+        assert!(value("telegram.chats", "x").is_err());
+        assert!(lookup("telegram.chats.alerts.chat_id").is_some());
+        assert!(lookup("telegram.route.urgency.urgent").is_none());
+    }
+
+    #[test]
+    fn coerce_splits_lists_and_checks_each_item() {
+        let list = |key: &str, raw: &str| coerce(lookup(key).unwrap(), raw).map(|v| v.to_string());
+        assert_eq!(
+            list("telegram.allowed_users", "1, 2,,3").unwrap(),
+            "[1, 2, 3]"
+        );
+        assert_eq!(list("telegram.allowed_users", " , ").unwrap(), "[]");
+        assert_eq!(
+            list("telegram.allowed_users", "1, me").unwrap_err(),
+            "must be an integer, not \"me\""
+        );
+        assert_eq!(list("listener.allow_hosts", "mac").unwrap(), "[\"mac\"]");
+        assert_eq!(
+            list("watch.codex.events", "failed,plan").unwrap(),
+            "[\"failed\", \"plan\"]"
+        );
+    }
+
+    /// Every field is spelled out, so a field added to `Config` without a
+    /// schema entry fails to compile here or fails validation.
+    #[test]
+    fn a_fully_populated_config_validates() {
+        let icon = Some(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"));
+        let config = Config {
+            default_provider: Some("forward".to_string()),
+            macos: Some(MacosConfig {
+                sound: Some("Ping".to_string()),
+                app_bundle_id: Some("com.example.app".to_string()),
+                icon: icon.clone(),
+                truncate_title: Some(60),
+                truncate_message: Some(200),
+            }),
+            remote: Some(RemoteConfig {
+                host: Some("mac.local".to_string()),
+                port: Some(4415),
+                url: Some("http://mac.local:4415".to_string()),
+                token: Some("sekret".to_string()),
+                timeout_ms: Some(3000),
+                retries: Some(2),
+                fallback_to_local: Some(true),
+                truncate_title: Some(60),
+                truncate_message: Some(200),
+            }),
+            listener: Some(ListenerConfig {
+                bind: Some("0.0.0.0".to_string()),
+                port: Some(4415),
+                token: Some("sekret".to_string()),
+                require_token: Some(true),
+                prefix_hostname: Some(true),
+                allow_hosts: Some(vec!["10.0.0.2".to_string()]),
+                on_click: Some("open -a Terminal".to_string()),
+                allow_remote_commands: Some(false),
+            }),
+            forward: Some(ForwardConfig {
+                enabled: Some(true),
+                targets: Some(vec!["telegram".to_string(), "remote".to_string()]),
+            }),
+            telegram: Some(TelegramConfig {
+                bot_token: Some("123:abc".to_string()),
+                api_url: Some("https://api.telegram.org".to_string()),
+                allowed_users: Some(vec![42]),
+                chat_id: Some("-100123".to_string()),
+                message_thread_id: Some(7),
+                parse_mode: Some("MarkdownV2".to_string()),
+                silent: Some(false),
+                truncate_title: Some(60),
+                truncate_message: Some(200),
+                connect_timeout_ms: Some(2000),
+                timeout_ms: Some(10000),
+                retries: Some(3),
+                min_interval_ms: Some(1000),
+                chats: map(
+                    "alerts",
+                    TelegramChatConfig {
+                        chat_id: Some("-100456".to_string()),
+                        message_thread_id: Some(9),
+                    },
+                ),
+                route: Some(TelegramRouteConfig {
+                    source: map("claude", "alerts".to_string()),
+                    urgency: map("high", "alerts".to_string()),
+                }),
+            }),
+            sources: map(
+                "claude",
+                SourceConfig {
+                    icon,
+                    app_bundle_id: Some("com.example.claude".to_string()),
+                    display_name: Some("Claude".to_string()),
+                },
+            ),
+            history: Some(HistoryConfig {
+                enabled: Some(true),
+                path: Some(PathBuf::from("/tmp/ding-history.jsonl")),
+                max_entries: Some(500),
+                max_age_days: Some(14),
+            }),
+            watch: Some(WatchConfig {
+                codex: Some(CodexWatchConfig {
+                    sessions_dir: Some(PathBuf::from("/tmp/codex-sessions")),
+                    events: Some(vec!["failed".to_string(), "complete".to_string()]),
+                }),
+            }),
+            hook_mappings: map(
+                "ci",
+                HookMappingConfig {
+                    event: Some("/event".to_string()),
+                    subtype: Some("/status".to_string()),
+                    source: Some("ci".to_string()),
+                    events: map(
+                        "build.failed",
+                        HookEventConfig {
+                            title: Some("Build failed".to_string()),
+                            message: Some("{/branch}".to_string()),
+                            urgency: Some(Urgency::High),
+                            tag: Some("ci".to_string()),
+                            skip: Some(false),
+                            metadata: map("branch", "{/branch}".to_string()),
+                        },
+                    ),
+                },
+            ),
+        };
+
+        let contents = toml::to_string(&config).unwrap();
+        assert_eq!(check(&contents), Vec::new(), "{contents}");
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long stored attachments are kept.
+pub const ATTACHMENT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How many stored attachments are kept, however recent.
+pub const MAX_STORED_ATTACHMENTS: usize = 200;
+
+/// Where ding keeps history, do-not-disturb, progress, and queue state.
+pub fn default_state_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_STATE_HOME") {
+        return PathBuf::from(dir).join("ding");
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return PathBuf::from(home).join(".local/state/ding");
+    }
+    std::env::temp_dir().join("ding")
+}
+
+/// Where ding keeps files it can regenerate, like the per-agent app bundles.
+pub fn cache_dir() -> PathBuf {
+    std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .unwrap_or_else(|_| std::env::temp_dir())
+        .join("ding")
+}
+
+/// Where stdin attachments, `ding run` output, and attachments received by
+/// the listener are kept.
+pub fn attachments_dir() -> PathBuf {
+    default_state_dir().join("attachments")
+}
+
+/// Removes files in `dir` last modified more than `max_age` ago.
+pub fn prune_dir(dir: &Path, max_age: Duration) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Removes the least recently modified files in `dir` until at most `keep`
+/// are left.
+pub fn prune_dir_to(dir: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some((metadata.modified().ok()?, entry.path()))
+        })
+        .collect::<Vec<_>>();
+    if files.len() <= keep {
+        return;
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - keep] {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Follows a file by name like `tail -F`: survives the file being missing,
+/// truncated, or replaced by a new file (log rotation).
+#[derive(Debug)]
+pub struct FileFollower {
+    path: PathBuf,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    pos: u64,
+    partial: Vec<u8>,
+    from_start: bool,
+}
+
+impl FileFollower {
+    /// When `from_start` is false, lines already in the file when it is first
+    /// opened are skipped. Files that appear later are always read in full.
+    pub fn new(path: impl Into<PathBuf>, from_start: bool) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+            identity: None,
+            pos: 0,
+            partial: Vec::new(),
+            from_start,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the complete lines appended since the last poll.
+    pub fn poll(&mut self) -> std::io::Result<Vec<String>> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                // Either not created yet (read it in full once it appears) or
+                // rotated away and not yet recreated (keep draining the old handle).
+                self.from_start = true;
+                return self.read_available();
+            }
+            Err(err) => return Err(err),
+        };
+
+        let identity = file_identity(&metadata);
+        let rotated = self.identity.is_some() && self.identity != identity;
+        if self.file.is_none() || rotated {
+            let mut lines = if rotated {
+                self.read_available()?
+            } else {
+                Vec::new()
+            };
+            let mut file = File::open(&self.path)?;
+            self.pos = if self.file.is_none() && !rotated && !self.from_start {
+                file.seek(SeekFrom::End(0))?
+            } else {
+                0
+            };
+            self.file = Some(file);
+            self.identity = identity;
+            self.partial.clear();
+            self.from_start = true;
+            lines.extend(self.read_available()?);
+            return Ok(lines);
+        }
+
+        if metadata.len() < self.pos {
+            // Truncated in place (copytruncate rotation).
+            self.pos = 0;
+            self.partial.clear();
+            if let Some(file) = self.file.as_mut() {
+                file.seek(SeekFrom::Start(0))?;
+            }
+        }
+        self.read_available()
+    }
+
+    fn read_available(&mut self) -> std::io::Result<Vec<String>> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(Vec::new());
+        };
+        let mut buf = Vec::new();
+        let read = file.read_to_end(&mut buf)?;
+        self.pos += read as u64;
+
+        let mut lines = Vec::new();
+        for byte in buf {
+            if byte == b'\n' {
+                lines.push(
+                    String::from_utf8_lossy(&self.partial)
+                        .trim_end()
+                        .to_string(),
+                );
+                self.partial.clear();
+            } else {
+                self.partial.push(byte);
+            }
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Suppresses repeats of the same key seen within `window`.
+#[derive(Debug)]
+pub struct Deduper {
+    window: Duration,
+    seen: HashMap<String, Instant>,
+}
+
+impl Deduper {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns true if `key` should be notified (i.e. it is not a recent duplicate).
+    pub fn check(&mut self, key: &str) -> bool {
+        if self.window.is_zero() {
+            return true;
+        }
+        let now = Instant::now();
+        self.seen
+            .retain(|_, seen| now.duration_since(*seen) < self.window);
+        if self.seen.contains_key(key) {
+            return false;
+        }
+        self.seen.insert(key.to_string(), now);
+        true
+    }
+}
+
+pub fn pid_alive(pid: i32) -> bool {
+    let res = unsafe { libc::kill(pid, 0) };
+    if res == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+pub fn process_name(pid: i32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+    Some(name)
+}
+
+pub fn find_pids(name: &str) -> Vec<i32> {
+    let output = match Command::new("pgrep").args(["-x", name]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<i32>().ok())
+        .filter(|pid| *pid != std::process::id() as i32)
+        .collect()
+}
+
+/// `ding watch file`: the lines appended to a followed file that match a
+/// pattern, with repeats inside the dedupe window dropped.
+#[derive(Debug)]
+pub struct MatchWatcher {
+    follower: FileFollower,
+    pattern: regex::Regex,
+    deduper: Deduper,
+}
+
+impl MatchWatcher {
+    pub fn new(follower: FileFollower, pattern: regex::Regex, dedupe_window: Duration) -> Self {
+        Self {
+            follower,
+            pattern,
+            deduper: Deduper::new(dedupe_window),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.follower.path()
+    }
+
+    /// Matching lines appended since the last poll.
+    pub fn poll(&mut self) -> std::io::Result<Vec<String>> {
+        Ok(self
+            .follower
+            .poll()?
+            .into_iter()
+            .filter(|line| self.pattern.is_match(line) && self.deduper.check(line))
+            .collect())
+    }
+}
+
+/// Blocks until process `pid` exits, checking every `interval`. Returns how
+/// long it was watched, or `None` when no such process was running.
+pub fn wait_for_pid(pid: i32, interval: Duration) -> Option<Duration> {
+    if !pid_alive(pid) {
+        return None;
+    }
+    let started = Instant::now();
+    while pid_alive(pid) {
+        std::thread::sleep(interval);
+    }
+    Some(started.elapsed())
+}
+
+/// Blocks until no process named `name` is left, checking every `interval`.
+/// Returns how long it was watched, or `None` when none was running.
+pub fn wait_for_proc_exit(name: &str, interval: Duration) -> Option<Duration> {
+    if find_pids(name).is_empty() {
+        return None;
+    }
+    let started = Instant::now();
+    while !find_pids(name).is_empty() {
+        std::thread::sleep(interval);
+    }
+    Some(started.elapsed())
+}
+
+/// Blocks until a process named `name` is running again.
+pub fn wait_for_proc_start(name: &str, interval: Duration) {
+    while find_pids(name).is_empty() {
+        std::thread::sleep(interval);
+    }
+}